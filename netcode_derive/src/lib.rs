@@ -1,9 +1,121 @@
+use std::collections::HashMap;
+
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{Data, DeriveInput, Error, Fields, Ident, Index, parse_macro_input};
+use quote::{ToTokens, quote};
+use syn::{
+    Attribute, Data, DataEnum, DeriveInput, Error, Field, Fields, Ident, Index, LitInt,
+    parse_macro_input,
+};
+
+/// Options parsed from `#[netcode(...)]` attributes.
+#[derive(Default)]
+struct NetcodeAttrs {
+    skip: bool,      // `#[netcode(skip)]`, field is not sent over the wire.
+    tag: Option<u8>, // `#[netcode(tag = N)]`, explicit tag byte for an enum variant.
+}
+
+/// Parses the `netcode` attributes. Errors on unknown options or tags that do not fit in a `u8`.
+fn parse_attrs(attrs: &[Attribute]) -> Result<NetcodeAttrs, Error> {
+    let mut out = NetcodeAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("netcode")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                out.skip = true;
+                Ok(())
+            } else if meta.path.is_ident("tag") {
+                let lit: LitInt = meta.value()?.parse()?;
+                let tag = lit
+                    .base10_parse::<u8>()
+                    .map_err(|_| Error::new_spanned(&lit, "netcode tag must be within 0..=255"))?;
+                out.tag = Some(tag);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported netcode option, expected `skip` or `tag`"))
+            }
+        })?;
+    }
+
+    Ok(out)
+}
+
+/// Checks if a field is marked with `#[netcode(skip)]`.
+fn is_skipped(field: &Field) -> Result<bool, Error> {
+    let attrs = parse_attrs(&field.attrs)?;
+    if attrs.tag.is_some() {
+        return Err(Error::new_spanned(
+            field,
+            "#[netcode(tag = N)] can only be placed on enum variants",
+        ));
+    }
+
+    Ok(attrs.skip)
+}
+
+/// Ensures `netcode` attributes are placed where they apply. `skip` is only valid on struct
+/// fields, not on the type itself (such as a unit struct) or on enum variant fields.
+fn validate_attrs(ast: &DeriveInput) -> Result<(), Error> {
+    if ast.attrs.iter().any(|attr| attr.path().is_ident("netcode")) {
+        return Err(Error::new_spanned(
+            &ast.ident,
+            "#[netcode(skip)] can only be placed on struct fields",
+        ));
+    }
+
+    if let Data::Enum(data_enum) = &ast.data {
+        for field in data_enum.variants.iter().flat_map(|v| v.fields.iter()) {
+            if is_skipped(field)? {
+                return Err(Error::new_spanned(
+                    field,
+                    "#[netcode(skip)] is not supported on enum variant fields",
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Obtains the tag byte for each variant. Variants without `#[netcode(tag = N)]` use their
+/// declaration index. Errors if two variants share a tag.
+fn variant_tags(data_enum: &DataEnum) -> Result<Vec<u8>, Error> {
+    let mut seen: HashMap<u8, &Ident> = HashMap::new();
+    let mut tags = Vec::with_capacity(data_enum.variants.len());
+
+    for (idx, variant) in data_enum.variants.iter().enumerate() {
+        let attrs = parse_attrs(&variant.attrs)?;
+        if attrs.skip {
+            return Err(Error::new_spanned(
+                &variant.ident,
+                "#[netcode(skip)] cannot be placed on enum variants",
+            ));
+        }
+
+        let tag = match attrs.tag {
+            Some(tag) => tag,
+            None => u8::try_from(idx).map_err(|_| {
+                Error::new_spanned(
+                    &variant.ident,
+                    "enums with more than 256 variants need explicit #[netcode(tag = N)]",
+                )
+            })?,
+        };
+
+        if let Some(other) = seen.insert(tag, &variant.ident) {
+            return Err(Error::new_spanned(
+                &variant.ident,
+                format!("netcode tag {tag} is already used by variant `{other}`"),
+            ));
+        }
+
+        tags.push(tag);
+    }
+
+    Ok(tags)
+}
 
 /// Derive NetEncode, convert a struct or enum into a byte vector for network transmission.
-#[proc_macro_derive(NetEncode)]
+/// Fields marked with `#[netcode(skip)]` are not encoded.
+#[proc_macro_derive(NetEncode, attributes(netcode))]
 pub fn derive_net_encode(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     match impl_net_encode(&ast) {
@@ -12,34 +124,82 @@ pub fn derive_net_encode(input: TokenStream) -> TokenStream {
     }
 }
 
-/// Generates `impl NetEncoder for T` with `fn encode(&self) -> Vec<u8>`.
+/// Generates `impl NetEncoder for T` with `fn encode(self) -> Vec<u8>` and the fallible
+/// `fn try_encode(self) -> Result<Vec<u8>>`.
 fn impl_net_encode(ast: &DeriveInput) -> Result<TokenStream, Error> {
     let name = &ast.ident;
+    validate_attrs(ast)?;
+
+    let (encode, try_encode) = (quote! { encode() }, quote! { try_encode()? });
+    let infallible = encode_body(ast, &encode)?;
+    let fallible = encode_body(ast, &try_encode)?;
 
-    let encode_body = match &ast.data {
+    // Wrap the bodies in the final `impl NetEncoder for #name { fn encode(...) { ... } }`.
+    let expanded = quote! {
+        #[doc = "Automatically generated implementation of the `NetEncoder` trait."]
+        #[doc = "Encodes this type into a newly allocated `Vec<u8>`."]
+        #[automatically_derived]
+        impl NetEncoder for #name {
+            /// Encodes the value into a byte vector for network transmission.
+            #[inline(always)]
+            fn encode(self) -> ::std::vec::Vec<u8> {
+                let mut out = ::std::vec::Vec::new();
+                #infallible
+                out
+            }
+
+            /// Encodes the value, failing if any field cannot be represented on the wire.
+            fn try_encode(
+                self,
+            ) -> ::std::result::Result<::std::vec::Vec<u8>, crate::net::error::NetError> {
+                let mut out = ::std::vec::Vec::new();
+                #fallible
+                Ok(out)
+            }
+        }
+    };
+
+    Ok(expanded.into())
+}
+
+/// Generates the statements that encode every field into `out`, calling `call` on each field,
+/// such as `encode()`.
+fn encode_body(ast: &DeriveInput, call: &impl ToTokens) -> Result<impl ToTokens, Error> {
+    let name = &ast.ident;
+    let body = match &ast.data {
         // Structs: named, unnamed, and unit encoding.
         Data::Struct(data_struct) => {
             match &data_struct.fields {
                 // Fields within the struct are named, like struct Foo { x: T, y: U };
                 Fields::Named(fields) => {
-                    let recurse = fields.named.iter().map(|f| {
-                        let field_name = &f.ident;
-                        quote! {
-                            out.extend(self.#field_name.encode());
+                    let mut recurse = vec![];
+                    for f in &fields.named {
+                        if is_skipped(f)? {
+                            continue;
                         }
-                    });
+
+                        let field_name = &f.ident;
+                        recurse.push(quote! {
+                            out.extend(self.#field_name.#call);
+                        });
+                    }
 
                     quote! { #(#recurse)* }
                 }
 
                 // Struct is tuple-like, struct Foo(T, U);
                 Fields::Unnamed(fields) => {
-                    let recurse = fields.unnamed.iter().enumerate().map(|(i, _)| {
-                        let index = Index::from(i);
-                        quote! {
-                            out.extend(self.#index.encode());
+                    let mut recurse = vec![];
+                    for (i, f) in fields.unnamed.iter().enumerate() {
+                        if is_skipped(f)? {
+                            continue;
                         }
-                    });
+
+                        let index = Index::from(i);
+                        recurse.push(quote! {
+                            out.extend(self.#index.#call);
+                        });
+                    }
 
                     quote! { #(#recurse)* }
                 }
@@ -54,9 +214,10 @@ fn impl_net_encode(ast: &DeriveInput) -> Result<TokenStream, Error> {
         // Enums: named, unnamed, and unit encoding.
         Data::Enum(data_enum) => {
             // Create a match arm for each variant.
-            let arms = data_enum.variants.iter().enumerate().map(|(idx, variant)| {
+            let tags = variant_tags(data_enum)?;
+            let arms = data_enum.variants.iter().zip(tags).map(|(variant, tag)| {
                 let var_ident = &variant.ident;
-                let variant_idx = idx as u8; // Tag ID for the variant.
+                let variant_idx = tag; // Tag ID for the variant.
 
                 match &variant.fields {
                     // Fields within the Enum arm are named, like enum Foo::Bar { x: T, y: U };
@@ -69,7 +230,7 @@ fn impl_net_encode(ast: &DeriveInput) -> Result<TokenStream, Error> {
 
                         let expansions = names.iter().map(|name| {
                             quote! {
-                                out.extend(#name.encode());
+                                out.extend(#name.#call);
                             }
                         });
 
@@ -91,7 +252,7 @@ fn impl_net_encode(ast: &DeriveInput) -> Result<TokenStream, Error> {
                         // For each field in the variant, generate code similar to `out.extend(f0.encode());`
                         let expansions = vars.iter().map(|var| {
                             quote! {
-                                out.extend(#var.encode());
+                                out.extend(#var.#call);
                             }
                         });
 
@@ -131,28 +292,13 @@ fn impl_net_encode(ast: &DeriveInput) -> Result<TokenStream, Error> {
         }
     };
 
-    // Wrap that body in the final `impl NetEncoder for #name { fn encode(...) { ... } }`.
-    let expanded = quote! {
-        #[doc = "Automatically generated implementation of the `NetEncoder` trait."]
-        #[doc = "Encodes this type into a newly allocated `Vec<u8>`."]
-        #[automatically_derived]
-        impl NetEncoder for #name {
-            /// Encodes the value into a byte vector for network transmission.
-            #[inline(always)]
-            fn encode(self) -> ::std::vec::Vec<u8> {
-                let mut out = ::std::vec::Vec::new();
-                #encode_body
-                out
-            }
-        }
-    };
-
-    Ok(expanded.into())
+    Ok(body)
 }
 
 /// Entry point for `#[derive(NetDecode)]`.
 /// Derive NetDecode, convert from Vec<u8> to a struct or enum.
-#[proc_macro_derive(NetDecode)]
+/// Fields marked with `#[netcode(skip)]` consume no bytes and are set to `Default::default()`.
+#[proc_macro_derive(NetDecode, attributes(netcode))]
 pub fn derive_net_decode(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     match impl_net_decode(&ast) {
@@ -162,8 +308,11 @@ pub fn derive_net_decode(input: TokenStream) -> TokenStream {
 }
 
 /// Generates `impl NetDecoder for #name` that returns `(Self, usize)`.
+/// Every field draws from one shared `DecodeBudget`, so `offset` never moves past the end of
+/// `data` and nested length prefixes are checked against what the whole payload has left.
 fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
     let name = &ast.ident;
+    validate_attrs(ast)?;
 
     let decode_body = match &ast.data {
         // Structs: named, unnamed, and unit encoding.
@@ -177,13 +326,20 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
                         .map(|f| f.ident.as_ref().unwrap())
                         .collect();
 
-                    let decode_fields = names.iter().map(|fname| {
-                        quote! {
-                            let (temp_val, used) = NetDecoder::decode(&data[offset..])?;
-                            offset += used;
-                            let #fname = temp_val;
-                        }
-                    });
+                    let mut decode_fields = vec![];
+                    for (f, fname) in fields.named.iter().zip(&names) {
+                        decode_fields.push(if is_skipped(f)? {
+                            quote! {
+                                let #fname = ::std::default::Default::default();
+                            }
+                        } else {
+                            quote! {
+                                let (temp_val, used) = NetDecoder::decode_budgeted(&data[offset..], budget)?;
+                                offset += used;
+                                let #fname = temp_val;
+                            }
+                        });
+                    }
 
                     quote! {
                         let mut offset = 0usize;
@@ -205,12 +361,19 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
                         .map(|i| quote::format_ident!("f{}", i))
                         .collect();
 
-                    let decode_steps = vars.iter().map(|fv| {
-                        quote! {
-                            let (#fv, used) = NetDecoder::decode(&data[offset..])?;
-                            offset += used;
-                        }
-                    });
+                    let mut decode_steps = vec![];
+                    for (f, fv) in fields_unnamed.unnamed.iter().zip(&vars) {
+                        decode_steps.push(if is_skipped(f)? {
+                            quote! {
+                                let #fv = ::std::default::Default::default();
+                            }
+                        } else {
+                            quote! {
+                                let (#fv, used) = NetDecoder::decode_budgeted(&data[offset..], budget)?;
+                                offset += used;
+                            }
+                        });
+                    }
 
                     quote! {
                         let mut offset = 0usize;
@@ -235,9 +398,10 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
 
         // Enums: named, unnamed, and unit encoding.
         Data::Enum(data_enum) => {
-            let variant_arms = data_enum.variants.iter().enumerate().map(|(idx, variant)| {
+            let tags = variant_tags(data_enum)?;
+            let variant_arms = data_enum.variants.iter().zip(tags).map(|(variant, tag)| {
                 let var_ident = &variant.ident;
-                let tag_value = idx as u8;
+                let tag_value = tag;
 
                 match &variant.fields {
                     // Fields within the Enum arm are named, like enum Foo::Bar { x: T, y: U };
@@ -250,7 +414,7 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
 
                         let decode_fields = idents.iter().map(|ident| {
                             quote! {
-                                let (temp_val, used) = NetDecoder::decode(&data[offset..])?;
+                                let (temp_val, used) = NetDecoder::decode_budgeted(&data[offset..], budget)?;
                                 offset += used;
                                 let #ident = temp_val;
                             }
@@ -276,7 +440,7 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
 
                         let decode_steps = vars.iter().map(|fv| {
                             quote! {
-                                let (#fv, used) = NetDecoder::decode(&data[offset..])?;
+                                let (#fv, used) = NetDecoder::decode_budgeted(&data[offset..], budget)?;
                                 offset += used;
                             }
                         });
@@ -309,6 +473,7 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
                     );
                 }
                 let tag = data[offset];
+                budget.consume(1)?;
                 offset += 1;
 
                 match tag {
@@ -340,6 +505,14 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
         impl NetDecoder for #name {
             #[inline(always)]
             fn decode(data: &[u8]) -> ::std::result::Result<(Self, usize), crate::net::error::NetError> {
+                Self::decode_budgeted(data, &mut crate::net::traits::DecodeBudget::new(data.len()))
+            }
+
+            #[allow(unused_variables)] // Unit structs read nothing.
+            fn decode_budgeted(
+                data: &[u8],
+                budget: &mut crate::net::traits::DecodeBudget,
+            ) -> ::std::result::Result<(Self, usize), crate::net::error::NetError> {
                 #decode_body
             }
         }
@@ -347,3 +520,70 @@ fn impl_net_decode(ast: &DeriveInput) -> Result<TokenStream, Error> {
 
     Ok(expanded.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn skip_is_accepted_on_struct_fields() {
+        let named: DeriveInput = parse_quote! {
+            struct Named { x: f32, #[netcode(skip)] cached: f32 }
+        };
+        let tuple: DeriveInput = parse_quote! {
+            struct Tuple(f32, #[netcode(skip)] f32);
+        };
+
+        assert!(validate_attrs(&named).is_ok());
+        assert!(validate_attrs(&tuple).is_ok());
+    }
+
+    #[test]
+    fn skip_is_rejected_outside_struct_fields() {
+        let unit: DeriveInput = parse_quote! {
+            #[netcode(skip)]
+            struct Unit;
+        };
+        let variant_field: DeriveInput = parse_quote! {
+            enum Kind { A(#[netcode(skip)] u8) }
+        };
+
+        let why = validate_attrs(&unit).unwrap_err().to_string();
+        assert!(why.contains("can only be placed on struct fields"), "{why}");
+        assert!(validate_attrs(&variant_field).is_err());
+    }
+
+    /// Obtains the variants of a parsed enum.
+    fn variants(input: &DeriveInput) -> &DataEnum {
+        let Data::Enum(data_enum) = &input.data else {
+            panic!("expected an enum");
+        };
+        data_enum
+    }
+
+    #[test]
+    fn explicit_tags_replace_declaration_order() {
+        let input: DeriveInput = parse_quote! {
+            enum Kind { #[netcode(tag = 7)] A, B, #[netcode(tag = 0)] C }
+        };
+
+        assert_eq!(variant_tags(variants(&input)).unwrap(), vec![7, 1, 0]);
+    }
+
+    #[test]
+    fn colliding_and_oversized_tags_are_rejected() {
+        let collision: DeriveInput = parse_quote! {
+            enum Kind { #[netcode(tag = 1)] A, B }
+        };
+        let oversized: DeriveInput = parse_quote! {
+            enum Kind { #[netcode(tag = 256)] A }
+        };
+
+        let why = variant_tags(variants(&collision)).unwrap_err().to_string();
+        assert!(why.contains("already used by variant `A`"), "{why}");
+        let why = variant_tags(variants(&oversized)).unwrap_err().to_string();
+        assert!(why.contains("within 0..=255"), "{why}");
+    }
+}
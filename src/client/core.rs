@@ -1,5 +1,4 @@
-use std::collections::HashMap;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use sdl3::Sdl;
 use sdl3::pixels::Color;
@@ -11,17 +10,20 @@ use crate::client::input::{Input, InputState};
 use crate::error::AppError;
 use crate::net::PacketLabel;
 use crate::net::Socket;
-use crate::shared::payload::{Connect, Movement, PayloadId, Position, ServerState};
-use crate::utils::decode;
+use crate::net::traits::Versioned;
+use crate::shared::payload::{Movement, PayloadId};
 use crate::vec2f::Vec2f;
 
 use super::socket::ClientSocket;
+use super::state::ClientGameState;
 
 /// Core of the client application.
 pub struct ClientCore {
-    socket: ClientSocket,   // Socket to the server.
-    sdl: Sdl,               // SDL context.
-    canvas: Canvas<Window>, // Canvas to draw on.
+    socket: ClientSocket,    // Socket to the server.
+    sdl: Sdl,                // SDL context.
+    canvas: Canvas<Window>,  // Canvas to draw on.
+    input_rate: Option<u16>, // Movement packets sent per second. None to match the server's tick rate.
+    camera: Vec2f,           // World position shown at the center of the window.
 }
 
 impl ClientCore {
@@ -29,6 +31,9 @@ impl ClientCore {
     const WIDTH: u32 = Self::SIZE as u32 * 20;
     const HEIGHT: u32 = Self::WIDTH;
 
+    /// Rate at which the camera closes the distance to the player, per second.
+    const CAMERA_FOLLOW: f32 = 10.0;
+
     /// Creates a new client core by initializing the SDL context and creating a window.
     pub fn new(socket: Socket) -> Result<Self, AppError> {
         let sdl = sdl3::init().map_err(AppError::Sdl)?;
@@ -49,14 +54,28 @@ impl ClientCore {
             socket: ClientSocket::new(socket),
             sdl,
             canvas,
+            input_rate: None,
+            camera: Vec2f::ZERO,
         })
     }
 
+    /// Sets the maximum number of movement packets sent per second, overriding the server's tick rate.
+    #[allow(dead_code)]
+    pub fn input_rate(mut self, per_second: u16) -> Self {
+        self.input_rate = Some(per_second);
+        self
+    }
+
+    /// Minimum time between movement packets. Zero if the rate is not known yet.
+    fn input_interval(&self, server_tps: u16) -> Duration {
+        match self.input_rate.unwrap_or(server_tps) {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs(1) / u32::from(rate),
+        }
+    }
+
     /// Runs the main loop for the client application. Handles input events, server updates, and rendering.
-    #[allow(clippy::too_many_lines)]
     pub fn run(&mut self) -> Result<(), AppError> {
-        const LERP_SNAP_SPEED: f32 = 10.0; // “pull‑to‑server” speed in Hz
-
         self.canvas.set_draw_color(Color::RGB(0, 0, 0));
         self.canvas.clear();
         self.canvas.present();
@@ -65,19 +84,9 @@ impl ClientCore {
         // Wait for the connection.
         self.socket.wait_for_connection()?;
 
-        let mut entity_id = 0;
-
-        let mut speed: u8 = 1;
-
         let mut last_frame_time = Instant::now();
         let mut input_state = InputState::new();
-
-        // Represents the server state.
-        let mut server_state = ServerState { tps: 0, tick_id: 0 };
-        let mut server_state_ms = Instant::now(); // Time when the server state was last received.
-        let mut _server_tick_est: u64; // Estimated tick from the server.
-
-        let mut entity_pos: HashMap<u32, (Vec2f, Vec2f, Vec2f)> = HashMap::new();
+        let mut game = ClientGameState::default();
 
         'game_loop: loop {
             // Get the delta time.
@@ -85,39 +94,14 @@ impl ClientCore {
             let dt = (now - last_frame_time).as_secs_f32();
             last_frame_time = now;
 
-            // Calculate the server tick based on the server state and the elapsed time.
-            let tick_duration = 1.0 / f32::from(server_state.tps);
-            let elapsed = now - server_state_ms;
-            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-            let ticks = (elapsed.as_secs_f32() / tick_duration).floor() as u64;
-            _server_tick_est = server_state.tick_id + ticks;
-
             // Process the packets from the server.
             let packets = self.socket.run_step()?;
             for packet in packets {
-                match packet.label() {
-                    PacketLabel::Extension(id) if id == u8::from(PayloadId::Connect) => {
-                        let Connect(entity, spawn_point) = decode::<Connect>(&packet)?;
-                        entity_id = entity;
-                        entity_pos.insert(entity, (spawn_point, spawn_point, Vec2f::ZERO));
-                    }
-                    PacketLabel::Extension(id) if id == u8::from(PayloadId::State) => {
-                        server_state = decode::<ServerState>(&packet)?;
-                        _server_tick_est = server_state.tick_id;
-                        server_state_ms = Instant::now(); // Reset the server state time.
-                    }
-                    PacketLabel::Extension(id) if id == u8::from(PayloadId::Position) => {
-                        let Position(entity, server_pos, vel) = decode::<Position>(&packet)?;
-                        if let Some((_local, remote, view)) = entity_pos.get_mut(&entity) {
-                            *remote = server_pos;
-                            *view = vel;
-                        } else {
-                            // Add a new remote player.
-                            entity_pos.insert(entity, (server_pos, server_pos, vel));
-                        }
-                    }
-
-                    _ => {}
+                if let Some(ack) = game.handle_packet(&packet)? {
+                    self.socket.send(
+                        PacketLabel::Extension(u8::from(PayloadId::SnapshotAck)),
+                        Some(ack),
+                    )?;
                 }
             }
 
@@ -127,11 +111,9 @@ impl ClientCore {
                 match input {
                     Input::Quit => break 'game_loop,
                     Input::Cursor(_dx, _dy) => (),
-                    Input::Speed(s) => speed = *s,
                     Input::MoveDelta(delta) => {
-                        if let Some((local, _, _)) = entity_pos.get_mut(&entity_id) {
+                        if game.move_local(*delta * dt) {
                             move_delta = *delta;
-                            *local += delta.scale(dt);
                         }
                     }
                 }
@@ -141,43 +123,46 @@ impl ClientCore {
             if move_delta != Vec2f::ZERO
                 || (input_state.is_movement_released() && !input_state.is_movement_held())
             {
-                // Send the movement to the server.
-                let payload = Movement(move_delta, speed);
+                game.queue_move(Movement(move_delta, input_state.speed()));
+            }
+
+            // Send the latest movement to the server, at most once per tick.
+            let interval = self.input_interval(game.server_state.tps);
+            if let Some(payload) = game.take_move(interval) {
                 self.socket.send(
                     PacketLabel::Extension(u8::from(PayloadId::Movement)),
-                    Some(payload),
+                    Some(Versioned(payload)),
                 )?;
             }
 
+            // Move the camera towards the center of the local player.
+            if let Some(local) = game.local_position() {
+                let target = local + Vec2f(0.5, 0.5);
+                self.camera = self
+                    .camera
+                    .lerp(target, (dt * Self::CAMERA_FOLLOW).min(1.0));
+            }
+
             self.canvas.set_draw_color(Color::RGB(255, 255, 255));
             self.canvas.clear();
 
             // Draw the grid and the player.
             self.draw_grid(Color::RGB(0, 0, 0));
 
-            // Render the local player's position.
-            for (entity, (local, remote, view)) in &mut entity_pos {
-                // Update the position of the remote player.
-                *local += (*remote - *local).scale((LERP_SNAP_SPEED * dt).min(1.0));
-
-                let scaled_local = local.scale(f32::from(Self::SIZE));
-                let scaled_remote = remote.scale(f32::from(Self::SIZE));
-                let scaled_view = view.scale(f32::from(Self::SIZE));
-
-                // Render the remote players.
-                if entity == &entity_id {
-                    self.render_pos(scaled_remote, Color::RGB(255, 0, 0));
-                    self.render_pos(scaled_local, Color::RGB(0, 0, 255));
+            // Blend towards the server positions, then render every entity.
+            game.interpolate();
+            for (entity, (local, remote, view)) in &game.entity_pos {
+                if *entity == game.entity_id {
+                    self.render_pos(*remote, Color::RGB(255, 0, 0));
+                    self.render_pos(*local, Color::RGB(0, 0, 255));
                 } else {
-                    self.render_pos(scaled_remote, Color::RGB(0, 255, 255));
-                    self.render_pos(scaled_local, Color::RGB(0, 255, 0));
+                    self.render_pos(*remote, Color::RGB(0, 255, 255));
+                    self.render_pos(*local, Color::RGB(0, 255, 0));
                 }
 
                 // Render the direction they are facing.
-                let start_x = scaled_remote.0 + f32::from(Self::SIZE) / 2.0;
-                let start_y = scaled_remote.1 + f32::from(Self::SIZE) / 2.0;
-                let start = Vec2f(start_x, start_y);
-                self.render_line(start, start + scaled_view, Color::RGB(255, 0, 0));
+                let start = *remote + Vec2f(0.5, 0.5);
+                self.render_line(start, start + *view, Color::RGB(255, 0, 0));
             }
 
             self.canvas.present();
@@ -186,26 +171,44 @@ impl ClientCore {
         Ok(())
     }
 
-    /// Draws a grid on the canvas.
+    /// Converts a world position into a window position, keeping the camera at the center.
+    #[allow(clippy::cast_precision_loss)]
+    fn world_to_screen(camera: Vec2f, pos: Vec2f) -> Vec2f {
+        let center = Vec2f(Self::WIDTH as f32 / 2.0, Self::HEIGHT as f32 / 2.0);
+        (pos - camera) * f32::from(Self::SIZE) + center
+    }
+
+    /// Draws a grid on the canvas, scrolling with the camera.
     #[allow(clippy::cast_precision_loss)]
     pub(crate) fn draw_grid(&mut self, color: Color) {
+        let size = f32::from(Self::SIZE);
+        let (width, height) = (Self::WIDTH as f32, Self::HEIGHT as f32);
+
+        // Lines fall on whole world units, so only the offset of the origin within a cell matters.
+        let origin = Self::world_to_screen(self.camera, Vec2f::ZERO);
+        let (offset_x, offset_y) = (origin.0.rem_euclid(size), origin.1.rem_euclid(size));
+
         self.canvas.set_draw_color(color);
-        for x in (0..Self::WIDTH).step_by(Self::SIZE.into()) {
-            let _ = self.canvas.draw_line(
-                FPoint::new(x as f32, 0.0),
-                FPoint::new(x as f32, Self::HEIGHT as f32),
-            );
+        let mut x = offset_x;
+        while x <= width {
+            let _ = self
+                .canvas
+                .draw_line(FPoint::new(x, 0.0), FPoint::new(x, height));
+            x += size;
         }
-        for y in (0..Self::HEIGHT).step_by(Self::SIZE.into()) {
-            let _ = self.canvas.draw_line(
-                FPoint::new(0.0, y as f32),
-                FPoint::new(Self::WIDTH as f32, y as f32),
-            );
+
+        let mut y = offset_y;
+        while y <= height {
+            let _ = self
+                .canvas
+                .draw_line(FPoint::new(0.0, y), FPoint::new(width, y));
+            y += size;
         }
     }
 
-    /// Renders a position on the canvas.
+    /// Renders a world position on the canvas.
     pub(crate) fn render_pos(&mut self, pos: Vec2f, color: Color) {
+        let pos = Self::world_to_screen(self.camera, pos);
         self.canvas.set_draw_color(color);
         #[allow(clippy::cast_possible_truncation)]
         let _ = self.canvas.fill_rect(Rect::new(
@@ -216,8 +219,12 @@ impl ClientCore {
         ));
     }
 
-    /// Renders a colored line from the starting position to ending.
+    /// Renders a colored line between two world positions.
     pub(crate) fn render_line(&mut self, start: Vec2f, end: Vec2f, color: Color) {
+        let (start, end) = (
+            Self::world_to_screen(self.camera, start),
+            Self::world_to_screen(self.camera, end),
+        );
         self.canvas.set_draw_color(color);
         let _ = self.canvas.draw_line(start, end);
     }
@@ -228,3 +235,30 @@ impl From<Vec2f> for FPoint {
         FPoint { x: vec.0, y: vec.1 }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_is_drawn_at_the_window_center() {
+        let center = Vec2f(320.0, 320.0);
+        let camera = Vec2f(3.5, -2.0);
+        assert_eq!(ClientCore::world_to_screen(camera, camera), center);
+        assert_eq!(
+            ClientCore::world_to_screen(Vec2f::ZERO, Vec2f::ZERO),
+            center
+        );
+
+        // Each world unit is one cell of the grid away from the camera.
+        let right_below = camera + Vec2f(1.0, 2.0);
+        assert_eq!(
+            ClientCore::world_to_screen(camera, right_below),
+            center + Vec2f(32.0, 64.0)
+        );
+        assert_eq!(
+            ClientCore::world_to_screen(camera, Vec2f(0.0, 0.0)),
+            center + Vec2f(-112.0, 64.0)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use sdl3::EventPump;
 use sdl3::event::Event;
@@ -12,57 +12,155 @@ pub(crate) enum Input {
     Quit,             // The user has requested to quit the application.
     Cursor(f32, f32), // The cursor position.
     MoveDelta(Vec2f), // The delta movement.
-    Speed(u8),        // The speed of the player.
+}
+
+/// Logical actions that keys can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Action {
+    Up,     // Move up.
+    Down,   // Move down.
+    Left,   // Move left.
+    Right,  // Move right.
+    Speed1, // Set the speed to 1.
+    Speed2, // Set the speed to 2.
+    Speed3, // Set the speed to 3.
+    Quit,   // Quit the application.
+}
+
+impl Action {
+    /// Checks if the action moves the player.
+    pub fn is_movement(self) -> bool {
+        matches!(self, Self::Up | Self::Down | Self::Left | Self::Right)
+    }
+}
+
+/// Maps keys to the actions they trigger. Each action is bound to a single key.
+pub(crate) struct KeyBindings(HashMap<Action, Keycode>);
+
+impl Default for KeyBindings {
+    /// WASD for movement, 1/2/3 for speed, and Escape to quit.
+    fn default() -> Self {
+        Self(HashMap::from([
+            (Action::Up, Keycode::W),
+            (Action::Down, Keycode::S),
+            (Action::Left, Keycode::A),
+            (Action::Right, Keycode::D),
+            (Action::Speed1, Keycode::_1),
+            (Action::Speed2, Keycode::_2),
+            (Action::Speed3, Keycode::_3),
+            (Action::Quit, Keycode::Escape),
+        ]))
+    }
+}
+
+impl KeyBindings {
+    /// Binds an action to a key, replacing its previous key. Any other action bound to the key
+    /// is unbound.
+    #[allow(dead_code)]
+    pub fn bind(&mut self, action: Action, keycode: Keycode) {
+        self.0.retain(|_, bound| *bound != keycode);
+        self.0.insert(action, keycode);
+    }
+
+    /// Obtains the key bound to an action.
+    pub fn key(&self, action: Action) -> Option<Keycode> {
+        self.0.get(&action).copied()
+    }
+
+    /// Obtains the action bound to a key.
+    pub fn action(&self, keycode: Keycode) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(_, bound)| **bound == keycode)
+            .map(|(action, _)| *action)
+    }
 }
 
 /// Represents the state of the input.
 pub(crate) struct InputState {
     held: HashSet<Keycode>,     // The keys that are currently held down.
     released: HashSet<Keycode>, // The keys that have been released.
+    speed: u8,                  // The selected speed, kept until another is selected.
+    pub bindings: KeyBindings,  // The actions each key triggers.
     pub events: Vec<Input>,     // The events that have been triggered.
 }
 
 impl InputState {
-    /// Creates a new instance of the input state.
+    /// Creates a new instance of the input state with the default key bindings.
     pub fn new() -> Self {
+        Self::with_bindings(KeyBindings::default())
+    }
+
+    /// Creates a new instance of the input state with custom key bindings.
+    pub fn with_bindings(bindings: KeyBindings) -> Self {
         Self {
             held: HashSet::new(),
             released: HashSet::new(),
+            speed: 1,
+            bindings,
             events: Vec::new(),
         }
     }
 
+    /// Obtains the selected speed of the player.
+    pub fn speed(&self) -> u8 {
+        self.speed
+    }
+
+    /// Checks if the key bound to an action is currently held down.
+    fn is_held(&self, action: Action) -> bool {
+        self.bindings
+            .key(action)
+            .is_some_and(|key| self.held.contains(&key))
+    }
+
+    /// Direction along an axis, cancelling out to 0 when both opposing actions are held.
+    fn axis(&self, negative: Action, positive: Action) -> f32 {
+        match (self.is_held(negative), self.is_held(positive)) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Checks if any of the keys are bound to movement.
+    fn has_movement(&self, keys: &HashSet<Keycode>) -> bool {
+        keys.iter()
+            .any(|key| self.bindings.action(*key).is_some_and(Action::is_movement))
+    }
+
     /// Checks if any movement keys are currently held down.
     pub fn is_movement_held(&self) -> bool {
-        self.held.contains(&Keycode::W)
-            || self.held.contains(&Keycode::A)
-            || self.held.contains(&Keycode::S)
-            || self.held.contains(&Keycode::D)
+        self.has_movement(&self.held)
     }
 
     /// Checks if any movement keys have been released.
     pub fn is_movement_released(&self) -> bool {
-        self.released.contains(&Keycode::W)
-            || self.released.contains(&Keycode::A)
-            || self.released.contains(&Keycode::S)
-            || self.released.contains(&Keycode::D)
+        self.has_movement(&self.released)
     }
 
     /// Obtains the input from the user.
     pub fn get_input(&mut self, pump: &mut EventPump, win_id: u32) {
-        let mut delta = Vec2f(0.0, 0.0);
+        self.process_events(pump.poll_iter(), win_id);
+    }
+
+    /// Processes the events into inputs, replacing the previous inputs.
+    pub fn process_events(&mut self, events: impl IntoIterator<Item = Event>, win_id: u32) {
         let mut last_pos = Vec2f(f32::MIN, f32::MIN);
-        let mut last_speed = 0u8;
         self.events = Vec::new();
         self.released.clear();
 
-        for event in pump.poll_iter() {
+        for event in events {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+                Event::Quit { .. } => {
+                    self.events = vec![Input::Quit];
+                    return;
+                }
+
+                Event::KeyDown {
+                    keycode: Some(keycode),
                     ..
-                } => {
+                } if self.bindings.action(keycode) == Some(Action::Quit) => {
                     self.events = vec![Input::Quit];
                     return;
                 }
@@ -82,19 +180,13 @@ impl InputState {
                     window_id,
                     repeat: false,
                     ..
-                } if window_id == win_id => {
-                    self.released.remove(&keycode);
-                    self.held.insert(keycode);
-                }
+                } if window_id == win_id => self.press(keycode),
 
                 Event::KeyUp {
                     keycode: Some(keycode),
                     window_id,
                     ..
-                } if window_id == win_id => {
-                    self.held.remove(&keycode);
-                    self.released.insert(keycode);
-                }
+                } if window_id == win_id => self.release(keycode),
 
                 _ => (),
             }
@@ -104,30 +196,98 @@ impl InputState {
             self.events.push(Input::Cursor(last_pos.0, last_pos.1));
         }
 
-        // Accumulate the delta movement.
-        for keycode in &self.held {
-            match keycode {
-                Keycode::W => delta += Vec2f(0.0, -1.0), // Move up
-                Keycode::A => delta += Vec2f(-1.0, 0.0), // Move left
-                Keycode::S => delta += Vec2f(0.0, 1.0),  // Move down
-                Keycode::D => delta += Vec2f(1.0, 0.0),  // Move right
-                Keycode::_1 => last_speed = 1,
-                Keycode::_2 => last_speed = 2,
-                Keycode::_3 => last_speed = 3,
-                _ => (),
-            }
+        if let Some(delta) = self.move_delta() {
+            self.events.push(Input::MoveDelta(delta));
         }
+    }
 
-        if delta != Vec2f(0.0, 0.0) {
-            if delta.length() > 1.0 {
-                delta = delta.normalized();
-            }
+    /// Records a key being pressed. Speed is selected on press, so the last key pressed wins.
+    fn press(&mut self, keycode: Keycode) {
+        self.released.remove(&keycode);
+        self.held.insert(keycode);
 
-            self.events.push(Input::MoveDelta(delta));
+        match self.bindings.action(keycode) {
+            Some(Action::Speed1) => self.speed = 1,
+            Some(Action::Speed2) => self.speed = 2,
+            Some(Action::Speed3) => self.speed = 3,
+            _ => (),
         }
+    }
 
-        if last_speed != 0 {
-            self.events.push(Input::Speed(last_speed));
-        }
+    /// Records a key being released.
+    fn release(&mut self, keycode: Keycode) {
+        self.held.remove(&keycode);
+        self.released.insert(keycode);
+    }
+
+    /// Normalized direction of the held movement keys. None if they do not move the player.
+    fn move_delta(&self) -> Option<Vec2f> {
+        // Opposing keys held together cancel out, so Up and Down together do not move.
+        let delta = Vec2f(
+            self.axis(Action::Left, Action::Right),
+            self.axis(Action::Up, Action::Down),
+        );
+
+        (delta != Vec2f::ZERO).then(|| delta.normalized())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a frame with no new events, returning the movement it produced.
+    fn frame(input: &mut InputState) -> Option<Vec2f> {
+        input.process_events([], 0);
+        input.events.iter().find_map(|event| match event {
+            Input::MoveDelta(delta) => Some(*delta),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn rebound_keys_produce_movement() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind(Action::Up, Keycode::Up);
+        bindings.bind(Action::Right, Keycode::Right);
+        let mut input = InputState::with_bindings(bindings);
+
+        input.press(Keycode::W);
+        assert_eq!(frame(&mut input), None, "unbound key moved the player");
+
+        input.press(Keycode::Up);
+        input.press(Keycode::Right);
+        assert_eq!(frame(&mut input), Some(Vec2f(1.0, -1.0).normalized()));
+        assert!(input.is_movement_held());
+
+        input.release(Keycode::Up);
+        assert_eq!(frame(&mut input), Some(Vec2f(1.0, 0.0)));
+    }
+
+    #[test]
+    fn opposite_keys_cancel_out() {
+        let mut input = InputState::new();
+        input.press(Keycode::W);
+        input.press(Keycode::S);
+        assert_eq!(frame(&mut input), None);
+
+        input.press(Keycode::A);
+        assert_eq!(frame(&mut input), Some(Vec2f(-1.0, 0.0)));
+    }
+
+    #[test]
+    fn speed_is_kept_until_another_is_selected() {
+        let mut input = InputState::new();
+        assert_eq!(input.speed(), 1);
+
+        input.press(Keycode::_3);
+        input.release(Keycode::_3);
+        frame(&mut input);
+        frame(&mut input);
+        assert_eq!(input.speed(), 3);
+
+        input.press(Keycode::_2);
+        frame(&mut input);
+        assert_eq!(input.speed(), 2);
     }
 }
@@ -1,5 +1,7 @@
 mod core;
 mod input;
+mod prediction;
 mod socket;
+mod state;
 
 pub use core::ClientCore;
@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+
+use crate::vec2f::Vec2f;
+
+/// Movement inputs sent to the server that it has not processed yet, along with the distance the
+/// local player was moved for each. Used to replay the inputs on top of the server's position so
+/// corrections do not undo movement the server has yet to see.
+pub(crate) struct PendingInputs {
+    next_seq: u32,                  // Sequence of the next input sent.
+    inputs: VecDeque<(u32, Vec2f)>, // Sequence and local displacement of each pending input.
+    unsent: Vec2f,                  // Local displacement since the last input was sent.
+}
+
+impl Default for PendingInputs {
+    fn default() -> Self {
+        Self {
+            next_seq: 1, // 0 is reserved for "no input processed yet".
+            inputs: VecDeque::with_capacity(Self::CAPACITY),
+            unsent: Vec2f::ZERO,
+        }
+    }
+}
+
+impl PendingInputs {
+    /// Most inputs held, the oldest is dropped once full.
+    const CAPACITY: usize = 64;
+
+    /// Records that the local player was moved before the next input is sent.
+    pub fn record(&mut self, displacement: Vec2f) {
+        self.unsent += displacement;
+    }
+
+    /// Assigns a sequence to an input being sent, holding the displacement recorded since the
+    /// last input until the server processes it.
+    pub fn next_sequence(&mut self) -> u32 {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1).max(1);
+
+        if self.inputs.len() >= Self::CAPACITY {
+            self.inputs.pop_front();
+        }
+
+        self.inputs.push_back((seq, self.unsent));
+        self.unsent = Vec2f::ZERO;
+        seq
+    }
+
+    /// Drops every input up to and including the last one the server processed.
+    pub fn acknowledge(&mut self, processed: u32) {
+        // Wrapping difference, so sequences that rolled over are still ordered.
+        self.inputs
+            .retain(|(seq, _)| processed.wrapping_sub(*seq).cast_signed() < 0);
+    }
+
+    /// Predicts the local player's position by replaying the pending inputs on top of the
+    /// position from the server.
+    pub fn predict(&self, server_pos: Vec2f) -> Vec2f {
+        self.inputs
+            .iter()
+            .fold(server_pos + self.unsent, |pos, (_, displacement)| {
+                pos + *displacement
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrections_replay_the_pending_inputs() {
+        let mut pending = PendingInputs::default();
+        pending.record(Vec2f(1.0, 0.0));
+        assert_eq!(pending.next_sequence(), 1);
+        pending.record(Vec2f(0.0, 2.0));
+        assert_eq!(pending.next_sequence(), 2);
+        pending.record(Vec2f(0.5, 0.0));
+
+        // The server processed the first input, the second and the unsent movement are replayed.
+        pending.acknowledge(1);
+        assert_eq!(pending.predict(Vec2f(10.0, 10.0)), Vec2f(10.5, 12.0));
+
+        pending.acknowledge(2);
+        assert_eq!(pending.predict(Vec2f(10.0, 10.0)), Vec2f(10.5, 10.0));
+
+        // Acknowledging an old input again changes nothing.
+        pending.acknowledge(1);
+        assert_eq!(pending.predict(Vec2f(10.0, 10.0)), Vec2f(10.5, 10.0));
+    }
+
+    #[test]
+    fn sequences_stay_ordered_across_the_wrap() {
+        let mut pending = PendingInputs {
+            next_seq: u32::MAX,
+            ..PendingInputs::default()
+        };
+        pending.record(Vec2f(1.0, 0.0));
+        assert_eq!(pending.next_sequence(), u32::MAX);
+        pending.record(Vec2f(0.0, 1.0));
+        assert_eq!(pending.next_sequence(), 1, "the reserved sequence was used");
+
+        pending.acknowledge(u32::MAX);
+        assert_eq!(pending.predict(Vec2f::ZERO), Vec2f(0.0, 1.0));
+    }
+
+    #[test]
+    fn the_oldest_input_is_dropped_once_full() {
+        let mut pending = PendingInputs::default();
+        for _ in 0..=PendingInputs::CAPACITY {
+            pending.record(Vec2f(1.0, 0.0));
+            pending.next_sequence();
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let held = PendingInputs::CAPACITY as f32;
+        assert_eq!(pending.predict(Vec2f::ZERO), Vec2f(held, 0.0));
+    }
+}
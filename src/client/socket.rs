@@ -6,7 +6,8 @@ use crate::net::error::NetError;
 use crate::net::traits::NetEncoder;
 use crate::net::{ClientId, Deliverable, Packet, PacketLabel, Socket};
 use crate::utils::decode;
-use crate::{Result, debugln, flee};
+use crate::utils::log::LogLevel;
+use crate::{Result, debugln, flee, logln};
 
 /// Basic client implementation that connects to a server.
 pub struct ClientSocket {
@@ -43,6 +44,22 @@ impl ClientSocket {
             packet.set_payload(data);
         }
 
+        self.send_packet(packet)
+    }
+
+    /// Sends a text message to the server. Messages too large to fit within a packet are rejected.
+    #[allow(dead_code)]
+    pub fn send_message(&mut self, text: &str) -> Result<()> {
+        let mut packet = Packet::new(PacketLabel::Message, self.id());
+        packet
+            .try_set_payload(MessagePayload(text.to_string()))
+            .map_err(AppError::Net)?;
+
+        self.send_packet(packet)
+    }
+
+    /// Sends a built packet to the server.
+    fn send_packet(&mut self, packet: Packet) -> Result<()> {
         match self.socket.send(Deliverable::new(self.server, packet)) {
             Ok(()) => Ok(()),
             Err(NetError::SocketError(why)) => Err(AppError::Net(NetError::SocketError(why))),
@@ -58,7 +75,13 @@ impl ClientSocket {
         let mut retry_count = 0;
         while retry_count < Self::MAX_CONNECTION_RETRY && self.server == ClientId::INVALID {
             // Send a connect packet to the server.
-            let payload = ConnectionPayload(Packet::CURRENT_VERSION, self.id(), 5000);
+            let payload = ConnectionPayload(
+                Packet::CURRENT_VERSION,
+                self.id(),
+                5000,
+                0,
+                Packet::MIN_VERSION,
+            );
             self.send(PacketLabel::Connect, Some(payload))?;
             std::thread::sleep(Duration::from_millis(500));
 
@@ -107,19 +130,15 @@ impl ClientSocket {
 
         match packet.label() {
             PacketLabel::Error => {
-                let payload = decode::<ErrorPayload>(&packet)?;
-                debugln!(
-                    "CLIENT: [{}] Received error: {:?}",
-                    packet.source(),
-                    payload
-                );
+                let ErrorPayload(code, msg) = decode::<ErrorPayload>(&packet)?;
+                logln!(LogLevel::Warn, "Server error ({code}): {msg}");
             }
 
             PacketLabel::Acknowledge => {
                 debugln!("CLIENT: [{}] Received acknowledge.", self.id());
             }
 
-            PacketLabel::Connect => {
+            PacketLabel::ConnectAccept => {
                 let payload = decode::<ConnectionPayload>(&packet)?;
                 self.server = packet.source();
                 debugln!(
@@ -151,6 +170,10 @@ impl ClientSocket {
                 debugln!("CLIENT: [{}] Received message: {:?}", self.id(), payload);
             }
 
+            PacketLabel::Connect => {
+                debugln!("CLIENT: [{}] Ignoring connect request.", self.id());
+            }
+
             PacketLabel::Extension(_value) => {}
         }
 
@@ -158,3 +181,52 @@ impl ClientSocket {
         Ok(Some(()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Local server and client that have completed the handshake.
+    fn connected() -> (Socket, ClientSocket) {
+        let (mut hub, mut server) = Socket::new_local_hub().unwrap();
+        let mut client = ClientSocket::new(Socket::new_local_client(&mut hub).unwrap());
+
+        let payload = ConnectionPayload(
+            Packet::CURRENT_VERSION,
+            client.id(),
+            5000,
+            0,
+            Packet::MIN_VERSION,
+        );
+        client.send(PacketLabel::Connect, Some(payload)).unwrap();
+        server.try_recv().unwrap();
+        client.run_step().unwrap();
+        assert_ne!(
+            client.server,
+            ClientId::INVALID,
+            "handshake did not complete"
+        );
+
+        (server, client)
+    }
+
+    #[test]
+    fn messages_reach_the_server() {
+        let (mut server, mut client) = connected();
+        client.send_message("hello server").unwrap();
+
+        let packet = server.try_recv().unwrap().expect("message was not sent");
+        assert_eq!(packet.label(), PacketLabel::Message);
+        let MessagePayload(text) = decode::<MessagePayload>(&packet).unwrap();
+        assert_eq!(text, "hello server");
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected() {
+        let (mut server, mut client) = connected();
+        let text = "x".repeat(Packet::MAX_PAYLOAD_SIZE + 1);
+
+        assert!(client.send_message(&text).is_err());
+        assert!(server.try_recv().unwrap().is_none());
+    }
+}
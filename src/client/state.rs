@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::client::prediction::PendingInputs;
+use crate::error::AppError;
+use crate::net::{Packet, PacketLabel};
+use crate::shared::payload::{
+    Connect, Despawn, Movement, MovementInput, PayloadId, ServerState, SnapshotAck,
+};
+use crate::shared::snapshot::{Snapshot, SnapshotHistory};
+use crate::utils::{Timestep, decode};
+use crate::vec2f::Vec2f;
+
+/// Shown position, latest position from the server, and velocity of an entity.
+pub(crate) type EntityView = (Vec2f, Vec2f, Vec2f);
+
+/// State of the game known to the client, built from the packets sent by the server. Holds no
+/// window or rendering resources, so it can be driven without SDL.
+pub(crate) struct ClientGameState {
+    pub entity_id: u32,                       // Entity controlled by the client.
+    pub entity_pos: HashMap<u32, EntityView>, // Entities known to the client.
+    pub server_state: ServerState,            // Latest state sent by the server.
+    server_state_at: Instant,                 // Time the server state was received.
+    step: Option<Timestep>, // Render clock, ticking at the server's rate once known.
+    previous: HashMap<u32, Vec2f>, // Server positions before the latest, blended from.
+    latest_at: Duration,    // Render time the latest server positions arrived.
+    snapshots: SnapshotHistory, // Applied snapshots, deltas build on them.
+    inputs: PendingInputs,  // Sent inputs the server has yet to process.
+    pending_move: Option<Movement>, // Latest movement waiting to be sent.
+    last_move_sent: Instant, // Time the last movement was sent.
+}
+
+impl Default for ClientGameState {
+    fn default() -> Self {
+        Self {
+            entity_id: 0,
+            entity_pos: HashMap::new(),
+            server_state: ServerState { tps: 0, tick_id: 0 },
+            server_state_at: Instant::now(),
+            step: None,
+            previous: HashMap::new(),
+            latest_at: Duration::ZERO,
+            snapshots: SnapshotHistory::default(),
+            inputs: PendingInputs::default(),
+            pending_move: None,
+            last_move_sent: Instant::now(),
+        }
+    }
+}
+
+impl ClientGameState {
+    /// Estimates the server's current tick from the last state it sent and the time since.
+    #[allow(dead_code)]
+    pub fn server_tick_estimate(&self) -> u64 {
+        if self.server_state.tps == 0 {
+            return self.server_state.tick_id;
+        }
+
+        let tick_duration = 1.0 / f32::from(self.server_state.tps);
+        let elapsed = self.server_state_at.elapsed();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let ticks = (elapsed.as_secs_f32() / tick_duration).floor() as u64;
+        self.server_state.tick_id + ticks
+    }
+
+    /// Applies a packet from the server. Returns the acknowledgement to send back for applied
+    /// snapshots.
+    pub fn handle_packet(&mut self, packet: &Packet) -> Result<Option<SnapshotAck>, AppError> {
+        match packet.label() {
+            PacketLabel::Extension(id) if id == u8::from(PayloadId::Connect) => {
+                let Connect(entity, spawn_point) = decode::<Connect>(packet)?;
+                self.entity_id = entity;
+                self.entity_pos
+                    .insert(entity, (spawn_point, spawn_point, Vec2f::ZERO));
+            }
+
+            PacketLabel::Extension(id) if id == u8::from(PayloadId::State) => {
+                self.server_state = decode::<ServerState>(packet)?;
+                self.server_state_at = Instant::now();
+
+                // Follow the server's tick rate, keeping the render clock running.
+                if self.server_state.tps > 0 {
+                    let rate = f32::from(self.server_state.tps);
+                    match self.step.as_mut() {
+                        Some(step) => step.set_rate(rate),
+                        None => self.step = Some(Timestep::new(rate)),
+                    }
+                }
+            }
+
+            PacketLabel::Extension(id) if id == u8::from(PayloadId::Snapshot) => {
+                let snapshot = decode::<Snapshot>(packet)?;
+                return Ok(self.apply_snapshot(&snapshot));
+            }
+
+            PacketLabel::Extension(id) if id == u8::from(PayloadId::Despawn) => {
+                let Despawn(entity) = decode::<Despawn>(packet)?;
+                self.entity_pos.remove(&entity);
+                self.previous.remove(&entity);
+                self.snapshots.forget(entity);
+            }
+
+            _ => {}
+        }
+
+        Ok(None)
+    }
+
+    /// Applies a snapshot onto the baseline it was created against. None if the baseline is no
+    /// longer held, the client waits for a full snapshot instead.
+    fn apply_snapshot(&mut self, snapshot: &Snapshot) -> Option<SnapshotAck> {
+        let baseline = match snapshot.baseline {
+            Some(baseline) => Some(self.snapshots.get(baseline)?),
+            None => None,
+        };
+
+        let state = snapshot.apply(baseline)?;
+
+        self.inputs.acknowledge(snapshot.input_seq);
+        for entry in &snapshot.entries.0 {
+            if let Some(&(server_pos, vel)) = state.get(&entry.0) {
+                self.update_entity(entry.0, server_pos, vel);
+            }
+        }
+
+        self.snapshots.push(snapshot.id, state);
+        Some(SnapshotAck(snapshot.id))
+    }
+
+    /// Updates an entity with its position from the server, adding it if it is new. The position
+    /// it replaces is kept as the start of the blend towards the new one.
+    fn update_entity(&mut self, entity: u32, server_pos: Vec2f, vel: Vec2f) {
+        self.latest_at = self.step.as_ref().map_or(Duration::ZERO, Timestep::elapsed);
+        if let Some((_shown, latest, view)) = self.entity_pos.get_mut(&entity) {
+            self.previous.insert(entity, *latest);
+
+            // Replay the inputs the server has not processed yet.
+            *latest = if entity == self.entity_id {
+                self.inputs.predict(server_pos)
+            } else {
+                server_pos
+            };
+            *view = vel;
+        } else {
+            // Add a new remote player.
+            self.entity_pos
+                .insert(entity, (server_pos, server_pos, vel));
+            self.previous.insert(entity, server_pos);
+        }
+    }
+
+    /// Moves the local player by the input ahead of the server. Returns `false` if the client
+    /// does not have an entity yet.
+    pub fn move_local(&mut self, displacement: Vec2f) -> bool {
+        let Some((local, latest, _)) = self.entity_pos.get_mut(&self.entity_id) else {
+            return false;
+        };
+
+        // Shift both positions being blended, so the input shows immediately.
+        *local += displacement;
+        *latest += displacement;
+        if let Some(previous) = self.previous.get_mut(&self.entity_id) {
+            *previous += displacement;
+        }
+        self.inputs.record(displacement);
+        true
+    }
+
+    /// Sets the movement to send next, replacing any that was not sent yet.
+    pub fn queue_move(&mut self, movement: Movement) {
+        self.pending_move = Some(movement);
+    }
+
+    /// Takes the queued movement if at least `interval` passed since the last one was sent,
+    /// assigning it the next input sequence.
+    pub fn take_move(&mut self, interval: Duration) -> Option<MovementInput> {
+        let movement = self
+            .pending_move
+            .take_if(|_| self.last_move_sent.elapsed() >= interval)?;
+
+        self.last_move_sent = Instant::now();
+        Some(MovementInput(movement, self.inputs.next_sequence()))
+    }
+
+    /// Position of the local player as shown. None if the client does not have an entity yet.
+    pub fn local_position(&self) -> Option<Vec2f> {
+        self.entity_pos
+            .get(&self.entity_id)
+            .map(|(local, _, _)| *local)
+    }
+
+    /// Advances the render clock and blends the shown positions between the last two positions
+    /// received from the server.
+    pub fn interpolate(&mut self) {
+        if let Some(step) = self.step.as_mut() {
+            step.update();
+        }
+
+        self.blend();
+    }
+
+    /// Shows each entity between its previous and latest server position, by how much of a server
+    /// tick has passed on the render clock since the latest arrived. Entities rest on the latest
+    /// position once a whole tick has passed without a newer one.
+    fn blend(&mut self) {
+        let alpha = self.step.as_ref().map_or(1.0, |step| {
+            let since = step.elapsed().saturating_sub(self.latest_at);
+            (since.as_secs_f32() / step.fixed_dt()).min(1.0)
+        });
+
+        for (entity, (shown, latest, _)) in &mut self.entity_pos {
+            let previous = self.previous.get(entity).copied().unwrap_or(*latest);
+            *shown = previous.lerp(*latest, alpha);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::ClientId;
+    use crate::net::traits::NetEncoder;
+    use crate::shared::snapshot::{SnapshotEntry, SnapshotPosition};
+
+    /// State with a render clock at 10 ticks per second and a remote entity at the origin.
+    fn state() -> ClientGameState {
+        let mut state = ClientGameState {
+            step: Some(Timestep::new(10.0)),
+            ..ClientGameState::default()
+        };
+        state.update_entity(7, Vec2f::ZERO, Vec2f::ZERO);
+        state
+    }
+
+    fn shown(state: &ClientGameState, entity: u32) -> Vec2f {
+        state.entity_pos[&entity].0
+    }
+
+    #[test]
+    fn blends_between_the_last_two_server_positions() {
+        let mut state = state();
+        state.update_entity(7, Vec2f(4.0, 0.0), Vec2f::ZERO);
+
+        state.blend();
+        assert_eq!(shown(&state, 7), Vec2f::ZERO);
+
+        for (elapsed_ms, expected) in [(25, 1.0), (25, 2.0), (50, 4.0), (100, 4.0)] {
+            state
+                .step
+                .as_mut()
+                .unwrap()
+                .advance(Duration::from_millis(elapsed_ms));
+            state.blend();
+            assert!(
+                (shown(&state, 7).0 - expected).abs() < 1e-3,
+                "expected {expected}, shown {:?}",
+                shown(&state, 7)
+            );
+        }
+    }
+
+    #[test]
+    fn newer_position_restarts_the_blend_from_the_last_one() {
+        let mut state = state();
+        state.update_entity(7, Vec2f(4.0, 0.0), Vec2f::ZERO);
+        state
+            .step
+            .as_mut()
+            .unwrap()
+            .advance(Duration::from_millis(100));
+
+        state.update_entity(7, Vec2f(8.0, 0.0), Vec2f::ZERO);
+        state.blend();
+        assert_eq!(shown(&state, 7), Vec2f(4.0, 0.0));
+
+        state
+            .step
+            .as_mut()
+            .unwrap()
+            .advance(Duration::from_millis(50));
+        state.blend();
+        assert!((shown(&state, 7).0 - 6.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn despawned_entities_are_forgotten_by_held_snapshots() {
+        let mut state = state();
+        let snapshot = |id, baseline, position| {
+            let entries = vec![SnapshotEntry(7, position, Vec2f::ZERO)];
+            let snapshot = Snapshot {
+                id,
+                baseline,
+                input_seq: 0,
+                entries: entries.into(),
+            };
+            Packet::with_payload(
+                PacketLabel::Extension(u8::from(PayloadId::Snapshot)),
+                ClientId(0),
+                snapshot,
+            )
+        };
+
+        let full = snapshot(0, None, SnapshotPosition::Full(Vec2f(1.0, 1.0)));
+        assert!(state.handle_packet(&full).unwrap().is_some());
+
+        let despawn = PacketLabel::Extension(u8::from(PayloadId::Despawn));
+        state
+            .handle_packet(&Packet::with_payload(despawn, ClientId(0), Despawn(7)))
+            .unwrap();
+        assert!(!state.entity_pos.contains_key(&7));
+
+        // The baseline no longer has the entity, so a delta for it cannot be applied.
+        let delta = snapshot(1, Some(0), SnapshotPosition::Delta(0, 0));
+        assert!(state.handle_packet(&delta).unwrap().is_none());
+
+        let full = snapshot(2, Some(0), SnapshotPosition::Full(Vec2f(1.0, 1.0)));
+        assert!(state.handle_packet(&full).unwrap().is_some());
+        assert_eq!(shown(&state, 7), Vec2f(1.0, 1.0));
+    }
+
+    /// Packet from the server carrying the payload.
+    fn from_server(id: PayloadId, payload: impl NetEncoder) -> Packet {
+        Packet::with_payload(PacketLabel::Extension(u8::from(id)), ClientId(0), payload)
+    }
+
+    /// Snapshot from the server with the full position of each entity.
+    fn full_snapshot(id: u32, input_seq: u32, positions: &[(u32, Vec2f)]) -> Packet {
+        let entries: Vec<_> = positions
+            .iter()
+            .map(|&(entity, pos)| SnapshotEntry(entity, SnapshotPosition::Full(pos), Vec2f::ZERO))
+            .collect();
+        let snapshot = Snapshot {
+            id,
+            baseline: None,
+            input_seq,
+            entries: entries.into(),
+        };
+        from_server(PayloadId::Snapshot, snapshot)
+    }
+
+    #[test]
+    fn server_packets_update_the_local_player() {
+        let mut state = ClientGameState::default();
+        let spawn = Vec2f(2.0, 2.0);
+        state
+            .handle_packet(&from_server(PayloadId::Connect, Connect(3, spawn)))
+            .unwrap();
+        assert_eq!(state.local_position(), Some(spawn));
+
+        // Moved ahead of the server, which has yet to see the input.
+        assert!(state.move_local(Vec2f(1.0, 0.0)));
+        state.queue_move(Movement(Vec2f(1.0, 0.0), 1));
+        let MovementInput(_, seq) = state.take_move(Duration::ZERO).unwrap();
+        let ack = state
+            .handle_packet(&full_snapshot(0, 0, &[(3, spawn), (9, Vec2f(5.0, 5.0))]))
+            .unwrap();
+        assert_eq!(ack.map(|SnapshotAck(id)| id), Some(0));
+
+        state.interpolate();
+        assert_eq!(state.local_position(), Some(Vec2f(3.0, 2.0)));
+        assert_eq!(shown(&state, 9), Vec2f(5.0, 5.0));
+
+        // Once processed, the server's position is used as it is.
+        state
+            .handle_packet(&full_snapshot(1, seq, &[(3, Vec2f(3.5, 2.0))]))
+            .unwrap();
+        state.interpolate();
+        assert_eq!(state.local_position(), Some(Vec2f(3.5, 2.0)));
+    }
+}
@@ -9,6 +9,7 @@ use client::ClientCore;
 use error::{AppError, Result};
 use net::{Socket, SocketOptions};
 use server::ServerCore;
+use utils::log::LogLevel;
 
 mod client;
 mod error;
@@ -28,17 +29,19 @@ enum Flags {
     Client,
     Server,
     Solo,
+    Threaded,
 }
 
 impl Flags {
     /// List of currently enabled valid flags for the application.
-    const ENABLED: [Flags; 6] = [
+    const ENABLED: [Flags; 7] = [
         Flags::Help,
         Flags::Remote,
         Flags::Local,
         Flags::Client,
         Flags::Server,
         Flags::Solo,
+        Flags::Threaded,
     ];
 
     /// Creates the help message for the application.
@@ -64,6 +67,9 @@ impl Flags {
             Flags::Client => String::from("--client: Run as a client."),
             Flags::Server => String::from("--server: Run as a server."),
             Flags::Solo => String::from("--solo: Run both client and server in the same process."),
+            Flags::Threaded => {
+                String::from("--threaded: Run the server's networking on a separate thread.")
+            }
         }
     }
 }
@@ -77,6 +83,7 @@ impl From<&Flags> for &'static str {
             Flags::Client => "--client",
             Flags::Server => "--server",
             Flags::Solo => "--solo",
+            Flags::Threaded => "--threaded",
         }
     }
 }
@@ -110,8 +117,9 @@ fn as_solo(args: &[String]) -> std::result::Result<(), std::boxed::Box<dyn std::
     let flag_clone = Arc::clone(&shutdown_flag);
 
     // Spawn the server with a connection in a separate thread.
+    let threaded = args.contains(&Flags::Threaded.to_string());
     let server_run = std::thread::spawn(move || {
-        let _ = ServerCore::new(sconn, Some(flag_clone)).run(SERVER_TICK_RATE);
+        let _ = new_server(sconn, Some(flag_clone), threaded).run(SERVER_TICK_RATE);
     });
 
     // Create the client with a connection.
@@ -134,11 +142,21 @@ fn as_client() -> std::result::Result<(), std::boxed::Box<dyn std::error::Error>
     Ok(())
 }
 
+/// Creates the server core, optionally running its networking on a separate thread.
+fn new_server(socket: Socket, sigint: Option<Arc<AtomicBool>>, threaded: bool) -> ServerCore {
+    if threaded {
+        ServerCore::new_threaded(socket, sigint)
+    } else {
+        ServerCore::new(socket, sigint)
+    }
+}
+
 /// Spawns a server that clients can connect to.
-fn as_server() -> std::result::Result<(), std::boxed::Box<dyn std::error::Error>> {
+fn as_server(args: &[String]) -> std::result::Result<(), std::boxed::Box<dyn std::error::Error>> {
     let server_opts = SocketOptions::default_server();
     let socket = Socket::new_remote(&server_opts).map_err(AppError::Net)?;
-    ServerCore::new(socket, None).run(SERVER_TICK_RATE)?;
+    let threaded = args.contains(&Flags::Threaded.to_string());
+    new_server(socket, None, threaded).run(SERVER_TICK_RATE)?;
     Ok(())
 }
 
@@ -150,7 +168,7 @@ fn main() {
     } else if args.contains(&Flags::Client.to_string()) {
         as_client()
     } else if args.contains(&Flags::Server.to_string()) {
-        as_server()
+        as_server(&args)
     } else if args.contains(&Flags::Solo.to_string()) {
         as_solo(&args)
     } else {
@@ -158,9 +176,10 @@ fn main() {
         Ok(())
     };
 
+    // Help goes to stdout for the user, the outcome goes through the logger with everything else.
     if let Err(why) = result {
-        println!("Error: {why}");
+        logln!(LogLevel::Error, "{why}");
     } else {
-        println!("Application exited successfully.");
+        logln!(LogLevel::Info, "Application exited successfully.");
     }
 }
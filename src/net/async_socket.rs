@@ -0,0 +1,156 @@
+use std::net::SocketAddr;
+
+use tokio::net::UdpSocket;
+
+use crate::flee;
+use crate::net::error::InvalidPacketError;
+
+use super::error::{NetError, Result};
+use super::traits::{NetDecoder, NetEncoder};
+use super::{ClientAddr, ClientId, Deliverable, Packet, Socket, SocketOptions};
+
+/// Asynchronous socket built on a tokio `UdpSocket`. Packets are validated and processed by the
+/// same logic as `Socket`, only the transport is driven by the async runtime.
+pub struct AsyncSocket {
+    udp: UdpSocket, // Raw asynchronous socket.
+    core: Socket,   // Handles validation, client storage, and packet actions.
+
+    buffer: Vec<u8>, // Buffer for receiving data.
+}
+
+#[allow(dead_code)]
+impl AsyncSocket {
+    /// Binds a new asynchronous socket using the given options.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::SocketError` if the socket cannot be bound or the server address is invalid.
+    pub async fn bind(opts: &SocketOptions) -> Result<Self> {
+        let addr = if opts.is_server() {
+            SocketOptions::DEFAULT_SERVER_ADDR
+        } else {
+            SocketOptions::DEFAULT_CLIENT_ADDR
+        };
+
+        let udp = match UdpSocket::bind(addr).await {
+            Ok(udp) => udp,
+            Err(why) => flee!(NetError::SocketError(why.to_string())),
+        };
+
+        Ok(Self {
+            udp,
+            core: Socket::new_detached(opts, addr.to_string())?,
+            buffer: vec![0; opts.recv_buffer_size],
+        })
+    }
+
+    /// Obtains the ID of the socket.
+    #[inline]
+    pub fn id(&self) -> ClientId {
+        self.core.id()
+    }
+
+    /// Obtains the synchronous socket that processes the packets.
+    #[inline]
+    pub fn inner(&self) -> &Socket {
+        &self.core
+    }
+
+    /// Sends a packet to the destination.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NotConnected` if the connection is not established.
+    /// - `NetError::SocketError` if there is a socket error.
+    pub async fn send(&mut self, deliverable: Deliverable) -> Result<()> {
+        self.core.send(deliverable)?;
+        self.flush().await
+    }
+
+    /// Waits to receive the next valid packet, replying to built-in packets such as pings and
+    /// connection requests before returning.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::InvalidPacket` if the packet is malformed or fails validation.
+    /// - `NetError::NotConnected` if the connection is not established.
+    /// - `NetError::SocketError` if there is a socket error.
+    pub async fn recv(&mut self) -> Result<Packet> {
+        loop {
+            // Reliable packets released by an earlier datagram are delivered first.
+            if let Some(packet) = self.core.try_recv()? {
+                return Ok(packet);
+            }
+
+            let (size, sender) = match self.udp.recv_from(&mut self.buffer).await {
+                Ok(received) => received,
+                Err(why) => flee!(NetError::SocketError(why.to_string())),
+            };
+
+            let client = ClientAddr::Ip(sender.ip(), sender.port());
+            if size >= self.buffer.len() {
+                flee!(NetError::InvalidPacket(
+                    client,
+                    InvalidPacketError::Header,
+                    format!(
+                        "datagram may be truncated, it filled the {} byte receive buffer",
+                        self.buffer.len()
+                    ),
+                ));
+            }
+
+            let packet = match Packet::decode(&self.buffer[..size]) {
+                Ok((packet, _)) => packet,
+                Err(NetError::NetCode(why)) => {
+                    flee!(NetError::InvalidPacket(
+                        client,
+                        InvalidPacketError::Header,
+                        why
+                    ))
+                }
+                Err(why) => flee!(why),
+            };
+
+            let result = self.core.process(client, packet);
+            self.flush().await?; // Deliver replies even if the packet was rejected.
+            if let Some(packet) = result? {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Runs the scheduled tasks, such as pings and disconnect sweeps, and sends their packets.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::SocketError` if there is a socket error.
+    /// - `NetError::Disconnected` if a client socket lost its connection to the server.
+    pub async fn run_tasks(&mut self) -> Result<()> {
+        let result = self.core.run_tasks(false);
+        self.flush().await?;
+        result
+    }
+
+    /// Sends all packets queued by the socket.
+    async fn flush(&mut self) -> Result<()> {
+        for (dest, packet) in self.core.take_outbox() {
+            let ClientAddr::Ip(ip, port) = dest else {
+                flee!(NetError::SocketError(
+                    "Cannot send to non-IP address".to_string()
+                ));
+            };
+
+            if let Err(why) = self
+                .udp
+                .send_to(&packet.encode(), SocketAddr::new(ip, port))
+                .await
+            {
+                flee!(NetError::SocketError(format!(
+                    "Unable to send packet: {why}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
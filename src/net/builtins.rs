@@ -8,11 +8,20 @@ use super::traits::{NetDecoder, NetEncoder};
 /// Built-in Connection payload.
 ///
 /// # Fields
-/// - `u8`: The packet version.
+/// - `u8`: The newest packet version supported by a client, or the version chosen by the server.
 /// - `ClientId`: The ID of the client.
 /// - `u64`: Amount of time in milliseconds to send ping.
+/// - `u16`: Random sequence base for the session, set by the server. Ignored when sent by a client.
+/// - `u8`: The oldest packet version supported by the sender.
 #[derive(NetEncode, NetDecode, Debug)]
-pub struct ConnectionPayload(pub u8, pub ClientId, pub u64);
+pub struct ConnectionPayload(pub u8, pub ClientId, pub u64, pub u16, pub u8);
+
+/// Built-in Acknowledge payload.
+///
+/// # Fields
+/// - `u16`: The reliable sequence being acknowledged.
+#[derive(NetEncode, NetDecode, Debug)]
+pub struct AckPayload(pub u16);
 
 /// Built-in Ping payload.
 ///
@@ -26,7 +35,7 @@ pub struct PingPayload(pub Duration, pub bool);
 ///
 /// # Fields
 /// - `ErrorPacket`: The error packet code.
-/// - `Option<String>`: An optional string message.
+/// - `String`: A human-readable message describing the error.
 #[derive(NetEncode, NetDecode, Debug)]
 pub struct ErrorPayload(pub ErrorPacket, pub String);
 
@@ -36,3 +45,25 @@ pub struct ErrorPayload(pub ErrorPacket, pub String);
 /// - `String`: The message string.
 #[derive(NetEncode, NetDecode, Debug)]
 pub struct MessagePayload(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{Packet, PacketLabel};
+
+    #[test]
+    fn error_payloads_keep_their_message() {
+        for code in [
+            ErrorPacket::TooManyConnections,
+            ErrorPacket::IncompatibleVersion(7),
+        ] {
+            let payload = ErrorPayload(code, format!("Server refused: {code}"));
+            let bytes = Packet::with_payload(PacketLabel::Error, ClientId(0), payload).encode();
+
+            let (packet, _) = Packet::decode(&bytes).unwrap();
+            let ErrorPayload(decoded, msg) = packet.payload::<ErrorPayload>().unwrap();
+            assert_eq!(decoded, code);
+            assert_eq!(msg, format!("Server refused: {code}"));
+        }
+    }
+}
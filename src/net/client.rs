@@ -77,18 +77,36 @@ impl std::hash::Hash for ClientAddr {
     }
 }
 
+impl ClientAddr {
+    /// Socket address of a remote client. None for local clients.
+    #[allow(dead_code)]
+    pub fn to_socket_addr(self) -> Option<SocketAddr> {
+        match self {
+            ClientAddr::Local(_) => None,
+            ClientAddr::Ip(ip, port) => Some(SocketAddr::new(ip, port)),
+        }
+    }
+}
+
+/// Remote clients are displayed as `IP:PORT`, with IPv6 addresses in brackets, so they can be
+/// parsed back into a `ClientAddr`.
 impl std::fmt::Display for ClientAddr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ClientAddr::Local(id) => write!(f, "Local({id})"),
-            #[cfg(feature = "shared_ip")]
-            ClientAddr::Ip(ip, port) => write!(f, "Ip({ip}, {port})"),
-            #[cfg(not(feature = "shared_ip"))]
-            ClientAddr::Ip(ip, _) => write!(f, "Ip({ip})"),
+            ClientAddr::Ip(ip, port) => write!(f, "{}", SocketAddr::new(*ip, *port)),
         }
     }
 }
 
+impl std::str::FromStr for ClientAddr {
+    type Err = std::net::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SocketAddr::from_str(s).map(ClientAddr::from)
+    }
+}
+
 impl From<SocketAddr> for ClientAddr {
     fn from(addr: SocketAddr) -> Self {
         ClientAddr::Ip(addr.ip(), addr.port())
@@ -100,3 +118,28 @@ impl From<ClientId> for ClientAddr {
         ClientAddr::Local(client_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn ipv6_addresses_round_trip_through_display() {
+        let socket = SocketAddr::from_str("[2001:db8::1]:4500").unwrap();
+        let addr = ClientAddr::from(socket);
+
+        let shown = addr.to_string();
+        assert_eq!(shown, "[2001:db8::1]:4500");
+
+        let parsed = ClientAddr::from_str(&shown).unwrap();
+        assert_eq!(parsed.to_socket_addr(), Some(socket));
+    }
+
+    #[test]
+    fn local_addresses_have_no_socket_address() {
+        let addr = ClientAddr::from(ClientId(4));
+        assert_eq!(addr.to_socket_addr(), None);
+        assert!(ClientAddr::from_str(&addr.to_string()).is_err());
+    }
+}
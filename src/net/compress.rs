@@ -0,0 +1,93 @@
+use super::error::{NetError, Result};
+
+/// Longest run of a repeated byte stored in a single block.
+const MAX_RUN: usize = 130;
+/// Shortest run worth storing as a run instead of literals.
+const MIN_RUN: usize = 3;
+/// Most literal bytes stored in a single block.
+const MAX_LITERALS: usize = 128;
+
+/// Compresses the data with run-length encoding. Each block starts with a control byte, where
+/// `0..=127` is followed by that many plus one literal bytes, and `128..=255` is followed by a
+/// single byte repeated that many minus 125 times.
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut literals = 0; // Start of the pending literals, relative to `idx`.
+    let mut idx = 0;
+
+    while idx < data.len() {
+        let run = data[idx..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|byte| **byte == data[idx])
+            .count();
+
+        if run >= MIN_RUN {
+            flush_literals(&mut out, &data[idx - literals..idx]);
+            literals = 0;
+
+            #[allow(clippy::cast_possible_truncation)]
+            out.push((run + 125) as u8);
+            out.push(data[idx]);
+            idx += run;
+        } else {
+            literals += 1;
+            idx += 1;
+        }
+    }
+
+    flush_literals(&mut out, &data[idx - literals..]);
+    out
+}
+
+/// Writes literal bytes as one or more literal blocks.
+fn flush_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERALS) {
+        #[allow(clippy::cast_possible_truncation)]
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Decompresses data produced by `compress`, refusing to produce more than `max_len` bytes.
+///
+/// # Errors
+///
+/// - `NetError::NetCode` if a block is truncated or the output would exceed `max_len`.
+pub(crate) fn decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len().min(max_len));
+    let mut idx = 0;
+
+    while idx < data.len() {
+        let control = usize::from(data[idx]);
+        idx += 1;
+
+        if control < MAX_LITERALS {
+            let Some(literals) = data.get(idx..idx + control + 1) else {
+                return Err(NetError::NetCode(
+                    "Not enough bytes to decompress literals".to_string(),
+                ));
+            };
+
+            out.extend_from_slice(literals);
+            idx += literals.len();
+        } else {
+            let Some(byte) = data.get(idx) else {
+                return Err(NetError::NetCode(
+                    "Not enough bytes to decompress run".to_string(),
+                ));
+            };
+
+            out.resize(out.len() + control - 125, *byte);
+            idx += 1;
+        }
+
+        if out.len() > max_len {
+            return Err(NetError::NetCode(format!(
+                "Decompressed payload exceeds {max_len} bytes"
+            )));
+        }
+    }
+
+    Ok(out)
+}
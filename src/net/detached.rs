@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use super::error::Result;
+use super::traits::SocketHandler;
+use super::{ClientAddr, Packet};
+
+/// Connection without its own transport. Outgoing packets are queued for the owner to deliver and
+/// incoming packets are handed to the socket directly, allowing an external runtime to drive I/O.
+pub(crate) struct DetachedSocket {
+    local_addr: String, // Local address of the transport driving the socket.
+    outbox: RefCell<VecDeque<(ClientAddr, Packet)>>, // Packets waiting to be delivered.
+}
+
+impl DetachedSocket {
+    /// Creates a new detached connection for a transport bound to the given address.
+    pub(crate) fn new(local_addr: String) -> Self {
+        Self {
+            local_addr,
+            outbox: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Obtains the address of the transport.
+    #[inline]
+    pub(crate) fn address(&self) -> &str {
+        &self.local_addr
+    }
+
+    /// Removes and returns all packets waiting to be delivered.
+    pub(crate) fn drain(&mut self) -> Vec<(ClientAddr, Packet)> {
+        self.outbox.get_mut().drain(..).collect()
+    }
+}
+
+impl SocketHandler for DetachedSocket {
+    #[inline]
+    fn send(&self, dest: &ClientAddr, packet: Packet) -> Result<()> {
+        self.outbox.borrow_mut().push_back((*dest, packet));
+        Ok(())
+    }
+
+    #[inline]
+    fn try_recv(&mut self) -> Result<Option<(ClientAddr, Packet)>> {
+        Ok(None) // Packets are received by the owner.
+    }
+
+    #[inline]
+    fn recv(&mut self) -> Result<Option<(ClientAddr, Packet)>> {
+        Ok(None) // Packets are received by the owner.
+    }
+}
@@ -1,5 +1,6 @@
 use super::ClientAddr;
 use super::netcode_derive::{NetDecode, NetEncode};
+use super::storage::StorageError;
 use super::traits::{NetDecoder, NetEncoder};
 
 /// Result type for network actions.
@@ -8,10 +9,16 @@ pub(crate) type Result<T> = std::result::Result<T, NetError>;
 /// Error codes included in the `PacketLabel::Error` packet.
 #[derive(Debug, PartialEq, Copy, Clone, NetEncode, NetDecode)]
 pub enum ErrorPacket {
-    TooManyConnections,   // Too many connections.
-    Blacklisted,          // Connection is blacklisted.
+    #[netcode(tag = 0)]
+    TooManyConnections, // Too many connections.
+    #[netcode(tag = 1)]
+    Blacklisted, // Connection is blacklisted.
+    #[netcode(tag = 2)]
     InvalidPacketVersion, // Invalid packet version.
-    Unknown,              // Unknown error.
+    #[netcode(tag = 3)]
+    Unknown, // Unknown error.
+    #[netcode(tag = 4)]
+    IncompatibleVersion(u8), // No version is supported by both sides, holds the server's newest.
 }
 
 impl std::fmt::Display for ErrorPacket {
@@ -21,10 +28,15 @@ impl std::fmt::Display for ErrorPacket {
             ErrorPacket::Blacklisted => write!(f, "Connection is blacklisted"),
             ErrorPacket::InvalidPacketVersion => write!(f, "Invalid packet version"),
             ErrorPacket::Unknown => write!(f, "Unknown error"),
+            ErrorPacket::IncompatibleVersion(version) => {
+                write!(f, "Incompatible version, server supports up to {version}")
+            }
         }
     }
 }
 
+impl std::error::Error for ErrorPacket {}
+
 /// Represents errors that can occur when processing packets.
 #[derive(Debug, PartialEq, Eq)]
 pub enum InvalidPacketError {
@@ -32,6 +44,7 @@ pub enum InvalidPacketError {
     Version, // The packet version is invalid or unsupported.
     Source,  // The source of the packet is invalid, ClientId or Address.
     Payload, // The payload of the packet is invalid or cannot be decoded.
+    Sequence, // The sequence number is outside of the current session's window.
 }
 
 impl std::fmt::Display for InvalidPacketError {
@@ -41,10 +54,13 @@ impl std::fmt::Display for InvalidPacketError {
             InvalidPacketError::Version => write!(f, "Invalid packet version"),
             InvalidPacketError::Source => write!(f, "Invalid packet source"),
             InvalidPacketError::Payload => write!(f, "Invalid packet payload"),
+            InvalidPacketError::Sequence => write!(f, "Invalid packet sequence"),
         }
     }
 }
 
+impl std::error::Error for InvalidPacketError {}
+
 /// Error codes for various connection actions.
 #[derive(Debug, PartialEq)]
 pub enum NetError {
@@ -56,7 +72,7 @@ pub enum NetError {
     SocketError(String),      // Socket error occurred. Unrecoverable.
 
     // Storage errors.
-    StorageError(String), // Error in storage.
+    Storage(StorageError), // Error in storage.
 
     // Packet errors.
     NetCode(String),                                       // Network code error.
@@ -68,7 +84,7 @@ impl std::fmt::Display for NetError {
         match self {
             NetError::NothingToDo => write!(f, "nothing to do"),
             NetError::Disconnected => write!(f, "disconnected from the connection"),
-            NetError::StorageError(why) => write!(f, "storage experienced {why}"),
+            NetError::Storage(why) => write!(f, "storage experienced {why}"),
             NetError::SocketError(why) => write!(f, "socket error: {why}"),
             NetError::NetCode(why) => write!(f, "network code error: {why}"),
             NetError::NotConnected(client) => write!(f, "not connected to destination {client}"),
@@ -79,4 +95,59 @@ impl std::fmt::Display for NetError {
     }
 }
 
-impl std::error::Error for NetError {}
+impl std::error::Error for NetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetError::Storage(why) => Some(why),
+            NetError::InvalidPacket(_, error, _) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<StorageError> for NetError {
+    fn from(error: StorageError) -> Self {
+        NetError::Storage(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::net::ClientId;
+
+    /// Collects the message of the error and of every source below it.
+    fn chain(error: &(dyn Error + 'static)) -> Vec<String> {
+        std::iter::successors(Some(error), |&error| error.source())
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn boxed_errors_walk_their_source_chain() {
+        let boxed: Box<dyn Error> = Box::new(NetError::from(StorageError::AtCapacity));
+        assert_eq!(
+            chain(boxed.as_ref()),
+            ["storage experienced capacity reached", "capacity reached"]
+        );
+
+        let invalid = NetError::InvalidPacket(
+            ClientAddr::Local(ClientId(2)),
+            InvalidPacketError::Payload,
+            "too large".to_string(),
+        );
+        let boxed: Box<dyn Error> = Box::new(invalid);
+        let messages = chain(boxed.as_ref());
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1], InvalidPacketError::Payload.to_string());
+    }
+
+    #[test]
+    fn errors_without_a_cause_have_no_source() {
+        let boxed: Box<dyn Error> = Box::new(NetError::Disconnected);
+        assert!(boxed.source().is_none());
+        assert!(StorageError::TimedOut.source().is_none());
+    }
+}
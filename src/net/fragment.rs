@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::error::Result;
+use super::{ClientId, Packet};
+
+/// Fragments received for a packet that is not yet complete.
+struct PartialPacket {
+    count: u16,                 // Number of fragments the packet was split into.
+    parts: Vec<Option<Packet>>, // Fragments received so far, grown to the highest index received.
+    received: u16,              // Number of distinct fragments received.
+    updated: Instant,           // Time the last fragment was received.
+}
+
+/// Splits outgoing packets that exceed the datagram size and reassembles incoming fragments.
+pub(crate) struct FragmentBuffer {
+    next_id: u16, // Message ID for the next split packet.
+    pending: HashMap<(ClientId, u16), PartialPacket>, // Incomplete packets by source and message ID.
+}
+
+impl FragmentBuffer {
+    /// Maximum number of fragments a single packet may be split into.
+    const MAX_FRAGMENTS: u16 = 1024;
+    /// Maximum number of incomplete packets buffered for a single source.
+    const MAX_PENDING_PER_SOURCE: usize = 8;
+    /// Maximum number of incomplete packets buffered across every source.
+    const MAX_PENDING: usize = 256;
+
+    /// Creates a new empty fragment buffer.
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Splits a packet into fragments carrying at most `max_payload` bytes of payload each.
+    pub fn split(&mut self, packet: Packet, max_payload: usize) -> Result<Vec<Packet>> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        packet.split(id, max_payload)
+    }
+
+    /// Stores a received fragment. Returns the reassembled packet once every fragment has arrived.
+    /// Fragments with an invalid index or count are dropped, as are fragments of new packets once
+    /// the source or the buffer as a whole has too many incomplete packets.
+    pub fn insert(&mut self, packet: Packet) -> Option<Packet> {
+        let fragment = packet.fragment()?;
+        if fragment.count == 0
+            || fragment.count > Self::MAX_FRAGMENTS
+            || fragment.index >= fragment.count
+        {
+            return None;
+        }
+
+        let key = (packet.source(), fragment.id);
+        if !self.pending.contains_key(&key) && !self.has_room(packet.source()) {
+            return None;
+        }
+
+        let partial = self.pending.entry(key).or_insert_with(|| PartialPacket {
+            count: fragment.count,
+            parts: Vec::new(),
+            received: 0,
+            updated: Instant::now(),
+        });
+
+        if partial.count != fragment.count {
+            // Count disagrees with earlier fragments, the set cannot be trusted.
+            self.pending.remove(&key);
+            return None;
+        }
+
+        let index = usize::from(fragment.index);
+        if partial.parts.len() <= index {
+            partial.parts.resize(index + 1, None);
+        }
+
+        let slot = &mut partial.parts[index];
+        if slot.is_none() {
+            partial.received += 1;
+        }
+        *slot = Some(packet);
+        partial.updated = Instant::now();
+
+        if partial.received < partial.count {
+            return None;
+        }
+
+        let partial = self.pending.remove(&key)?;
+        Packet::join(partial.parts.into_iter().flatten().collect())
+    }
+
+    /// Checks if another incomplete packet from `source` can be buffered.
+    fn has_room(&self, source: ClientId) -> bool {
+        self.pending.len() < Self::MAX_PENDING
+            && self
+                .pending
+                .keys()
+                .filter(|(from, _)| *from == source)
+                .count()
+                < Self::MAX_PENDING_PER_SOURCE
+    }
+
+    /// Drops incomplete packets that have not received a fragment within `timeout_ms`.
+    /// Returns the number of packets dropped.
+    pub fn drain_expired(&mut self, timeout_ms: u64) -> usize {
+        let timeout = Duration::from_millis(timeout_ms);
+        let before = self.pending.len();
+        self.pending
+            .retain(|_, partial| partial.updated.elapsed() < timeout);
+        before - self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::net::PacketLabel;
+
+    /// Splits a packet from `source` with a 40 byte payload into 4 fragments.
+    fn fragments(buffer: &mut FragmentBuffer, source: ClientId) -> Vec<Packet> {
+        let packet = Packet::with_payload(PacketLabel::Ping, source, vec![7u8; 40]);
+        buffer.split(packet, 10).unwrap()
+    }
+
+    #[test]
+    fn out_of_order_fragments_are_reassembled() {
+        let mut buffer = FragmentBuffer::new();
+        let mut parts = fragments(&mut buffer, ClientId(1));
+        parts.swap(0, 3);
+        parts.swap(1, 2);
+
+        let last = parts.pop().unwrap();
+        for part in parts {
+            assert!(buffer.insert(part).is_none());
+        }
+
+        let whole = buffer.insert(last).expect("packet was not reassembled");
+        assert!(whole.fragment().is_none());
+        assert_eq!(whole.payload::<Vec<u8>>().unwrap(), vec![7u8; 40]);
+        assert!(buffer.pending.is_empty());
+    }
+
+    #[test]
+    fn incomplete_packets_time_out() {
+        let mut buffer = FragmentBuffer::new();
+        let parts = fragments(&mut buffer, ClientId(1));
+        assert!(buffer.insert(parts[0].clone()).is_none());
+
+        assert_eq!(buffer.drain_expired(1000), 0);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(buffer.drain_expired(20), 1);
+        assert!(buffer.pending.is_empty());
+    }
+
+    #[test]
+    fn slots_grow_only_to_the_highest_index() {
+        let mut buffer = FragmentBuffer::new();
+        let parts = fragments(&mut buffer, ClientId(1));
+        assert!(buffer.insert(parts[1].clone()).is_none());
+
+        let partial = buffer.pending.values().next().unwrap();
+        assert_eq!(partial.parts.len(), 2);
+    }
+
+    #[test]
+    fn incomplete_packets_are_capped_per_source() {
+        let mut buffer = FragmentBuffer::new();
+        for _ in 0..FragmentBuffer::MAX_PENDING_PER_SOURCE {
+            let parts = fragments(&mut buffer, ClientId(1));
+            assert!(buffer.insert(parts[0].clone()).is_none());
+        }
+
+        let parts = fragments(&mut buffer, ClientId(1));
+        buffer.insert(parts[0].clone());
+        assert_eq!(buffer.pending.len(), FragmentBuffer::MAX_PENDING_PER_SOURCE);
+
+        // Other sources still have room.
+        let parts = fragments(&mut buffer, ClientId(2));
+        buffer.insert(parts[0].clone());
+        assert_eq!(
+            buffer.pending.len(),
+            FragmentBuffer::MAX_PENDING_PER_SOURCE + 1
+        );
+    }
+}
@@ -1,96 +1,142 @@
-use std::sync::mpsc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, mpsc};
 
 use crate::flee;
 
 use super::error::{NetError, Result};
 use super::traits::SocketHandler;
-use super::{ClientAddr, Packet};
+use super::{ClientAddr, ClientId, Packet};
 
-/// Local connection that uses MPSC to communicate locally.
+/// Packet along with the local address of the socket that sent it.
+type LocalPacket = (ClientId, Packet);
+
+/// Senders for each local address a socket can reach, shared so a hub can add routes to a
+/// socket after it was created.
+type Routes = Arc<Mutex<HashMap<ClientId, mpsc::Sender<LocalPacket>>>>;
+
+/// Local connection that uses MPSC to communicate locally. Packets are routed by the local
+/// address of the destination.
 pub(crate) struct LocalSocket {
-    tx: Option<mpsc::Sender<Packet>>,   // Sender for the connection.
-    rx: Option<mpsc::Receiver<Packet>>, // Receiver for the connection.
+    addr: ClientId,                  // Local address of the socket.
+    routes: Routes,                  // Senders for the reachable sockets.
+    rx: mpsc::Receiver<LocalPacket>, // Receiver for the connection.
 }
 
 impl LocalSocket {
-    /// Creates a new local connection.
-    pub(crate) fn new() -> Self {
-        Self { tx: None, rx: None }
-    }
-
-    /// Creates the receiver for the connection.
-    pub(crate) fn create_rx(&mut self) -> Result<mpsc::Receiver<Packet>> {
-        if self.tx.is_some() {
-            flee!(NetError::SocketError(
-                "Cannot create receiver when sender already exists".to_string()
-            ));
-        }
-
-        let (tx, rx) = mpsc::channel::<Packet>();
-        self.tx = Some(tx);
-        Ok(rx)
-    }
-
-    /// Sets the receiver for the connection.
-    pub(crate) fn set_rx(&mut self, rx: mpsc::Receiver<Packet>) -> Result<()> {
-        if self.rx.is_some() {
-            flee!(NetError::SocketError(
-                "Cannot set receiver when one already exists".to_string()
-            ));
-        }
-
-        self.rx = Some(rx);
-        Ok(())
-    }
-
     /// Obtains the address of the socket.
     #[inline]
     pub(crate) fn address() -> &'static str {
         "localhost"
     }
+
+    /// Wraps a received packet with the address of the socket that sent it.
+    #[inline]
+    fn received((from, packet): LocalPacket) -> (ClientAddr, Packet) {
+        (ClientAddr::Local(from), packet)
+    }
 }
 
 impl SocketHandler for LocalSocket {
     #[inline]
-    fn send(&self, _dest: &ClientAddr, packet: Packet) -> Result<()> {
-        if let Some(sender) = &self.tx {
+    fn send(&self, dest: &ClientAddr, packet: Packet) -> Result<()> {
+        let ClientAddr::Local(to) = dest else {
+            flee!(NetError::SocketError(format!(
+                "Cannot send to non-local address {dest}"
+            )));
+        };
+
+        let routes = self
+            .routes
+            .lock()
+            .map_err(|_| NetError::SocketError("Local routes were poisoned".to_string()))?;
+
+        if let Some(sender) = routes.get(to) {
             sender
-                .send(packet)
+                .send((self.addr, packet))
                 .map_err(|_| NetError::SocketError("Failed to send packet".to_string()))?;
             Ok(())
         } else {
-            flee!(NetError::SocketError(
-                "Cannot send to a socket that has no sender".to_string()
-            ));
+            flee!(NetError::SocketError(format!(
+                "Cannot send to a socket that has no sender: {dest}"
+            )));
         }
     }
 
     #[inline]
     fn try_recv(&mut self) -> Result<Option<(ClientAddr, Packet)>> {
-        if let Some(rx) = &self.rx {
-            match rx.try_recv() {
-                Ok(packet) => Ok(Some((ClientAddr::Local(packet.source()), packet))),
-                Err(mpsc::TryRecvError::Empty) => Ok(None),
-                Err(mpsc::TryRecvError::Disconnected) => flee!(NetError::Disconnected),
-            }
-        } else {
-            flee!(NetError::SocketError(
-                "Cannot receive from a socket that has no receiver".to_string()
-            ));
+        match self.rx.try_recv() {
+            Ok(packet) => Ok(Some(Self::received(packet))),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => flee!(NetError::Disconnected),
         }
     }
 
     #[inline]
     fn recv(&mut self) -> Result<Option<(ClientAddr, Packet)>> {
-        if let Some(rx) = &self.rx {
-            match rx.recv() {
-                Ok(packet) => Ok(Some((ClientAddr::Local(packet.source()), packet))),
-                Err(_) => flee!(NetError::Disconnected),
-            }
-        } else {
+        match self.rx.recv() {
+            Ok(packet) => Ok(Some(Self::received(packet))),
+            Err(_) => flee!(NetError::Disconnected),
+        }
+    }
+}
+
+/// Connects any number of local clients to a single local server. Each client is given its own
+/// local address that the server routes its packets by.
+pub struct LocalHub {
+    server_tx: mpsc::Sender<LocalPacket>, // Sender for the server, cloned for each client.
+    server_routes: Routes,                // Routes of the server, one per client.
+    next_addr: ClientId,                  // Local address of the next client.
+}
+
+impl LocalHub {
+    /// Local address of the server, matching the ID clients use to address it.
+    const SERVER_ADDR: ClientId = ClientId(0);
+
+    /// Creates a new hub along with the socket for its server.
+    pub(crate) fn new() -> (Self, LocalSocket) {
+        let (server_tx, rx) = mpsc::channel::<LocalPacket>();
+        let server_routes = Routes::default();
+
+        let server = LocalSocket {
+            addr: Self::SERVER_ADDR,
+            routes: Arc::clone(&server_routes),
+            rx,
+        };
+
+        let hub = Self {
+            server_tx,
+            server_routes,
+            next_addr: ClientId(1),
+        };
+
+        (hub, server)
+    }
+
+    /// Creates the socket for a new client that can reach the server.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::SocketError` if the local addresses are exhausted or the routes were poisoned.
+    pub(crate) fn client(&mut self) -> Result<LocalSocket> {
+        let addr = self.next_addr;
+        if addr == ClientId::INVALID {
             flee!(NetError::SocketError(
-                "Cannot receive from a socket that has no receiver".to_string()
+                "No local addresses remain for another client".to_string()
             ));
         }
+
+        let (tx, rx) = mpsc::channel::<LocalPacket>();
+        self.server_routes
+            .lock()
+            .map_err(|_| NetError::SocketError("Local routes were poisoned".to_string()))?
+            .insert(addr, tx);
+        self.next_addr = ClientId(addr.0 + 1);
+
+        let routes = HashMap::from([(Self::SERVER_ADDR, self.server_tx.clone())]);
+        Ok(LocalSocket {
+            addr,
+            routes: Arc::new(Mutex::new(routes)),
+            rx,
+        })
     }
 }
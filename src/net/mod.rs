@@ -1,7 +1,15 @@
+#[cfg(feature = "async")]
+mod async_socket;
 mod client;
+#[cfg(feature = "compress")]
+mod compress;
+#[cfg(feature = "async")]
+mod detached;
+mod fragment;
 mod local;
 mod opts;
 mod packet;
+mod reliable;
 mod remote;
 mod socket;
 mod task;
@@ -11,6 +19,7 @@ pub mod error;
 pub mod storage;
 pub mod traits;
 
+pub use local::LocalHub;
 pub(crate) use local::LocalSocket;
 pub(crate) use remote::RemoteSocket;
 
@@ -18,9 +27,15 @@ pub use netcode_derive;
 
 pub use client::{ClientAddr, ClientId};
 pub use opts::SocketOptions;
+#[allow(unused_imports)]
+pub use packet::PayloadFormat;
 pub use packet::{Packet, PacketLabel};
 pub use socket::Socket;
 
+#[cfg(feature = "async")]
+#[allow(unused_imports)]
+pub use async_socket::AsyncSocket;
+
 /// Used to specify the destination and packet for a socket action.
 pub struct Deliverable {
     pub(crate) to: ClientId,   // ID of the destination user.
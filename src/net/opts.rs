@@ -1,3 +1,5 @@
+use super::Packet;
+
 pub struct SocketOptions {
     /// The maximum number of clients that can be connected to this socket.
     pub(crate) max_clients: u16,
@@ -15,6 +17,18 @@ pub struct SocketOptions {
     pub(crate) disconnect_interval_ms: Option<u64>,
     /// Interval for sending ping packets.
     pub(crate) ping_interval_ms: Option<u64>,
+    /// Servers ping every connected client at the ping interval.
+    pub(crate) server_keepalive: bool,
+    /// Time to wait for an acknowledgement before resending a reliable packet.
+    pub(crate) reliable_retry_ms: u64,
+    /// Largest datagram to send. Larger packets are split into fragments.
+    pub(crate) max_datagram_size: usize,
+    /// Size of the buffer used to receive datagrams.
+    pub(crate) recv_buffer_size: usize,
+    /// Time to wait for the remaining fragments of a packet before dropping it.
+    pub(crate) fragment_timeout_ms: u64,
+    /// Bytes each peer may be sent per second before low priority packets are dropped.
+    pub(crate) max_bytes_per_sec: Option<usize>,
 }
 
 #[allow(dead_code)]
@@ -35,6 +49,12 @@ impl SocketOptions {
             error_reset_interval_ms: None,
             disconnect_interval_ms: Some(15000),
             ping_interval_ms: Some(5000),
+            server_keepalive: false,
+            reliable_retry_ms: 250,
+            max_datagram_size: 1000,
+            recv_buffer_size: 1024,
+            fragment_timeout_ms: 5000,
+            max_bytes_per_sec: None,
         }
     }
 
@@ -49,6 +69,66 @@ impl SocketOptions {
             error_reset_interval_ms: Some(60000),
             disconnect_interval_ms: Some(15000),
             ping_interval_ms: None,
+            server_keepalive: false,
+            reliable_retry_ms: 250,
+            max_datagram_size: 1000,
+            recv_buffer_size: 1024,
+            fragment_timeout_ms: 5000,
+            max_bytes_per_sec: None,
+        }
+    }
+
+    /// Options for tests, using sub-second intervals so that scheduled tasks (archive and
+    /// blacklist draining, disconnect sweeps, pings) fire within the lifetime of a test.
+    #[cfg(test)]
+    pub fn for_loopback_test(is_server: bool) -> Self {
+        let base = Self::new(is_server)
+            .task_interval(10)
+            .disconnect_interval(500)
+            .reliable_retry(50)
+            .fragment_timeout(250);
+        if is_server {
+            base.max_clients(4)
+                .archive_interval(250)
+                .blacklist_interval(250)
+                .error_reset_interval(500)
+        } else {
+            base.ping_interval(100)
+        }
+    }
+
+    /// Options for peers on a local network where latency is low and loss is rare. Dead peers are
+    /// dropped after 3 seconds, clients ping every second, reliable packets are resent after 50ms,
+    /// and incomplete fragments are dropped after 1 second. Tasks are checked every 50ms to keep
+    /// up with the retries. Other options keep their defaults and can be overridden by chaining.
+    pub fn lan(is_server: bool) -> Self {
+        let base = Self::new(is_server)
+            .task_interval(50)
+            .disconnect_interval(3000)
+            .reliable_retry(50)
+            .fragment_timeout(1000);
+        if is_server {
+            base
+        } else {
+            base.ping_interval(1000)
+        }
+    }
+
+    /// Options for peers across the internet where latency varies and packets are lost more
+    /// often. Dead peers are dropped after 30 seconds, clients ping every 5 seconds, reliable
+    /// packets are resent after 500ms, and incomplete fragments are dropped after 10 seconds.
+    /// Tasks are checked every 250ms. Other options keep their defaults and can be overridden by
+    /// chaining.
+    pub fn wan(is_server: bool) -> Self {
+        let base = Self::new(is_server)
+            .task_interval(250)
+            .disconnect_interval(30000)
+            .reliable_retry(500)
+            .fragment_timeout(10000);
+        if is_server {
+            base
+        } else {
+            base.ping_interval(5000)
         }
     }
 
@@ -61,6 +141,54 @@ impl SocketOptions {
         }
     }
 
+    /// Checks that the options can be used to create a socket, describing the first problem found.
+    /// The reliable retry is excluded from the interval check as it is documented to be rounded up
+    /// to the task interval.
+    ///
+    /// # Errors
+    ///
+    /// - `max_clients` is zero.
+    /// - `server_keepalive` is enabled without a ping interval.
+    /// - `task_interval_ms` is longer than the shortest enabled interval, delaying that task.
+    /// - `max_datagram_size` leaves no room for a payload after the packet header.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_clients == 0 {
+            return Err("max_clients must be at least 1".to_string());
+        } else if self.server_keepalive && self.ping_interval_ms.is_none() {
+            return Err("server keepalive requires a ping interval".to_string());
+        }
+
+        let delayed = [
+            ("archive", self.archive_interval_ms),
+            ("blacklist", self.blacklist_interval_ms),
+            ("error reset", self.error_reset_interval_ms),
+            ("disconnect", self.disconnect_interval_ms),
+            ("ping", self.ping_interval_ms),
+            ("fragment timeout", Some(self.fragment_timeout_ms)),
+        ]
+        .into_iter()
+        .filter_map(|(name, interval)| interval.map(|ms| (name, ms)))
+        .min_by_key(|(_, ms)| *ms)
+        .filter(|(_, ms)| self.task_interval_ms > *ms);
+
+        if let Some((name, interval_ms)) = delayed {
+            return Err(format!(
+                "task interval of {}ms is longer than the {name} interval of {interval_ms}ms",
+                self.task_interval_ms
+            ));
+        }
+
+        if self.max_datagram_size <= Packet::MAX_HEADER_SIZE {
+            return Err(format!(
+                "max datagram size of {} bytes must be larger than the {} byte packet header",
+                self.max_datagram_size,
+                Packet::MAX_HEADER_SIZE
+            ));
+        }
+
+        Ok(())
+    }
+
     // Returns true if the socket is configured as a server.
     pub fn is_server(&self) -> bool {
         self.server_address.is_none()
@@ -143,6 +271,59 @@ impl SocketOptions {
         self
     }
 
+    /// Sets if a server pings every connected client at the ping interval. Clients refresh their
+    /// activity when they reply, so half-open connections are detected by the disconnect sweep
+    /// sooner. Requires a ping interval to be set.
+    pub fn server_keepalive(mut self, enabled: bool) -> Self {
+        self.server_keepalive = enabled;
+        self
+    }
+
+    /// Sets the time to wait for an acknowledgement before resending a reliable packet in
+    /// milliseconds. Resends are checked by the task scheduler, so the delay is rounded up to
+    /// the task interval.
+    pub fn reliable_retry(mut self, retry_ms: u64) -> Self {
+        self.reliable_retry_ms = retry_ms;
+        self
+    }
+
+    /// Sets the largest datagram to send in bytes. Packets that do not fit are split into
+    /// fragments and reassembled by the receiver. Must be smaller than the receive buffer of the
+    /// peer.
+    pub fn max_datagram_size(mut self, size: usize) -> Self {
+        self.max_datagram_size = size;
+        self
+    }
+
+    /// Sets the size of the buffer used to receive datagrams in bytes. Datagrams that fill the
+    /// buffer are rejected as possibly truncated, so it should be larger than the
+    /// `max_datagram_size` of the peer.
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = size;
+        self
+    }
+
+    /// Sets the time to wait for the remaining fragments of a packet in milliseconds before the
+    /// received fragments are dropped.
+    pub fn fragment_timeout(mut self, timeout_ms: u64) -> Self {
+        self.fragment_timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Sets the number of bytes each peer may be sent per second. Once a peer's budget is used,
+    /// unreliable extension packets (such as state updates) to it are dropped until the next
+    /// second, while control and reliable packets are always sent.
+    pub fn max_bytes_per_sec(mut self, limit: usize) -> Self {
+        self.max_bytes_per_sec = Some(limit);
+        self
+    }
+
+    /// Disables the bandwidth limit.
+    pub fn disable_bandwidth_limit(mut self) -> Self {
+        self.max_bytes_per_sec = None;
+        self
+    }
+
     /// Disables the ping interval.
     pub fn disable_ping(mut self) -> Self {
         // Disables the ping interval by setting it to None
@@ -150,3 +331,103 @@ impl SocketOptions {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts the options are rejected with an error mentioning `reason`.
+    fn assert_rejected(opts: &SocketOptions, reason: &str) {
+        match opts.validate() {
+            Ok(()) => panic!("options were accepted, expected {reason:?}"),
+            Err(why) => assert!(why.contains(reason), "{why:?} does not mention {reason:?}"),
+        }
+    }
+
+    #[test]
+    fn defaults_are_accepted() {
+        assert_eq!(SocketOptions::default_server().validate(), Ok(()));
+        assert_eq!(SocketOptions::default_client().validate(), Ok(()));
+    }
+
+    #[test]
+    fn zero_clients_are_rejected() {
+        assert_rejected(&SocketOptions::new(true).max_clients(0), "max_clients");
+    }
+
+    #[test]
+    fn keepalive_without_ping_is_rejected() {
+        let opts = SocketOptions::new(true).server_keepalive(true);
+        assert_rejected(&opts, "keepalive");
+        assert_eq!(opts.ping_interval(1000).validate(), Ok(()));
+    }
+
+    #[test]
+    fn task_interval_longer_than_a_task_is_rejected() {
+        let opts = SocketOptions::new(false)
+            .task_interval(2000)
+            .ping_interval(1000);
+        assert_rejected(&opts, "ping interval of 1000ms");
+
+        // Disabled tasks are not checked, while the fragment timeout always is.
+        let opts = opts.disable_ping().fragment_timeout(5000);
+        assert_eq!(opts.validate(), Ok(()));
+        assert_rejected(&opts.fragment_timeout(1000), "fragment timeout");
+    }
+
+    #[test]
+    fn datagrams_without_room_for_a_payload_are_rejected() {
+        let opts = SocketOptions::new(true).max_datagram_size(Packet::MAX_HEADER_SIZE);
+        assert_rejected(&opts, "max datagram size");
+        assert_eq!(
+            opts.max_datagram_size(Packet::MAX_HEADER_SIZE + 1)
+                .validate(),
+            Ok(())
+        );
+    }
+
+    /// Intervals a preset changes: task, disconnect, ping, reliable retry, and fragment timeout.
+    fn intervals(opts: &SocketOptions) -> (u64, Option<u64>, Option<u64>, u64, u64) {
+        (
+            opts.task_interval_ms,
+            opts.disconnect_interval_ms,
+            opts.ping_interval_ms,
+            opts.reliable_retry_ms,
+            opts.fragment_timeout_ms,
+        )
+    }
+
+    #[test]
+    fn presets_set_their_intervals() {
+        let lan = SocketOptions::lan(false);
+        assert_eq!(intervals(&lan), (50, Some(3000), Some(1000), 50, 1000));
+        let wan = SocketOptions::wan(false);
+        assert_eq!(intervals(&wan), (250, Some(30000), Some(5000), 500, 10000));
+
+        // Servers do not ping unless keepalive is enabled, and keep their other defaults.
+        let lan = SocketOptions::lan(true);
+        assert_eq!(intervals(&lan), (50, Some(3000), None, 50, 1000));
+        let wan = SocketOptions::wan(true);
+        assert_eq!(intervals(&wan), (250, Some(30000), None, 500, 10000));
+        assert_eq!(wan.max_clients, SocketOptions::default_server().max_clients);
+
+        for is_server in [true, false] {
+            assert_eq!(SocketOptions::lan(is_server).validate(), Ok(()));
+            assert_eq!(SocketOptions::wan(is_server).validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn presets_can_be_overridden() {
+        let opts = SocketOptions::wan(false).ping_interval(2000);
+        assert_eq!(opts.ping_interval_ms, Some(2000));
+        assert_eq!(opts.reliable_retry_ms, 500);
+        assert_eq!(opts.validate(), Ok(()));
+
+        // Overrides are still checked against the rest of the preset.
+        assert_rejected(
+            &SocketOptions::lan(false).task_interval(2000),
+            "ping interval",
+        );
+    }
+}
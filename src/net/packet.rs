@@ -1,7 +1,11 @@
-use super::ClientId;
-use super::error::{NetError, Result};
+use std::mem;
+
+#[cfg(feature = "compress")]
+use super::compress::{compress, decompress};
+use super::error::{InvalidPacketError, NetError, Result};
 use super::netcode_derive::{NetDecode, NetEncode};
 use super::traits::{NetDecoder, NetEncoder};
+use super::{ClientAddr, ClientId};
 
 /// Packet labels for connections that can be sent.
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -11,7 +15,7 @@ pub enum PacketLabel {
     Error = 0x00,
     /// Acknowledge an action.
     Acknowledge,
-    /// Connect to a server or client.
+    /// Request to connect, sent by a client to a server.
     Connect,
     /// Disconnect from a server or client.
     Disconnect,
@@ -19,8 +23,35 @@ pub enum PacketLabel {
     Ping,
     /// Message packet, used to send a message to a server or client.
     Message,
-    /// Expandable packet label, can be >= 0x06.
+    /// Expandable packet label, can be >= 0x06 and < 0xFE. Sockets refuse to send lower or
+    /// higher values since they would be received as a builtin label.
     Extension(u8),
+    /// Accepts a connection, sent by a server in reply to `Connect`. Uses the reserved 0xFE.
+    ConnectAccept,
+}
+
+impl PacketLabel {
+    /// Smallest byte an `Extension` can use, lower bytes belong to builtin labels.
+    pub const EXTENSION_MIN: u8 = 0x06;
+    /// Largest byte an `Extension` can use, higher bytes are reserved for builtin labels.
+    pub const EXTENSION_MAX: u8 = 0xFD;
+
+    /// Creates an extension label. None if the value is used by a builtin label.
+    #[allow(dead_code)]
+    pub fn extension(value: u8) -> Option<Self> {
+        (Self::EXTENSION_MIN..=Self::EXTENSION_MAX)
+            .contains(&value)
+            .then_some(Self::Extension(value))
+    }
+
+    /// Checks if the label decodes back to itself once encoded. Only an `Extension` using the
+    /// byte of a builtin label fails, since it would be received as the builtin label.
+    pub fn is_valid(self) -> bool {
+        match self {
+            Self::Extension(value) => (Self::EXTENSION_MIN..=Self::EXTENSION_MAX).contains(&value),
+            _ => true,
+        }
+    }
 }
 
 impl NetEncoder for PacketLabel {
@@ -35,6 +66,7 @@ impl NetEncoder for PacketLabel {
             PacketLabel::Ping => 0x04,
             PacketLabel::Message => 0x05,
             PacketLabel::Extension(value) => value,
+            PacketLabel::ConnectAccept => 0xFE,
         };
         buffer
     }
@@ -55,23 +87,215 @@ impl NetDecoder for PacketLabel {
             0x03 => Ok((PacketLabel::Disconnect, 1)),
             0x04 => Ok((PacketLabel::Ping, 1)),
             0x05 => Ok((PacketLabel::Message, 1)),
+            0xFE => Ok((PacketLabel::ConnectAccept, 1)),
             value => Ok((PacketLabel::Extension(value), 1)),
         }
     }
 }
 
+/// Serialization format of a payload, stored as the first byte of the payload.
+#[allow(dead_code)]
+#[derive(PartialEq, Copy, Clone, Debug, NetEncode, NetDecode)]
+pub enum PayloadFormat {
+    Binary, // Compact binary encoding produced by `NetEncoder`.
+    Json,   // UTF-8 JSON text, human-readable for admin and debug tooling.
+}
+
+/// Position of a packet within a larger packet that was split to fit within a datagram.
+#[derive(PartialEq, Copy, Clone, Debug, NetEncode, NetDecode)]
+pub(crate) struct Fragment {
+    pub id: u16,    // Message ID shared by all fragments of the same packet.
+    pub index: u16, // Index of this fragment.
+    pub count: u16, // Total number of fragments.
+}
+
+/// Lookup table for the CRC32 (IEEE) checksum.
+#[cfg(feature = "checksum")]
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC32 (IEEE) checksum of the data.
+#[cfg(feature = "checksum")]
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(u32::MAX, |crc, byte| {
+        CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Flag byte before a payload that is sent as is.
+#[cfg(feature = "compress")]
+const PAYLOAD_RAW: u8 = 0x00;
+/// Flag byte before a payload that is run-length encoded.
+#[cfg(feature = "compress")]
+const PAYLOAD_RLE: u8 = 0x01;
+
+/// Encodes the payload. With the `compress` feature, it is prefixed with a flag byte and
+/// compressed unless that would not make it smaller.
+fn encode_payload(payload: Vec<u8>) -> Vec<u8> {
+    #[cfg(feature = "compress")]
+    {
+        let compressed = compress(&payload);
+        let (flag, payload) = if compressed.len() < payload.len() {
+            (PAYLOAD_RLE, compressed)
+        } else {
+            (PAYLOAD_RAW, payload)
+        };
+
+        let mut out = vec![flag];
+        out.extend(payload);
+        out
+    }
+
+    #[cfg(not(feature = "compress"))]
+    payload
+}
+
+/// Decodes the payload, decompressing it if the `compress` feature flagged it as compressed.
+fn decode_payload(data: &[u8]) -> Result<(Vec<u8>, usize)> {
+    #[cfg(feature = "compress")]
+    {
+        let Some((flag, rest)) = data.split_first() else {
+            return Err(NetError::NetCode(
+                "Not enough bytes to decode payload flag".to_string(),
+            ));
+        };
+
+        let (payload, used) = Vec::<u8>::decode(rest)?;
+        match *flag {
+            PAYLOAD_RAW => Ok((payload, used + 1)),
+            PAYLOAD_RLE => Ok((decompress(&payload, Packet::MAX_PAYLOAD_SIZE)?, used + 1)),
+            flag => Err(NetError::NetCode(format!("Unknown payload flag {flag}"))),
+        }
+    }
+
+    #[cfg(not(feature = "compress"))]
+    Vec::<u8>::decode(data)
+}
+
 /// A packet that be sent over a connection.
-#[derive(Debug, Clone, NetEncode, NetDecode)]
+#[derive(Debug, Clone)]
 pub struct Packet {
-    label: PacketLabel, // Label of the packet.
-    source: ClientId,   // ID of the source.
-    sequence: u16,      // Sequence number for ordering packets.
-    payload: Vec<u8>,   // Extra payload / data to be sent.
+    label: PacketLabel,         // Label of the packet.
+    source: ClientId,           // ID of the source.
+    sequence: u16,              // Sequence number for ordering packets.
+    reliable: Option<u16>,      // Reliable sequence if the packet must be acknowledged and ordered.
+    fragment: Option<Fragment>, // Set if the packet is one part of a larger packet.
+    payload: Vec<u8>,           // Extra payload / data to be sent.
+}
+
+impl NetEncoder for Packet {
+    /// Encodes the header and payload. With the `compress` feature, the payload is compressed
+    /// when that makes it smaller while the header is left as is. With the `checksum` feature, a
+    /// CRC32 of everything before it is appended.
+    fn encode(self) -> Vec<u8> {
+        let mut out = self.label.encode();
+        out.extend(self.source.encode());
+        out.extend(self.sequence.encode());
+        out.extend(self.reliable.encode());
+        out.extend(self.fragment.encode());
+        out.extend(encode_payload(self.payload));
+
+        #[cfg(feature = "checksum")]
+        out.extend(crc32(&out).encode());
+
+        out
+    }
+}
+
+impl NetDecoder for Packet {
+    /// Decodes the header and payload. With the `checksum` feature, the trailing CRC32 is
+    /// verified first and a mismatch is rejected.
+    fn decode(data: &[u8]) -> Result<(Self, usize)> {
+        #[cfg(feature = "checksum")]
+        let data = {
+            let Some(split) = data.len().checked_sub(4) else {
+                return Err(NetError::NetCode(
+                    "Not enough bytes to decode Packet checksum".to_string(),
+                ));
+            };
+
+            let (body, checksum) = data.split_at(split);
+            let (expected, _) = u32::decode(checksum)?;
+            if crc32(body) != expected {
+                return Err(NetError::NetCode("Packet checksum mismatch".to_string()));
+            }
+
+            body
+        };
+
+        let (label, mut offset) = PacketLabel::decode(data)?;
+        let (source, used) = ClientId::decode(&data[offset..])?;
+        offset += used;
+        let (sequence, used) = u16::decode(&data[offset..])?;
+        offset += used;
+        let (reliable, used) = Option::<u16>::decode(&data[offset..])?;
+        offset += used;
+        let (fragment, used) = Option::<Fragment>::decode(&data[offset..])?;
+        offset += used;
+        let (payload, used) = decode_payload(&data[offset..])?;
+        offset += used;
+
+        #[cfg(feature = "checksum")]
+        let offset = offset + 4;
+
+        Ok((
+            Self {
+                label,
+                source,
+                sequence,
+                reliable,
+                fragment,
+                payload,
+            },
+            offset,
+        ))
+    }
 }
 
 impl Packet {
-    /// Current version of Packets.
+    /// Current version of Packets, the newest one supported.
     pub(crate) const CURRENT_VERSION: u8 = 0x01;
+    /// Oldest version of Packets still supported.
+    pub(crate) const MIN_VERSION: u8 = 0x01;
+
+    /// Picks the newest version within both the supported range and the peer's `min..=max`
+    /// range. None if the ranges do not overlap.
+    pub(crate) fn negotiate_version(min: u8, max: u8) -> Option<u8> {
+        let version = max.min(Self::CURRENT_VERSION);
+        (version >= min.max(Self::MIN_VERSION)).then_some(version)
+    }
+
+    /// Largest encoded size of everything except the payload, used to determine how much payload
+    /// fits within a datagram.
+    pub(crate) const MAX_HEADER_SIZE: usize = 1 // Label.
+        + 2 // Source.
+        + 2 // Sequence.
+        + 3 // Reliable sequence.
+        + 7 // Fragment.
+        + if cfg!(feature = "compress") { 1 } else { 0 }
+        + if cfg!(feature = "checksum") { 4 } else { 0 };
+
+    /// Largest payload accepted by `try_set_payload`. Payloads larger than a datagram are
+    /// fragmented, this bounds how many fragments a single packet may need.
+    pub(crate) const MAX_PAYLOAD_SIZE: usize = 64 * 1024;
 
     /// Creates a new packet with the given type and sender UUID.
     #[inline]
@@ -80,10 +304,29 @@ impl Packet {
             label,
             source,
             sequence: 0,
+            reliable: None,
+            fragment: None,
             payload: vec![],
         }
     }
 
+    /// Creates a new packet with the given type, sender UUID, and payload.
+    #[inline]
+    pub fn with_payload(label: PacketLabel, source: ClientId, payload: impl NetEncoder) -> Self {
+        let mut packet = Self::new(label, source);
+        packet.set_payload(payload);
+        packet
+    }
+
+    /// Sets the sequence number of the packet, returning it for chaining.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn sequenced(mut self, sequence: u16) -> Self {
+        self.set_sequence(sequence);
+        self
+    }
+
     /// Obtains the type.
     #[inline]
     pub fn label(&self) -> PacketLabel {
@@ -115,7 +358,73 @@ impl Packet {
         self.sequence = sequence;
     }
 
-    /// Obtains the payload of the packet.
+    /// Reliable sequence of the packet. None if the packet is unreliable.
+    #[inline]
+    pub fn reliable(&self) -> Option<u16> {
+        self.reliable
+    }
+
+    /// Sets the reliable sequence of the packet.
+    #[inline]
+    pub(crate) fn set_reliable(&mut self, reliable: Option<u16>) {
+        self.reliable = reliable;
+    }
+
+    /// Fragment information of the packet. None if the packet is whole.
+    #[inline]
+    pub(crate) fn fragment(&self) -> Option<Fragment> {
+        self.fragment
+    }
+
+    /// Splits the packet into fragments carrying at most `max_payload` bytes of payload each.
+    /// Every fragment keeps the header of the original packet.
+    pub(crate) fn split(self, id: u16, max_payload: usize) -> Result<Vec<Packet>> {
+        let chunks: Vec<&[u8]> = self.payload.chunks(max_payload.max(1)).collect();
+        let Ok(count) = u16::try_from(chunks.len()) else {
+            return Err(NetError::NetCode(format!(
+                "Packet payload of {} bytes is too large to fragment",
+                self.payload.len()
+            )));
+        };
+
+        Ok(chunks
+            .into_iter()
+            .zip(0..)
+            .map(|(chunk, index)| Packet {
+                fragment: Some(Fragment { id, index, count }),
+                payload: chunk.to_vec(),
+                ..self.clone()
+            })
+            .collect())
+    }
+
+    /// Joins fragments, ordered by index, back into the original packet.
+    pub(crate) fn join(mut fragments: Vec<Packet>) -> Option<Packet> {
+        let payload = fragments
+            .iter_mut()
+            .flat_map(|p| mem::take(&mut p.payload))
+            .collect();
+        let mut packet = fragments.into_iter().next()?;
+        packet.fragment = None;
+        packet.payload = payload;
+        Some(packet)
+    }
+
+    /// Checks if the packet may be dropped when a peer is over its bandwidth budget. Only
+    /// unreliable extension packets, such as state updates, are low priority.
+    #[inline]
+    pub(crate) fn is_low_priority(&self) -> bool {
+        matches!(self.label, PacketLabel::Extension(_)) && self.reliable.is_none()
+    }
+
+    /// Length of the encoded payload in bytes.
+    #[inline]
+    pub(crate) fn payload_len(&self) -> usize {
+        self.payload.len()
+    }
+
+    /// Obtains the payload of the packet. Bytes left over after decoding are ignored, so newer
+    /// peers can append fields. Use `payload_exact` to reject them.
     #[inline]
     pub fn payload<T: NetDecoder>(&self) -> Result<T> {
         T::decode(&self.payload)
@@ -123,9 +432,334 @@ impl Packet {
             .map_err(|_| NetError::NetCode("Failed to decode payload".to_string()))
     }
 
+    /// Obtains the payload of the packet, requiring it to use every byte of the payload.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NetCode` if the payload fails to decode.
+    /// - `NetError::InvalidPacket` if bytes are left over after decoding.
+    #[allow(dead_code)]
+    pub fn payload_exact<T: NetDecoder>(&self) -> Result<T> {
+        let (payload, used) = T::decode(&self.payload)
+            .map_err(|_| NetError::NetCode("Failed to decode payload".to_string()))?;
+
+        if used != self.payload.len() {
+            return Err(NetError::InvalidPacket(
+                ClientAddr::Local(self.source),
+                InvalidPacketError::Payload,
+                format!(
+                    "Payload has {} trailing bytes after decoding",
+                    self.payload.len().saturating_sub(used)
+                ),
+            ));
+        }
+
+        Ok(payload)
+    }
+
     /// Sets the payload of the packet.
     #[inline]
     pub fn set_payload(&mut self, payload: impl NetEncoder) {
         self.payload = payload.encode();
     }
+
+    /// Sets the payload of the packet, rejecting payloads larger than `MAX_PAYLOAD_SIZE`. The
+    /// packet is left unchanged if the payload is rejected.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::InvalidPacket` if the encoded payload is too large.
+    /// - `NetError::NetCode` if the payload cannot be encoded.
+    #[allow(dead_code)]
+    pub fn try_set_payload(&mut self, payload: impl NetEncoder) -> Result<()> {
+        let payload = payload.try_encode()?;
+        if payload.len() > Self::MAX_PAYLOAD_SIZE {
+            return Err(NetError::InvalidPacket(
+                ClientAddr::Local(self.source),
+                InvalidPacketError::Payload,
+                format!(
+                    "payload of {} bytes exceeds the maximum of {} bytes",
+                    payload.len(),
+                    Self::MAX_PAYLOAD_SIZE
+                ),
+            ));
+        }
+
+        self.payload = payload;
+        Ok(())
+    }
+
+    /// Obtains the format of a payload that was set with `set_payload_as`.
+    #[allow(dead_code)]
+    pub fn payload_format(&self) -> Result<PayloadFormat> {
+        PayloadFormat::decode(&self.payload).map(|(format, _)| format)
+    }
+
+    /// Obtains the payload of the packet, ensuring it was set in the expected format.
+    /// `Json` payloads are decoded as a `String` containing the JSON text.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NetCode` if the format does not match or the payload cannot be decoded.
+    /// - `NetError::InvalidPacket` if a `Json` payload is not valid UTF-8.
+    #[allow(dead_code)]
+    pub fn payload_as<T: NetDecoder>(&self, format: PayloadFormat) -> Result<T> {
+        let (found, used) = PayloadFormat::decode(&self.payload)?;
+        if found != format {
+            return Err(NetError::NetCode(format!(
+                "Payload format mismatch: expected {format:?} but got {found:?}"
+            )));
+        }
+
+        let body = &self.payload[used..];
+        if format == PayloadFormat::Json {
+            self.validate_text(body)?;
+        }
+
+        T::decode(body)
+            .map(|(payload, _)| payload)
+            .map_err(|_| NetError::NetCode("Failed to decode payload".to_string()))
+    }
+
+    /// Sets the payload of the packet prefixed with its format, allowing the receiver to dispatch
+    /// to the correct decoder. `Json` payloads are expected to be a `String` of JSON text. The
+    /// packet is left unchanged if the payload is rejected.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::InvalidPacket` if the payload is too large or a `Json` payload is not UTF-8.
+    /// - `NetError::NetCode` if the payload cannot be encoded.
+    #[allow(dead_code)]
+    pub fn set_payload_as(
+        &mut self,
+        format: PayloadFormat,
+        payload: impl NetEncoder,
+    ) -> Result<()> {
+        let body = payload.try_encode()?;
+        if format == PayloadFormat::Json {
+            self.validate_text(&body)?;
+        }
+
+        let mut bytes = format.encode();
+        bytes.extend(body);
+        self.try_set_payload(bytes.as_slice())
+    }
+
+    /// Ensures a text payload is valid UTF-8.
+    fn validate_text(&self, body: &[u8]) -> Result<()> {
+        match std::str::from_utf8(body) {
+            Ok(_) => Ok(()),
+            Err(why) => Err(NetError::InvalidPacket(
+                ClientAddr::Local(self.source),
+                InvalidPacketError::Payload,
+                format!("text payload is not valid UTF-8: {why}"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Packet {
+        let mut packet = Packet::with_payload(PacketLabel::Ping, ClientId(3), 0xDEAD_BEEF_u32);
+        packet.set_sequence(42);
+        packet
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        let bytes = sample().encode();
+        let (packet, used) = Packet::decode(&bytes).unwrap();
+        assert_eq!(used, bytes.len());
+        assert_eq!(packet.label(), PacketLabel::Ping);
+        assert_eq!(packet.source(), ClientId(3));
+        assert_eq!(packet.sequence(), 42);
+        assert_eq!(packet.payload::<u32>().unwrap(), 0xDEAD_BEEF);
+    }
+
+    #[test]
+    fn fluent_packets_match_step_by_step_ones() {
+        let fluent =
+            Packet::with_payload(PacketLabel::Ping, ClientId(3), 0xDEAD_BEEF_u32).sequenced(42);
+
+        let mut manual = Packet::new(PacketLabel::Ping, ClientId(3));
+        manual.set_payload(0xDEAD_BEEF_u32);
+        manual.set_sequence(42);
+
+        assert_eq!(fluent.label(), manual.label());
+        assert_eq!(fluent.source(), manual.source());
+        assert_eq!(fluent.sequence(), manual.sequence());
+        assert_eq!(fluent.payload::<u32>().unwrap(), 0xDEAD_BEEF);
+        assert_eq!(fluent.encode(), manual.encode());
+    }
+
+    #[test]
+    fn extensions_never_alias_builtin_labels() {
+        for value in [0x00, 0x03, 0x05, 0xFE, 0xFF] {
+            assert_eq!(PacketLabel::extension(value), None, "{value:#04x}");
+            assert!(!PacketLabel::Extension(value).is_valid());
+        }
+
+        for value in [PacketLabel::EXTENSION_MIN, 0x80, PacketLabel::EXTENSION_MAX] {
+            let label = PacketLabel::extension(value).unwrap();
+            let (decoded, _) = PacketLabel::decode(&label.encode()).unwrap();
+            assert_eq!(decoded, label);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn crc32_matches_the_ieee_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn flipped_payload_byte_is_rejected() {
+        let mut bytes = sample().encode();
+        let payload_byte = bytes.len() - 5; // Last payload byte, just before the checksum.
+        bytes[payload_byte] ^= 0x01;
+
+        let Err(NetError::NetCode(why)) = Packet::decode(&bytes) else {
+            panic!("corrupted packet was accepted");
+        };
+        assert!(why.contains("checksum mismatch"), "{why}");
+    }
+
+    #[test]
+    fn exact_payloads_reject_trailing_bytes() {
+        let mut packet = sample();
+        packet.set_payload((0xDEAD_BEEF_u32, 7_u16));
+        assert_eq!(
+            packet.payload_exact::<(u32, u16)>().unwrap(),
+            (0xDEAD_BEEF, 7)
+        );
+
+        // The lenient decode ignores what is left over, the exact one does not.
+        assert_eq!(packet.payload::<u32>().unwrap(), 0xDEAD_BEEF);
+        let Err(NetError::InvalidPacket(_, InvalidPacketError::Payload, why)) =
+            packet.payload_exact::<u32>()
+        else {
+            panic!("trailing bytes were accepted");
+        };
+        assert!(why.contains("2 trailing bytes"), "{why}");
+
+        assert!(matches!(
+            packet.payload_exact::<u64>(),
+            Err(NetError::NetCode(_))
+        ));
+    }
+
+    #[test]
+    fn payloads_up_to_the_maximum_are_accepted() {
+        let prefix = Vec::<u8>::new().encode().len();
+        let mut packet = sample();
+
+        let largest = vec![0xAB_u8; Packet::MAX_PAYLOAD_SIZE - prefix];
+        packet.try_set_payload(largest.clone()).unwrap();
+        assert_eq!(packet.payload::<Vec<u8>>().unwrap(), largest);
+
+        // One byte over is rejected and leaves the packet as it was.
+        let oversized = vec![0xCD_u8; Packet::MAX_PAYLOAD_SIZE - prefix + 1];
+        let Err(NetError::InvalidPacket(_, InvalidPacketError::Payload, _)) =
+            packet.try_set_payload(oversized)
+        else {
+            panic!("oversized payload was accepted");
+        };
+        assert_eq!(packet.payload::<Vec<u8>>().unwrap(), largest);
+    }
+
+    #[test]
+    fn formatted_payloads_round_trip() {
+        let mut packet = sample();
+        packet
+            .set_payload_as(PayloadFormat::Binary, 0xDEAD_BEEF_u32)
+            .unwrap();
+        assert_eq!(packet.payload_format().unwrap(), PayloadFormat::Binary);
+        assert_eq!(
+            packet.payload_as::<u32>(PayloadFormat::Binary).unwrap(),
+            0xDEAD_BEEF
+        );
+        assert!(packet.payload_as::<String>(PayloadFormat::Json).is_err());
+
+        let json = r#"{"command":"kick","id":3}"#.to_string();
+        packet
+            .set_payload_as(PayloadFormat::Json, json.clone())
+            .unwrap();
+        assert_eq!(packet.payload_format().unwrap(), PayloadFormat::Json);
+        assert_eq!(
+            packet.payload_as::<String>(PayloadFormat::Json).unwrap(),
+            json
+        );
+    }
+
+    #[test]
+    fn formatted_payloads_are_validated() {
+        let mut packet = sample();
+        packet
+            .set_payload_as(PayloadFormat::Json, "{}".to_string())
+            .unwrap();
+
+        // Text that is not UTF-8 and payloads over the maximum leave the packet as it was.
+        let Err(NetError::InvalidPacket(_, InvalidPacketError::Payload, why)) =
+            packet.set_payload_as(PayloadFormat::Json, &[0xFF, 0xFE][..])
+        else {
+            panic!("invalid UTF-8 was accepted");
+        };
+        assert!(why.contains("UTF-8"), "{why}");
+
+        let oversized = "x".repeat(Packet::MAX_PAYLOAD_SIZE);
+        let Err(NetError::InvalidPacket(_, InvalidPacketError::Payload, _)) =
+            packet.set_payload_as(PayloadFormat::Json, oversized)
+        else {
+            panic!("oversized payload was accepted");
+        };
+        assert_eq!(
+            packet.payload_as::<String>(PayloadFormat::Json).unwrap(),
+            "{}"
+        );
+
+        // Bytes that were not set as text are rejected when read as text.
+        let mut bytes = PayloadFormat::Json.encode();
+        bytes.extend([0xFF, 0xFE]);
+        packet.set_payload(bytes.as_slice());
+        assert!(matches!(
+            packet.payload_as::<String>(PayloadFormat::Json),
+            Err(NetError::InvalidPacket(_, InvalidPacketError::Payload, _))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn compressed_payloads_round_trip_like_raw_ones() {
+        let mut repetitive = vec![0_u8; 300];
+        repetitive.extend(1..=20);
+        repetitive.extend([7_u8; 140]);
+
+        let encoded = encode_payload(repetitive.clone());
+        assert_eq!(encoded[0], PAYLOAD_RLE);
+        assert!(encoded.len() < repetitive.len());
+
+        let mut packet = sample();
+        packet.set_payload(repetitive.clone());
+        let bytes = packet.encode();
+        let (decoded, used) = Packet::decode(&bytes).unwrap();
+        assert_eq!(used, bytes.len());
+        assert_eq!(decoded.payload::<Vec<u8>>().unwrap(), repetitive);
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn incompressible_payloads_are_not_expanded() {
+        let raw: Vec<u8> = (0..=255).collect();
+        let encoded = encode_payload(raw.clone());
+        assert_eq!(encoded[0], PAYLOAD_RAW);
+        assert_eq!(encoded.len(), raw.len() + 1); // Only the flag byte is added.
+
+        let (payload, used) = decode_payload(&encoded).unwrap();
+        assert_eq!(payload, raw);
+        assert_eq!(used, encoded.len());
+    }
 }
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::Packet;
+
+/// Per-client state for reliable, ordered delivery. Outgoing packets are kept until they are
+/// acknowledged and incoming packets are released in sequence order.
+pub(crate) struct ReliableChannel {
+    next_send: u16, // Reliable sequence for the next outgoing packet.
+    outstanding: HashMap<u16, (Packet, Instant, u8)>, // Unacknowledged packets, when they were last sent, and times resent.
+    next_recv: u16,             // Reliable sequence expected to be delivered next.
+    held: HashMap<u16, Packet>, // Packets that arrived ahead of a gap.
+}
+
+impl ReliableChannel {
+    /// Maximum number of sequences ahead of the expected one that will be held.
+    const HOLD_WINDOW: u16 = 256;
+    /// Maximum number of times a packet is resent before the peer is considered unreachable.
+    const MAX_RESENDS: u8 = 10;
+
+    /// Creates a new channel with no packets in flight.
+    pub fn new() -> Self {
+        Self {
+            next_send: 0,
+            outstanding: HashMap::new(),
+            next_recv: 0,
+            held: HashMap::new(),
+        }
+    }
+
+    /// Assigns the next reliable sequence to the packet and keeps a copy until it is acknowledged.
+    pub fn track(&mut self, packet: &mut Packet) {
+        let sequence = self.next_send;
+        self.next_send = self.next_send.wrapping_add(1);
+
+        packet.set_reliable(Some(sequence));
+        self.outstanding
+            .insert(sequence, (packet.clone(), Instant::now(), 0));
+    }
+
+    /// Marks a packet as delivered. Returns `true` if it was outstanding.
+    pub fn acknowledge(&mut self, sequence: u16) -> bool {
+        self.outstanding.remove(&sequence).is_some()
+    }
+
+    /// Obtains the packets that have not been acknowledged within `retry`, marking them as resent.
+    /// Packets already resent `MAX_RESENDS` times are not resent again, see `is_exhausted`.
+    pub fn expired(&mut self, retry: Duration) -> Vec<Packet> {
+        let now = Instant::now();
+        self.outstanding
+            .values_mut()
+            .filter(|(_, sent, resends)| {
+                *resends < Self::MAX_RESENDS && now.duration_since(*sent) >= retry
+            })
+            .map(|(packet, sent, resends)| {
+                *sent = now;
+                *resends += 1;
+                packet.clone()
+            })
+            .collect()
+    }
+
+    /// Checks if a packet went unacknowledged for `retry` after its last resend, meaning the peer
+    /// is unreachable.
+    pub fn is_exhausted(&self, retry: Duration) -> bool {
+        self.outstanding
+            .values()
+            .any(|(_, sent, resends)| *resends >= Self::MAX_RESENDS && sent.elapsed() >= retry)
+    }
+
+    /// Accepts a received packet, returning the packets now ready for delivery in order. Packets
+    /// ahead of a gap are held until it fills, and duplicates are dropped. Returns None if the
+    /// packet is too far ahead to hold, meaning it must not be acknowledged so it is resent.
+    pub fn receive(&mut self, sequence: u16, packet: Packet) -> Option<Vec<Packet>> {
+        let ahead = sequence.wrapping_sub(self.next_recv);
+        let behind = self.next_recv.wrapping_sub(sequence);
+        if (1..=Self::HOLD_WINDOW).contains(&behind) {
+            return Some(vec![]); // Already delivered.
+        } else if ahead >= Self::HOLD_WINDOW {
+            return None; // Too far ahead to hold.
+        } else if ahead > 0 {
+            self.held.insert(sequence, packet);
+            return Some(vec![]);
+        }
+
+        // Release the packet and any held packets that directly follow it.
+        let mut ready = vec![packet];
+        self.next_recv = self.next_recv.wrapping_add(1);
+        while let Some(next) = self.held.remove(&self.next_recv) {
+            ready.push(next);
+            self.next_recv = self.next_recv.wrapping_add(1);
+        }
+
+        Some(ready)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{ClientId, PacketLabel};
+
+    fn packet(sequence: u16) -> Packet {
+        let mut packet = Packet::new(PacketLabel::Ping, ClientId(1));
+        packet.set_reliable(Some(sequence));
+        packet
+    }
+
+    fn sequences(packets: &[Packet]) -> Vec<Option<u16>> {
+        packets.iter().map(Packet::reliable).collect()
+    }
+
+    #[test]
+    fn held_packets_are_released_once_the_gap_fills() {
+        let mut channel = ReliableChannel::new();
+        assert_eq!(channel.receive(1, packet(1)).unwrap().len(), 0);
+        assert_eq!(channel.receive(2, packet(2)).unwrap().len(), 0);
+
+        let ready = channel.receive(0, packet(0)).unwrap();
+        assert_eq!(sequences(&ready), vec![Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn duplicates_are_dropped_and_far_packets_rejected() {
+        let mut channel = ReliableChannel::new();
+        assert_eq!(channel.receive(0, packet(0)).unwrap().len(), 1);
+        assert_eq!(
+            channel.receive(0, packet(0)).map(|ready| ready.len()),
+            Some(0)
+        );
+
+        let far = ReliableChannel::HOLD_WINDOW + 1;
+        assert!(channel.receive(far, packet(far)).is_none());
+    }
+
+    #[test]
+    fn resends_stop_once_exhausted() {
+        let mut channel = ReliableChannel::new();
+        channel.track(&mut packet(0));
+        assert!(!channel.is_exhausted(Duration::ZERO));
+
+        for _ in 0..ReliableChannel::MAX_RESENDS {
+            assert_eq!(channel.expired(Duration::ZERO).len(), 1);
+        }
+        assert!(channel.expired(Duration::ZERO).is_empty());
+        assert!(channel.is_exhausted(Duration::ZERO));
+
+        // An acknowledgement that finally arrives clears it.
+        assert!(channel.acknowledge(0));
+        assert!(!channel.is_exhausted(Duration::ZERO));
+    }
+}
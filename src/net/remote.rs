@@ -11,15 +11,17 @@ use super::{ClientAddr, Packet, SocketOptions};
 pub(crate) struct RemoteSocket {
     socket: UdpSocket, // Raw socket.
 
-    local_addr: String, // Local address for the socket.
+    local_addr: SocketAddr, // Address the socket is bound to, with the port assigned by the OS.
+    address: String,        // Local address for the socket, formatted once for `address`.
 
-    buffer: [u8; 1024], // Buffer for receiving data.
-    nonblocking: bool,  // Used to track if the socket is in non-blocking mode.
+    buffer: Vec<u8>,   // Buffer for receiving data.
+    nonblocking: bool, // Used to track if the socket is in non-blocking mode.
 }
 
 impl RemoteSocket {
-    /// Creates a new remote connection with the given address.
-    pub(crate) fn new(is_server: bool) -> Result<Self> {
+    /// Creates a new remote connection with the given address, receiving datagrams of up to
+    /// `buffer_size` bytes.
+    pub(crate) fn new(is_server: bool, buffer_size: usize) -> Result<Self> {
         let addr = if is_server {
             SocketOptions::DEFAULT_SERVER_ADDR
         } else {
@@ -32,11 +34,18 @@ impl RemoteSocket {
             Err(why) => flee!(NetError::SocketError(why.to_string())),
         };
 
+        // Binding to port 0 lets the OS pick the port, so ask the socket what it was given.
+        let local_addr = match socket.local_addr() {
+            Ok(local_addr) => local_addr,
+            Err(why) => flee!(NetError::SocketError(why.to_string())),
+        };
+
         let mut connection = Self {
             socket,
-            local_addr: addr.to_string(),
+            local_addr,
+            address: local_addr.to_string(),
 
-            buffer: [0; 1024],
+            buffer: vec![0; buffer_size],
             nonblocking: false,
         };
 
@@ -49,7 +58,13 @@ impl RemoteSocket {
     /// Obtains the address of the socket.
     #[inline]
     pub(crate) fn address(&self) -> &str {
-        &self.local_addr
+        &self.address
+    }
+
+    /// Obtains the address the socket is bound to.
+    #[inline]
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
     }
 
     /// Toggles between blocking and non-blocking modes.
@@ -63,6 +78,35 @@ impl RemoteSocket {
         Ok(())
     }
 
+    /// Decodes a datagram of `size` bytes that was received into the buffer. A datagram that fills
+    /// the buffer exactly may have been truncated, so it is rejected rather than decoded.
+    fn parse(&self, size: usize, sender: SocketAddr) -> Result<(ClientAddr, Packet)> {
+        let addr = ClientAddr::Ip(sender.ip(), sender.port());
+        if size >= self.buffer.len() {
+            flee!(NetError::InvalidPacket(
+                addr,
+                InvalidPacketError::Header,
+                format!(
+                    "datagram may be truncated, it filled the {} byte receive buffer",
+                    self.buffer.len()
+                ),
+            ));
+        }
+
+        match Packet::decode(&self.buffer[..size]) {
+            Ok((packet, _)) => Ok((addr, packet)),
+            Err(NetError::NetCode(why)) => {
+                // Wraps the error to provide more context.
+                flee!(NetError::InvalidPacket(
+                    addr,
+                    InvalidPacketError::Header,
+                    why
+                ))
+            }
+            Err(why) => flee!(why),
+        }
+    }
+
     /// Wraps the `send_to` method to send a packet to a specific address.
     fn send_to<T: ToSocketAddrs>(&self, packet: Packet, addr: &T) -> Result<()> {
         if let Err(why) = self.socket.send_to(&packet.encode(), addr) {
@@ -94,22 +138,7 @@ impl SocketHandler for RemoteSocket {
         }
 
         match self.socket.recv_from(&mut self.buffer) {
-            Ok((size, sender)) => {
-                // Parse the packet and client.
-                let (packet, _) = match Packet::decode(&self.buffer[..size]) {
-                    Ok(packet) => packet,
-                    Err(NetError::NetCode(why)) => {
-                        // Wraps the error to provide more context.
-                        flee!(NetError::InvalidPacket(
-                            ClientAddr::Ip(sender.ip(), sender.port()),
-                            InvalidPacketError::Header,
-                            why,
-                        ))
-                    }
-                    Err(why) => flee!(why),
-                };
-                Ok(Some((ClientAddr::Ip(sender.ip(), sender.port()), packet)))
-            }
+            Ok((size, sender)) => self.parse(size, sender).map(Some),
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                 Ok(None) // No data available, return None.
             }
@@ -124,23 +153,30 @@ impl SocketHandler for RemoteSocket {
         }
 
         match self.socket.recv_from(&mut self.buffer) {
-            Ok((size, sender)) => {
-                // Parse the packet and client.
-                let (packet, _) = match Packet::decode(&self.buffer[..size]) {
-                    Ok(packet) => packet,
-                    Err(NetError::NetCode(why)) => {
-                        // Wraps the error to provide more context.
-                        flee!(NetError::InvalidPacket(
-                            ClientAddr::Ip(sender.ip(), sender.port()),
-                            InvalidPacketError::Header,
-                            why,
-                        ))
-                    }
-                    Err(why) => flee!(why),
-                };
-                Ok(Some((ClientAddr::Ip(sender.ip(), sender.port()), packet)))
-            }
+            Ok((size, sender)) => self.parse(size, sender).map(Some),
             Err(why) => flee!(NetError::SocketError(why.to_string())),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn oversized_datagrams_are_rejected() {
+        let mut receiver = RemoteSocket::new(false, 64).unwrap();
+        let dest = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), receiver.local_addr().port());
+
+        let sender = UdpSocket::bind(SocketOptions::DEFAULT_CLIENT_ADDR).unwrap();
+        sender.send_to(&[0xAB; 200], dest).unwrap();
+
+        let result = receiver.recv();
+        let Err(NetError::InvalidPacket(_, InvalidPacketError::Header, why)) = result else {
+            panic!("oversized datagram was not rejected: {result:?}");
+        };
+        assert!(why.contains("may be truncated"), "{why}");
+    }
+}
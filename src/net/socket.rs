@@ -1,15 +1,18 @@
-use std::mem;
+use std::collections::VecDeque;
 use std::net::SocketAddr;
 use std::str::FromStr;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use super::builtins::{ConnectionPayload, ErrorPayload, PingPayload};
+use super::builtins::{AckPayload, ConnectionPayload, ErrorPayload, PingPayload};
+#[cfg(feature = "async")]
+use super::detached::DetachedSocket;
 use super::error::{ErrorPacket, NetError, Result};
+use super::fragment::FragmentBuffer;
 use super::storage::{ClientStorage, StorageError};
 use super::task::TaskScheduler;
 use super::traits::SocketHandler;
 use super::{
-    ClientAddr, ClientId, Deliverable, LocalSocket, Packet, PacketLabel, RemoteSocket,
+    ClientAddr, ClientId, Deliverable, LocalHub, LocalSocket, Packet, PacketLabel, RemoteSocket,
     SocketOptions,
 };
 use crate::net::error::InvalidPacketError;
@@ -18,10 +21,21 @@ use crate::{debugln, flee};
 /// Default ID of the server.
 const SERVER_CLIENT_ID: ClientId = ClientId(0);
 
+/// Maximum distance a received sequence number may be from the newest one received.
+const SEQUENCE_WINDOW: u16 = 1024;
+
+/// Percentage of client slots in use at which the socket is considered near capacity.
+const NEAR_CAPACITY_PERCENT: usize = 90;
+
+/// Duration a client is blacklisted for after sending too many invalid packets.
+const ERROR_BLACKLIST_DURATION: Duration = Duration::from_secs(30);
+
 /// Socket type for the connection. Either a remote or local connection.
 enum SocketType {
     Remote(Box<RemoteSocket>), // Remote connection that uses UDP to communicate with a client / server.
     Local(LocalSocket),        // Local connection that uses MPSC to communicate locally.
+    #[cfg(feature = "async")]
+    Detached(DetachedSocket), // Connection whose I/O is driven by an external runtime.
 }
 
 impl SocketHandler for SocketType {
@@ -30,6 +44,8 @@ impl SocketHandler for SocketType {
         match self {
             SocketType::Remote(socket) => socket.send(dest, packet),
             SocketType::Local(socket) => socket.send(dest, packet),
+            #[cfg(feature = "async")]
+            SocketType::Detached(socket) => socket.send(dest, packet),
         }
     }
 
@@ -38,6 +54,8 @@ impl SocketHandler for SocketType {
         match self {
             SocketType::Remote(socket) => socket.try_recv(),
             SocketType::Local(socket) => socket.try_recv(),
+            #[cfg(feature = "async")]
+            SocketType::Detached(socket) => socket.try_recv(),
         }
     }
 
@@ -46,6 +64,8 @@ impl SocketHandler for SocketType {
         match self {
             SocketType::Remote(socket) => socket.recv(),
             SocketType::Local(socket) => socket.recv(),
+            #[cfg(feature = "async")]
+            SocketType::Detached(socket) => socket.recv(),
         }
     }
 }
@@ -53,9 +73,15 @@ impl SocketHandler for SocketType {
 /// Socket for the connection. Used to send and receive packets to a client / server.
 /// This is a unified interface for both local and remote connections.
 pub struct Socket {
-    id: ClientId,                    // Unique identifier for the connection.
+    id: ClientId,                          // Unique identifier for the connection.
     server_addr: Option<ClientAddr>, // The server address for the connection. Only set for clients.
     raw: SocketType,                 // Lower level socket type for the connection.
+    loopback: VecDeque<Packet>,      // Packets sent to self, waiting to be received.
+    ready: VecDeque<(ClientAddr, Packet)>, // Reliable packets released in order, waiting to be received.
+    fragments: FragmentBuffer,             // Fragments of large packets waiting to be reassembled.
+    max_payload: usize,                    // Largest payload sent without fragmenting.
+    max_bytes_per_sec: Option<usize>, // Bytes each peer may be sent per second. None for no limit.
+    closed: bool,                     // Set once shut down, no further packets are sent.
 
     clients: ClientStorage<ClientAddr>, // Storage for the clients connected to the socket.
     scheduler: TaskScheduler,           // Task scheduler for managing tasks.
@@ -64,6 +90,12 @@ pub struct Socket {
 impl Socket {
     /// Creates a new socket with the given socket type.
     fn new(socket: SocketType, opts: &SocketOptions, addr: Option<ClientAddr>) -> Result<Self> {
+        if let Err(why) = opts.validate() {
+            flee!(NetError::SocketError(format!(
+                "Invalid socket options: {why}"
+            )));
+        }
+
         let offset = ClientId(u16::from(opts.is_server()));
         let id = if opts.is_server() {
             SERVER_CLIENT_ID
@@ -71,16 +103,20 @@ impl Socket {
             ClientId::INVALID
         };
 
-        let clients =
-            match ClientStorage::new(offset, ClientId(opts.max_clients), ClientId::INVALID) {
-                Ok(clients) => clients,
-                Err(why) => flee!(NetError::StorageError(why.to_string())),
-            };
+        let clients = ClientStorage::new(offset, ClientId(opts.max_clients), ClientId::INVALID)?;
 
         let mut socket = Self {
             id,
             server_addr: addr,
             raw: socket,
+            loopback: VecDeque::new(),
+            ready: VecDeque::new(),
+            fragments: FragmentBuffer::new(),
+            max_payload: opts
+                .max_datagram_size
+                .saturating_sub(Packet::MAX_HEADER_SIZE),
+            max_bytes_per_sec: opts.max_bytes_per_sec,
+            closed: false,
 
             clients,
             scheduler: TaskScheduler::new(opts.task_interval_ms),
@@ -97,7 +133,7 @@ impl Socket {
         if let Some(interval) = opts.blacklist_interval_ms {
             // Set the blacklist interval for clearing blacklisted clients.
             socket.register_task("blacklist", interval, move |sock| {
-                sock.clients.task_drain_blacklist(interval);
+                sock.clients.task_drain_blacklist();
                 Ok(())
             });
         }
@@ -130,18 +166,31 @@ impl Socket {
             });
         }
 
-        if !socket.is_server() {
-            if let Some(interval) = opts.ping_interval_ms {
+        // Resend reliable packets that have not been acknowledged in time.
+        let retry = opts.reliable_retry_ms;
+        socket.register_task("reliable", retry, move |sock| sock.resend_reliable(retry));
+
+        // Drop packets whose remaining fragments never arrived.
+        let timeout = opts.fragment_timeout_ms;
+        socket.register_task("fragments", timeout, move |sock| {
+            let dropped = sock.fragments.drain_expired(timeout);
+            if dropped > 0 {
+                debugln!("Dropped {} incomplete fragmented packets.", dropped);
+            }
+
+            Ok(())
+        });
+
+        if let Some(interval) = opts.ping_interval_ms {
+            if !socket.is_server() {
                 // Register the ping task.
                 socket.register_task("ping", interval, |sock| {
-                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-                    let mut packet = Packet::new(PacketLabel::Ping, sock.id());
-                    packet.set_payload(PingPayload(now, true));
-
-                    sock.send(Deliverable {
-                        to: ClientId(0),
-                        packet,
-                    })
+                    sock.send(Deliverable::new(SERVER_CLIENT_ID, sock.ping_packet()))
+                });
+            } else if opts.server_keepalive {
+                // Ping every client, half-open connections never reply and are swept as expired.
+                socket.register_task("keepalive", interval, |sock| {
+                    sock.broadcast(&sock.ping_packet())
                 });
             }
         }
@@ -151,54 +200,76 @@ impl Socket {
 
     /// Creates a new local connection pair.
     pub fn new_local_pair() -> Result<(Self, Self)> {
-        let mut server_socket = LocalSocket::new();
-        let mut client_socket = LocalSocket::new();
-
-        // Obtain the receivers for both connections.
-        let server_rx = server_socket.create_rx()?;
-        let client_rx = client_socket.create_rx()?;
-
-        // Set the receivers for both connections.
-        server_socket.set_rx(client_rx)?;
-        client_socket.set_rx(server_rx)?;
-
-        let server = SocketType::Local(server_socket);
-        let client = SocketType::Local(client_socket);
+        let (mut hub, server) = Self::new_local_hub()?;
+        let client = Self::new_local_client(&mut hub)?;
+        Ok((server, client)) // Hub is dropped so the server notices when the client goes away.
+    }
 
+    /// Creates a new local server along with the hub used to connect local clients to it.
+    pub fn new_local_hub() -> Result<(LocalHub, Self)> {
+        let (hub, server) = LocalHub::new();
         let server_opts = SocketOptions::default_server();
-        let client_opts = SocketOptions::default_client();
-
-        let server_addr = Some(ClientAddr::Local(SERVER_CLIENT_ID));
         Ok((
-            Self::new(server, &server_opts, None)?,
-            Self::new(client, &client_opts, server_addr)?,
+            hub,
+            Self::new(SocketType::Local(server), &server_opts, None)?,
         ))
     }
 
-    /// Creates a new remote connection with the given address.
-    pub fn new_remote(opts: &SocketOptions) -> Result<Self> {
-        // Convert the server address from String to Client.
-        let addr = if let Some(address) = &opts.server_address {
-            match SocketAddr::from_str(address) {
-                Ok(addr) => Some(ClientAddr::Ip(addr.ip(), addr.port())),
-                Err(_) => flee!(NetError::SocketError(format!(
-                    "Failed to parse server address: '{address}'. Please use a valid IP:PORT format.",
-                ))),
-            }
-        } else {
-            None
+    /// Creates a new local client that connects to the server of the hub.
+    pub fn new_local_client(hub: &mut LocalHub) -> Result<Self> {
+        let client = SocketType::Local(hub.client()?);
+        let client_opts = SocketOptions::default_client();
+        let server_addr = Some(ClientAddr::Local(SERVER_CLIENT_ID));
+        Self::new(client, &client_opts, server_addr)
+    }
+
+    /// Converts the server address from the options into a `ClientAddr`. None for servers.
+    fn parse_server_addr(opts: &SocketOptions) -> Result<Option<ClientAddr>> {
+        let Some(address) = &opts.server_address else {
+            return Ok(None);
         };
 
-        let socket = RemoteSocket::new(addr.is_none())?;
+        match SocketAddr::from_str(address) {
+            Ok(addr) => Ok(Some(ClientAddr::Ip(addr.ip(), addr.port()))),
+            Err(_) => flee!(NetError::SocketError(format!(
+                "Failed to parse server address: '{address}'. Please use a valid IP:PORT format.",
+            ))),
+        }
+    }
+
+    /// Creates a new remote connection with the given address.
+    pub fn new_remote(opts: &SocketOptions) -> Result<Self> {
+        let addr = Self::parse_server_addr(opts)?;
+        let socket = RemoteSocket::new(addr.is_none(), opts.recv_buffer_size)?;
         Self::new(SocketType::Remote(Box::new(socket)), opts, addr)
     }
 
+    /// Creates a new connection whose packets are sent and received by an external transport
+    /// bound to `local_addr`. Outgoing packets are collected with `take_outbox`.
+    #[cfg(feature = "async")]
+    pub(crate) fn new_detached(opts: &SocketOptions, local_addr: String) -> Result<Self> {
+        let addr = Self::parse_server_addr(opts)?;
+        let socket = DetachedSocket::new(local_addr);
+        Self::new(SocketType::Detached(socket), opts, addr)
+    }
+
+    /// Removes and returns the packets waiting to be delivered by an external transport.
+    #[cfg(feature = "async")]
+    pub(crate) fn take_outbox(&mut self) -> Vec<(ClientAddr, Packet)> {
+        match &mut self.raw {
+            SocketType::Detached(socket) => socket.drain(),
+            _ => vec![],
+        }
+    }
+
     /// Checks if the socket is a local connection.
     #[inline]
     pub fn is_remote(&self) -> bool {
         match &self.raw {
             SocketType::Remote(_) => true,
             SocketType::Local(_) => false,
+            #[cfg(feature = "async")]
+            SocketType::Detached(_) => true,
         }
     }
 
@@ -214,6 +285,20 @@ impl Socket {
         match &self.raw {
             SocketType::Remote(socket) => socket.address(),
             SocketType::Local(_) => LocalSocket::address(),
+            #[cfg(feature = "async")]
+            SocketType::Detached(socket) => socket.address(),
+        }
+    }
+
+    /// Address a remote socket is bound to, including the port assigned by the OS when binding
+    /// to port 0. None for local sockets.
+    #[allow(dead_code)]
+    pub fn local_socket_addr(&self) -> Option<SocketAddr> {
+        match &self.raw {
+            SocketType::Remote(socket) => Some(socket.local_addr()),
+            SocketType::Local(_) => None,
+            #[cfg(feature = "async")]
+            SocketType::Detached(socket) => SocketAddr::from_str(socket.address()).ok(),
         }
     }
 
@@ -241,6 +326,81 @@ impl Socket {
         self.clients.addr_iter().map(|(id, _)| id).collect()
     }
 
+    /// Number of clients currently connected to the socket.
+    #[inline]
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Removes an address from the blacklist before its timeout ends, allowing it to connect
+    /// again. Returns true if it was blacklisted.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn unblacklist(&mut self, addr: &ClientAddr) -> bool {
+        self.clients.unblacklist(addr)
+    }
+
+    /// Number of clients that can still connect before the socket is full.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn capacity_remaining(&self) -> usize {
+        self.clients.capacity().saturating_sub(self.clients.len())
+    }
+
+    /// Checks if the maximum number of clients are connected, so new connections are rejected.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.clients.is_full()
+    }
+
+    /// Resends the reliable packets that have not been acknowledged within `retry_ms`. Peers that
+    /// never acknowledge them are considered unreachable and disconnected.
+    fn resend_reliable(&mut self, retry_ms: u64) -> Result<()> {
+        for client_id in self.clients.exhausted_reliable(retry_ms) {
+            debugln!(
+                "Disconnecting [{}], reliable packets were never acknowledged.",
+                client_id
+            );
+
+            if self.is_server() {
+                self.disconnect_client(client_id, true)?;
+            } else {
+                flee!(NetError::Disconnected);
+            }
+        }
+
+        for (client_id, packet) in self.clients.expired_reliable(retry_ms) {
+            match self.send(Deliverable::new(client_id, packet)) {
+                Ok(()) => {}
+                Err(NetError::SocketError(why)) => flee!(NetError::SocketError(why)),
+                Err(why) => {
+                    debugln!(
+                        "Failed to resend reliable packet to [{}]: {}",
+                        client_id,
+                        why
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Obtains the IDs of the clients currently connected to the socket.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn connected_clients(&self) -> Vec<ClientId> {
+        self.remote_ids()
+    }
+
+    /// Checks if a client is currently connected to the socket.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn is_connected(&self, client_id: ClientId) -> bool {
+        self.clients.contains(client_id)
+    }
+
     /// Obtains the last sequence ID for the connection.
     #[allow(dead_code)]
     #[inline]
@@ -248,22 +408,92 @@ impl Socket {
         self.clients.get_sequence(client_id)
     }
 
-    /// Adds a new task to the scheduler.
-    pub fn register_task<F, N: Into<String>>(&mut self, name: N, frequency_ms: u64, callback: F)
+    /// Smoothed round-trip time to a client, measured from its ping replies. None until the
+    /// first pong has been received.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn rtt(&self, client_id: ClientId) -> Option<Duration> {
+        self.clients.get_rtt(client_id).copied()
+    }
+
+    /// Ratio of recent packets from a client that never arrived, between 0 and 1, measured from
+    /// gaps in their sequence numbers. None until a packet has been received from the client.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn packet_loss(&self, client_id: ClientId) -> Option<f32> {
+        self.clients.get_packet_loss(client_id)
+    }
+
+    /// Adds a new task to the scheduler, returning its ID.
+    pub fn register_task<F, N: Into<String>>(
+        &mut self,
+        name: N,
+        frequency_ms: u64,
+        callback: F,
+    ) -> usize
     where
         F: Fn(&mut Socket) -> Result<()> + Send + Sync + 'static,
     {
-        self.scheduler.register(name, frequency_ms, callback);
+        self.scheduler.register(name, frequency_ms, callback)
+    }
+
+    /// Adds a task that runs once after `delay_ms`, such as a delayed reconnect attempt,
+    /// returning its ID.
+    #[allow(dead_code)]
+    pub fn register_task_once<F, N: Into<String>>(
+        &mut self,
+        name: N,
+        delay_ms: u64,
+        callback: F,
+    ) -> usize
+    where
+        F: FnOnce(&mut Socket) -> Result<()> + Send + Sync + 'static,
+    {
+        self.scheduler.register_once(name, delay_ms, callback)
     }
 
-    /// Runs the tasks in the scheduler.
+    /// Removes a task so it never runs again. A task may cancel itself while running. Returns
+    /// false if there is no task with the ID.
+    #[allow(dead_code)]
+    pub fn cancel_task(&mut self, task_id: usize) -> bool {
+        self.scheduler.unregister(task_id)
+    }
+
+    /// Runs the tasks in the scheduler that are ready. Stops at the first task that fails, the
+    /// tasks after it run on the next pass.
     pub fn run_tasks(&mut self, force: bool) -> Result<()> {
-        if force || self.scheduler.is_ready() {
-            let mut scheduler = mem::take(&mut self.scheduler);
-            scheduler.run(self)?; // Run the tasks.
-            self.scheduler = scheduler; // Move it back into `self`.
+        if !force && !self.scheduler.is_ready() {
+            return Ok(());
         }
-        Ok(())
+
+        let mut result = Ok(());
+        let mut tasks = self.scheduler.take_ready();
+        for task in &mut tasks {
+            result = task.run(self);
+            task.reset();
+            if task.is_once() {
+                self.scheduler.unregister(task.id());
+            }
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.scheduler.restore(tasks);
+        result
+    }
+
+    /// Number of connected clients and the maximum number of clients allowed.
+    pub fn capacity(&self) -> (usize, usize) {
+        (self.clients.len(), self.clients.capacity())
+    }
+
+    /// Checks if the number of connected clients is at or above `NEAR_CAPACITY_PERCENT` of the
+    /// maximum, allowing a matchmaker to redirect clients before they are rejected.
+    pub fn is_near_capacity(&self) -> bool {
+        let (used, max) = self.capacity();
+        used * 100 >= max * NEAR_CAPACITY_PERCENT
     }
 
     /// Adds a new client, returning the client's ID.
@@ -281,8 +511,19 @@ impl Socket {
                 ErrorPacket::Blacklisted,
                 "Your address is currently blacklisted. Please try again later.",
             ),
-            Err(why) => flee!(NetError::StorageError(why.to_string())),
-            Ok(client_id) => return Ok(client_id),
+            Err(why) => Err(why)?,
+            Ok(client_id) => {
+                if self.is_near_capacity() {
+                    let (used, max) = self.capacity();
+                    debugln!(
+                        "SOCKET: Nearing client capacity, {} of {} slots in use.",
+                        used,
+                        max
+                    );
+                }
+
+                return Ok(client_id);
+            }
         };
 
         self.send_err(&client, err, msg)?;
@@ -317,10 +558,12 @@ impl Socket {
                         debugln!("Failed to disconnect client with too many errors: {}", why);
                     }
 
-                    self.clients.blacklist_client_addr(addr);
+                    self.clients
+                        .blacklist_client_addr(addr, ERROR_BLACKLIST_DURATION);
                 } else {
                     // Client is not connected, but has too many errors.
-                    self.clients.blacklist_client_addr(addr);
+                    self.clients
+                        .blacklist_client_addr(addr, ERROR_BLACKLIST_DURATION);
                 }
 
                 debugln!("Blacklisted client with too many errors: {}", addr);
@@ -341,15 +584,7 @@ impl Socket {
         // Check if a new client connecting, otherwise give it the old ID.
         if packet.label() == PacketLabel::Connect {
             // New client connecting, assign it a new ID.
-            let cache_id = if self.is_remote() {
-                // Remote connection, assign a new ID.
-                self.add_client(*sender)?
-            } else {
-                // Need to generate an ID that is not INVALID_CLIENT_ID.
-                let id = self.clients.next_id();
-                self.add_client(ClientAddr::Local(id))?
-            };
-
+            let cache_id = self.add_client(*sender)?;
             packet.set_source(cache_id);
         } else if let Some(id) = self.clients.get_id(sender) {
             packet.set_source(id); // Discovered ID from cache.
@@ -415,11 +650,22 @@ impl Socket {
     /// - `NetError::InvalidPacketSender` if the sender ID is invalid.
     /// - `NetError::InvalidPacketAddress` if the address is invalid.
     /// - `NetError::InvalidPacketPayload` if the payload is invalid.
+    /// - `NetError::InvalidPacket` if the sender ID could never be assigned to a client.
     fn validate(&mut self, sender: &ClientAddr, packet: &mut Packet) -> Result<()> {
         if self.clients.is_blacklisted(sender) {
             flee!(NetError::NothingToDo);
         }
 
+        // Reject IDs that could never be assigned before they are used for any lookup.
+        let source = packet.source();
+        if self.is_server() && source != ClientId::INVALID && !self.clients.is_assignable(source) {
+            flee!(NetError::InvalidPacket(
+                *sender,
+                InvalidPacketError::Source,
+                format!("{source} is outside of the assignable client IDs")
+            ));
+        }
+
         let mut authed = !self.is_server();
 
         // Handles a packet with an invalid client ID.
@@ -433,10 +679,60 @@ impl Socket {
             self.validate_client_lookup(sender, packet.source())?;
         }
 
+        self.validate_sequence(sender, packet)
+    }
+
+    /// Ensures the packet's sequence number falls within the window of the current session and
+    /// has not been received before. Packets captured from an earlier session use a different
+    /// base and are rejected, as are replays within the current session.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::InvalidPacket` if the sequence number is outside of the window or was
+    ///   already received.
+    fn validate_sequence(&mut self, sender: &ClientAddr, packet: &Packet) -> Result<()> {
+        if matches!(
+            packet.label(),
+            PacketLabel::Connect | PacketLabel::ConnectAccept
+        ) {
+            return Ok(()); // Handshake packets establish the base, nothing to check yet.
+        }
+
+        let Some(&last) = self.clients.get_recv_sequence(packet.source()) else {
+            return Ok(()); // No session with the sender.
+        };
+
+        // Wrapping difference, so sequences that rolled over past `u16::MAX` are still ahead.
+        let delta = packet.sequence().wrapping_sub(last).cast_signed();
+        if delta.unsigned_abs() > SEQUENCE_WINDOW {
+            flee!(NetError::InvalidPacket(
+                *sender,
+                InvalidPacketError::Sequence,
+                format!(
+                    "sequence {} is outside of the window around {last}",
+                    packet.sequence(),
+                )
+            ));
+        } else if !self.clients.mark_received(packet.source(), delta) {
+            flee!(NetError::InvalidPacket(
+                *sender,
+                InvalidPacketError::Sequence,
+                format!("sequence {} was already received", packet.sequence())
+            ));
+        }
+
+        if delta > 0
+            && let Some(last) = self.clients.get_recv_sequence_mut(packet.source())
+        {
+            *last = packet.sequence();
+        }
+
+        self.clients.record_sequence(packet.source(), delta);
         Ok(())
     }
 
-    /// Processes the connection packet for the socket. This handles both server and client modes.
+    /// Processes the handshake for the socket. Servers handle `Connect` requests and clients
+    /// handle the `ConnectAccept` replies.
     fn packet_action_connection(&mut self, packet: &Packet, addr: &ClientAddr) -> Result<()> {
         let Ok(conn) = packet.payload::<ConnectionPayload>() else {
             // Failed to decode connection payload, return an error.
@@ -447,28 +743,49 @@ impl Socket {
             ));
         };
 
-        if conn.0 != Packet::CURRENT_VERSION {
-            flee!(NetError::InvalidPacket(
-                *addr,
-                InvalidPacketError::Version,
-                format!(
-                    "packet version mismatch {} != {}",
-                    conn.0,
-                    Packet::CURRENT_VERSION
-                ),
-            ));
-        }
-
         if self.is_server() {
-            // Server mode: Send connection payload to the client.
-            let payload = ConnectionPayload(Packet::CURRENT_VERSION, packet.source(), 5000);
-            let mut response = Packet::new(PacketLabel::Connect, self.id());
-            response.set_payload(payload);
+            let Some(version) = Packet::negotiate_version(conn.4, conn.0) else {
+                // Tell the client which version it needs instead of dropping it silently.
+                self.send_err(
+                    addr,
+                    ErrorPacket::IncompatibleVersion(Packet::CURRENT_VERSION),
+                    &format!(
+                        "Supported packet versions are {} to {}, client supports {} to {}.",
+                        Packet::MIN_VERSION,
+                        Packet::CURRENT_VERSION,
+                        conn.4,
+                        conn.0
+                    ),
+                )?;
+
+                self.queue_removal(packet.source());
+                flee!(NetError::NothingToDo);
+            };
+
+            // Server mode: Send connection payload to the client, including the session's base.
+            let Some(&base) = self.clients.get_sequence(packet.source()) else {
+                flee!(NetError::NotConnected(*addr));
+            };
+
+            let payload =
+                ConnectionPayload(version, packet.source(), 5000, base, Packet::MIN_VERSION);
+            let response = Packet::with_payload(PacketLabel::ConnectAccept, self.id(), payload);
             self.send(Deliverable::new(packet.source(), response))?;
         } else {
+            if !(Packet::MIN_VERSION..=Packet::CURRENT_VERSION).contains(&conn.0) {
+                flee!(NetError::InvalidPacket(
+                    *addr,
+                    InvalidPacketError::Version,
+                    format!("server chose unsupported packet version {}", conn.0),
+                ));
+            }
+
             // Client mode: Accept the connection and set the ID.
             self.id = conn.1;
-            self.clients.insert(packet.source(), *addr);
+            self.clients.insert(packet.source(), *addr, conn.3);
+            if let Some(last) = self.clients.get_recv_sequence_mut(packet.source()) {
+                *last = packet.sequence(); // The reply already consumed a sequence number.
+            }
         }
 
         Ok(())
@@ -499,13 +816,42 @@ impl Socket {
 
         if ping.1 {
             // Ping packet, send a pong packet back.
-            let mut response = Packet::new(PacketLabel::Ping, self.id());
-            response.set_payload(PingPayload(ping.0, false));
+            let response =
+                Packet::with_payload(PacketLabel::Ping, self.id(), PingPayload(ping.0, false));
             self.send(Deliverable::new(packet.source(), response))?;
+        } else {
+            // Pong packet, the payload holds the time our ping was sent.
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            self.clients
+                .update_rtt(packet.source(), now.saturating_sub(ping.0));
         }
         Ok(())
     }
 
+    /// Creates a ping packet stamped with the current time, echoed back in the pong.
+    fn ping_packet(&self) -> Packet {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        Packet::with_payload(PacketLabel::Ping, self.id(), PingPayload(now, true))
+    }
+
+    /// Processes an acknowledgement, releasing the reliable packet it refers to.
+    fn packet_action_acknowledge(&mut self, packet: &Packet, addr: &ClientAddr) -> Result<()> {
+        let Ok(AckPayload(sequence)) = packet.payload::<AckPayload>() else {
+            // Failed to decode acknowledge payload, return an error.
+            flee!(NetError::InvalidPacket(
+                *addr,
+                InvalidPacketError::Payload,
+                "Could not parse acknowledge payload".to_string()
+            ));
+        };
+
+        if let Some(channel) = self.clients.get_reliable_mut(packet.source()) {
+            channel.acknowledge(sequence);
+        }
+
+        Ok(())
+    }
+
     /// Processes the packet actions for errors. This handles the error packets and invokes the appropriate error handling.
     fn packet_actions_errors(&mut self, packet: &Packet, addr: &ClientAddr) -> Result<()> {
         if self.is_server() {
@@ -522,19 +868,28 @@ impl Socket {
             ));
         };
 
-        match payload.0 {
+        let ErrorPayload(code, msg) = payload;
+        match code {
             ErrorPacket::TooManyConnections => {
-                flee!(NetError::SocketError(
-                    "Received 'TooManyConnections' error from server.".to_string()
-                ));
+                flee!(NetError::SocketError(format!(
+                    "Received 'TooManyConnections' error from server: {msg}"
+                )));
             }
             ErrorPacket::Blacklisted => {
-                flee!(NetError::SocketError(
-                    "Received 'Blacklisted' error from server. You are temporarily blocked."
-                        .to_string()
-                ));
+                flee!(NetError::SocketError(format!(
+                    "Received 'Blacklisted' error from server. You are temporarily blocked: {msg}"
+                )));
+            }
+            ErrorPacket::IncompatibleVersion(version) => {
+                flee!(NetError::SocketError(format!(
+                    "Server requires packet version {version}, this client supports {} to {}: {msg}",
+                    Packet::MIN_VERSION,
+                    Packet::CURRENT_VERSION
+                )));
+            }
+            _ => {
+                debugln!("SOCKET: Received '{}' error from server: {}", code, msg);
             }
-            _ => {}
         }
 
         Ok(())
@@ -543,7 +898,15 @@ impl Socket {
     /// Handles the packet actions based on the packet type.
     fn packet_actions(&mut self, packet: &Packet, addr: &ClientAddr) -> Result<()> {
         let result = match packet.label() {
-            PacketLabel::Connect => self.packet_action_connection(packet, addr),
+            PacketLabel::Acknowledge => self.packet_action_acknowledge(packet, addr),
+            PacketLabel::Connect if self.is_server() => self.packet_action_connection(packet, addr),
+            PacketLabel::ConnectAccept if !self.is_server() => {
+                self.packet_action_connection(packet, addr)
+            }
+            PacketLabel::Connect | PacketLabel::ConnectAccept => {
+                // Handshake sent to the wrong side, never act on it.
+                Err(NetError::NothingToDo)
+            }
             PacketLabel::Disconnect => self.packet_action_disconnection(packet, addr),
             PacketLabel::Ping => self.packet_action_ping(packet, addr),
             PacketLabel::Error => self.packet_actions_errors(packet, addr),
@@ -559,7 +922,9 @@ impl Socket {
         Ok(())
     }
 
-    /// Disconnects a client from the server and notifies the client if requested.
+    /// Disconnects a client from the server and notifies the client if requested. If the client
+    /// was connected, a disconnect packet from it is delivered on the next `try_recv` or `recv` so
+    /// the application learns of timeouts the same way as graceful disconnects.
     ///
     /// # Errors
     ///
@@ -578,7 +943,59 @@ impl Socket {
             self.send(Deliverable::new(client_id, to_send))?;
         }
 
+        let connected = self.clients.get_addr(client_id).is_some();
         self.queue_removal(client_id);
+
+        if connected {
+            // Only once, the application may call back into this when handling the packet.
+            self.loopback
+                .push_back(Packet::new(PacketLabel::Disconnect, client_id));
+        }
+
+        Ok(())
+    }
+
+    /// Disconnects a client from the server, notifying it, and blacklists its address for a
+    /// duration so it cannot reconnect. A disconnect packet from the client is delivered the same
+    /// as with `disconnect_client`.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NotConnected` if the client is not connected.
+    /// - `NetError::SocketError` if there is a socket error.
+    pub fn blacklist_client(&mut self, client_id: ClientId, duration: Duration) -> Result<()> {
+        if !self.is_server() {
+            flee!(NetError::NothingToDo);
+        }
+
+        let Some(addr) = self.clients.get_addr(client_id).copied() else {
+            flee!(NetError::NotConnected(ClientAddr::Local(client_id)));
+        };
+
+        self.disconnect_client(client_id, true)?;
+        self.clients.blacklist_client_addr(&addr, duration);
+        debugln!("SOCKET: Blacklisted client [{}] at {}.", client_id, addr);
+        Ok(())
+    }
+
+    /// Shuts the socket down, notifying every connected client with a disconnect packet after
+    /// running the scheduled tasks one final time. Afterwards `send` returns
+    /// `NetError::Disconnected`. Calling this on a socket that is already shut down does nothing.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::SocketError` if there is a socket error.
+    pub fn shutdown(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+
+        self.run_tasks(true)?;
+
+        let packet = Packet::new(PacketLabel::Disconnect, self.id());
+        self.broadcast(&packet)?;
+
+        self.closed = true;
         Ok(())
     }
 
@@ -590,11 +1007,11 @@ impl Socket {
     /// - `NetError::NotConnected` if the connection is not established.
     /// - `NetError::SocketError` if there is a socket error.
     fn send_err(&mut self, to: &ClientAddr, error: ErrorPacket, msg: &str) -> Result<()> {
-        let mut packet = Packet::new(PacketLabel::Error, self.id);
-        let mut bytes = vec![error as u8];
-
-        bytes.extend_from_slice(msg.as_bytes());
-        packet.set_payload(bytes);
+        let mut packet = Packet::with_payload(
+            PacketLabel::Error,
+            self.id,
+            ErrorPayload(error, msg.to_string()),
+        );
 
         // Attempt to set the Sequence ID.
         if let Some(client_id) = self.clients.get_id(to) {
@@ -607,45 +1024,273 @@ impl Socket {
         self.raw.send(to, packet)
     }
 
-    /// Sends a packet to the destination UUID. If the packet is a connect packet, it will not check for self connection.
+    /// Sends a packet to the destination UUID. Packets addressed to the socket itself are treated
+    /// as a mistake and rejected unless they are connect packets; use `send_loopback` to deliver
+    /// a packet to self on purpose.
     ///
     /// # Errors
     ///
-    /// - `NetError::SelfConnection` if the destination is the same as the source and the packet is not a connect packet.
+    /// - `NetError::NothingToDo` if the destination is the socket itself and the packet is not a connect packet.
     /// - `NetError::NotConnected` if the connection is not established.
     /// - `NetError::SocketError` if there is a socket error.
+    /// - `NetError::Disconnected` if the socket has been shut down.
+    /// - `NetError::NothingToDo` if the packet is low priority and the destination is over its
+    ///   bandwidth budget.
+    /// - `NetError::InvalidPacket` if the label is an `Extension` using the byte of a builtin label.
     #[allow(dead_code)]
     pub fn send(&mut self, Deliverable { to, mut packet }: Deliverable) -> Result<()> {
-        if self.id() == to && packet.label() != PacketLabel::Connect {
+        if self.closed {
+            flee!(NetError::Disconnected);
+        } else if !packet.label().is_valid() {
+            // Would be received as a builtin label with a different meaning.
+            flee!(NetError::InvalidPacket(
+                ClientAddr::Local(to),
+                InvalidPacketError::Header,
+                format!("{:?} uses the byte of a builtin label", packet.label())
+            ));
+        } else if self.id() == to && packet.label() != PacketLabel::Connect {
             debugln!(
-                "Self connection detected: source ID {} and destination ID {}. Packet: {:?}.",
-                self.id(),
+                "Rejected packet addressed to self ({}), use `send_loopback` for self-delivery. Packet: {:?}.",
                 to,
                 packet
             );
             flee!(NetError::NothingToDo);
         }
 
-        // Update the sequence number for the packet if it's not a connect packet.
-        if packet.source() != ClientId::INVALID && packet.source() == self.id() {
-            if let Some(seq) = self.clients.get_sequence_mut(to) {
-                *seq = seq.wrapping_add(1);
-                packet.set_sequence(*seq);
-            } else {
-                flee!(NetError::NotConnected(ClientAddr::Local(to)));
-            }
+        // Drop low priority packets once the destination has used its bandwidth budget.
+        let size = Packet::MAX_HEADER_SIZE + packet.payload_len();
+        if self.max_bytes_per_sec.is_some_and(|limit| {
+            packet.is_low_priority() && self.clients.exceeds_bandwidth(to, size, limit)
+        }) {
+            debugln!(
+                "Bandwidth budget exceeded for [{}], dropping packet: {:?}.",
+                to,
+                packet
+            );
+            flee!(NetError::NothingToDo);
         }
 
+        self.stamp_sequence(to, &mut packet)?;
+
         // Send the packet to the client.
-        if let Some(client) = self.clients.get_addr(to) {
-            self.raw.send(client, packet)
+        let dest = if let Some(client) = self.clients.get_addr(to) {
+            *client
         } else if let Some(client) = self.server_addr() {
-            self.raw.send(&client, packet)
+            client
         } else if !self.is_remote() {
-            self.raw.send(&ClientAddr::Local(SERVER_CLIENT_ID), packet)
+            ClientAddr::Local(SERVER_CLIENT_ID)
         } else {
             flee!(NetError::NotConnected(ClientAddr::Local(to)));
+        };
+
+        self.clients.record_sent(to, size);
+        self.send_fragmented(to, &dest, packet)
+    }
+
+    /// Updates the sequence number for a packet sent by this socket to `to`. Connect packets are
+    /// sent before an ID is assigned and are left as is.
+    fn stamp_sequence(&mut self, to: ClientId, packet: &mut Packet) -> Result<()> {
+        if packet.source() == ClientId::INVALID || packet.source() != self.id() {
+            return Ok(());
+        }
+
+        let Some(seq) = self.clients.get_sequence_mut(to) else {
+            flee!(NetError::NotConnected(ClientAddr::Local(to)));
+        };
+
+        *seq = seq.wrapping_add(1);
+        packet.set_sequence(*seq);
+        Ok(())
+    }
+
+    /// Sends a packet to the address, splitting it into fragments if it does not fit within a
+    /// single datagram. Every fragment after the first takes its own sequence number, otherwise
+    /// the receiver would reject them as replays.
+    fn send_fragmented(&mut self, to: ClientId, dest: &ClientAddr, packet: Packet) -> Result<()> {
+        if packet.payload_len() <= self.max_payload {
+            return self.raw.send(dest, packet);
+        }
+
+        let fragments = self.fragments.split(packet, self.max_payload)?;
+        for (idx, mut fragment) in fragments.into_iter().enumerate() {
+            if idx > 0 {
+                self.stamp_sequence(to, &mut fragment)?;
+            }
+            self.raw.send(dest, fragment)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a packet that is resent until the destination acknowledges it. Reliable packets are
+    /// delivered to the destination's application once, in the order they were sent.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NotConnected` if the connection is not established.
+    /// - `NetError::SocketError` if there is a socket error.
+    #[allow(dead_code)]
+    pub fn send_reliable(&mut self, Deliverable { to, mut packet }: Deliverable) -> Result<()> {
+        let Some(channel) = self.clients.get_reliable_mut(to) else {
+            flee!(NetError::NotConnected(ClientAddr::Local(to)));
+        };
+
+        channel.track(&mut packet);
+        self.send(Deliverable::new(to, packet))
+    }
+
+    /// Sends a copy of the packet to every connected client. Clients that cannot be reached are
+    /// skipped so that one bad connection does not prevent delivery to the rest.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::SocketError` if there is a socket error.
+    pub fn broadcast(&mut self, packet: &Packet) -> Result<()> {
+        self.broadcast_except(ClientId::INVALID, packet)
+    }
+
+    /// Sends a copy of the packet to every connected client except `except`, such as relaying a
+    /// client's packet to everyone but the sender.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::SocketError` if there is a socket error.
+    pub fn broadcast_except(&mut self, except: ClientId, packet: &Packet) -> Result<()> {
+        for client_id in self.remote_ids() {
+            if client_id == except {
+                continue;
+            }
+
+            match self.send(Deliverable::new(client_id, packet.clone())) {
+                Ok(()) => {}
+                Err(NetError::SocketError(why)) => flee!(NetError::SocketError(why)),
+                Err(why) => {
+                    debugln!(
+                        "Failed to broadcast packet to client [{}]: {}",
+                        client_id,
+                        why
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a packet to the socket itself, bypassing the self-connection guard in `send`, such as
+    /// for echo tests. Nothing is sent over the connection, so this works the same for local and
+    /// remote sockets. The packet is delivered on the next `try_recv` or `recv`. Its packet actions
+    /// are skipped, since the socket keeps no per-client state about itself.
+    #[allow(dead_code)]
+    pub fn send_loopback(&mut self, mut packet: Packet) {
+        packet.set_source(self.id());
+        self.loopback.push_back(packet);
+    }
+
+    /// Receives the next packet sent to self, if any, and processes its actions.
+    fn recv_loopback(&mut self) -> Result<Option<Packet>> {
+        let Some(packet) = self.loopback.pop_front() else {
+            return Ok(None);
+        };
+
+        // Disconnect notices for clients are queued here too, only those run their actions.
+        if packet.source() != self.id() {
+            self.packet_actions(&packet, &ClientAddr::Local(self.id()))?;
+        }
+        Ok(Some(packet))
+    }
+
+    /// Validates a packet received from `client` and runs its actions. Returns None if the packet
+    /// is a fragment of an incomplete packet, or is reliable and is being held for an earlier one
+    /// or was already delivered. Reliable packets only run their actions once they are released in
+    /// order, so retransmitted copies are never acted on twice.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::InvalidPacket` if the packet fails validation.
+    /// - `NetError::NotConnected` if the connection is not established.
+    pub(crate) fn process(
+        &mut self,
+        client: ClientAddr,
+        mut packet: Packet,
+    ) -> Result<Option<Packet>> {
+        if packet.fragment().is_some()
+            && matches!(
+                packet.label(),
+                PacketLabel::Connect | PacketLabel::ConnectAccept
+            )
+        {
+            // Handshakes are never split, and validating one would authenticate the sender.
+            debugln!("Dropping fragmented handshake from [{}].", client);
+            return Ok(None);
+        }
+
+        if let Err(why) = self.validate(&client, &mut packet) {
+            self.handle_invalid_packet_err(&why)?;
+            flee!(why);
         }
+
+        if packet.fragment().is_some() {
+            if !self.is_connected(packet.source()) {
+                // Only established sessions may make the socket buffer fragments.
+                debugln!("Dropping fragment from unconnected client [{}].", client);
+                return Ok(None);
+            }
+
+            match self.fragments.insert(packet) {
+                Some(whole) => packet = whole,
+                None => return Ok(None), // Waiting on the remaining fragments.
+            }
+        }
+
+        let source = packet.source();
+        let released = match (packet.reliable(), self.clients.get_reliable_mut(source)) {
+            (Some(reliable), Some(channel)) => {
+                let Some(released) = channel.receive(reliable, packet) else {
+                    return Ok(None); // Too far ahead to hold, the sender will resend it.
+                };
+
+                // Acknowledge every accepted copy, the previous acknowledgement may have been lost.
+                self.acknowledge(source, reliable)?;
+                released
+            }
+            _ => vec![packet],
+        };
+
+        let mut released = released.into_iter();
+        let Some(next) = released.next() else {
+            return Ok(None);
+        };
+
+        self.ready.extend(released.map(|packet| (client, packet)));
+        self.packet_actions(&next, &client)?;
+        Ok(Some(next))
+    }
+
+    /// Sends a receipt for the reliable packet with the `sequence`, so the client stops resending
+    /// it. Received reliable packets are acknowledged automatically.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NotConnected` if the connection is not established.
+    /// - `NetError::SocketError` if there is a socket error.
+    pub fn acknowledge(&mut self, client_id: ClientId, sequence: u16) -> Result<()> {
+        let ack = Packet::with_payload(PacketLabel::Acknowledge, self.id(), AckPayload(sequence));
+        self.send(Deliverable::new(client_id, ack))
+    }
+
+    /// Obtains the next packet that is ready without reading from the connection.
+    fn recv_pending(&mut self) -> Result<Option<Packet>> {
+        if let Some(packet) = self.recv_loopback()? {
+            return Ok(Some(packet));
+        }
+
+        let Some((client, packet)) = self.ready.pop_front() else {
+            return Ok(None);
+        };
+
+        self.packet_actions(&packet, &client)?;
+        Ok(Some(packet))
     }
 
     /// Tries to receive a packet from the connection. Returns None if no packet is available.
@@ -661,24 +1306,44 @@ impl Socket {
     /// - `NetError::Disconnected` if the connection is disconnected.
     #[allow(dead_code)]
     pub fn try_recv(&mut self) -> Result<Option<Packet>> {
-        match self.raw.try_recv() {
-            Ok(Some((client, mut packet))) => {
-                if let Err(why) = self.validate(&client, &mut packet) {
+        if let Some(packet) = self.recv_pending()? {
+            return Ok(Some(packet));
+        }
+
+        loop {
+            match self.raw.try_recv() {
+                Ok(Some((client, packet))) => {
+                    if let Some(packet) = self.process(client, packet)? {
+                        return Ok(Some(packet));
+                    }
+                }
+                Ok(None) => return Ok(None),
+                Err(why) => {
                     self.handle_invalid_packet_err(&why)?;
-                    flee!(why);
+                    flee!(why)
                 }
-
-                self.packet_actions(&packet, &client)?;
-                Ok(Some(packet))
-            }
-            Ok(None) => Ok(None),
-            Err(why) => {
-                self.handle_invalid_packet_err(&why)?;
-                flee!(why)
             }
         }
     }
 
+    /// Receives every packet that is currently available, appending them to `out` so the caller
+    /// can reuse the buffer between calls. Returns the number of packets appended. Each packet is
+    /// validated and processed the same as with `try_recv`.
+    ///
+    /// # Errors
+    ///
+    /// Stops at the first error that `try_recv` would return, packets received before it remain
+    /// in `out`.
+    #[allow(dead_code)]
+    pub fn try_recv_all(&mut self, out: &mut Vec<Packet>) -> Result<usize> {
+        let start = out.len();
+        while let Some(packet) = self.try_recv()? {
+            out.push(packet);
+        }
+
+        Ok(out.len() - start)
+    }
+
     /// Waits to receive a packet from the connection. Returns an error if a connection issue occurs.
     ///
     /// # Errors
@@ -692,21 +1357,788 @@ impl Socket {
     /// - `NetError::Disconnected` if the connection is disconnected.
     #[allow(dead_code)]
     pub fn recv(&mut self) -> Result<Option<Packet>> {
-        match self.raw.recv() {
-            Ok(Some((client, mut packet))) => {
-                if let Err(why) = self.validate(&client, &mut packet) {
+        if let Some(packet) = self.recv_pending()? {
+            return Ok(Some(packet));
+        }
+
+        loop {
+            match self.raw.recv() {
+                Ok(Some((client, packet))) => {
+                    if let Some(packet) = self.process(client, packet)? {
+                        return Ok(Some(packet));
+                    }
+                }
+                Ok(None) => return Ok(None),
+                Err(why) => {
                     self.handle_invalid_packet_err(&why)?;
-                    flee!(why);
+                    flee!(why)
                 }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use super::*;
+
+    /// Creates a local server and client with the fast intervals of `for_loopback_test`.
+    fn loopback_pair() -> (Socket, Socket) {
+        loopback_pair_with(&SocketOptions::for_loopback_test(true))
+    }
+
+    /// Creates a local server with the options and a client with the fast intervals of
+    /// `for_loopback_test`.
+    fn loopback_pair_with(server_opts: &SocketOptions) -> (Socket, Socket) {
+        let (mut hub, server) = LocalHub::new();
+        let server = Socket::new(SocketType::Local(server), server_opts, None).unwrap();
 
-                self.packet_actions(&packet, &client)?;
-                Ok(Some(packet))
+        let client_opts = SocketOptions::for_loopback_test(false);
+        let server_addr = Some(ClientAddr::Local(SERVER_CLIENT_ID));
+        let client = SocketType::Local(hub.client().unwrap());
+        let client = Socket::new(client, &client_opts, server_addr).unwrap();
+        (server, client)
+    }
+
+    /// Completes the handshake for the client, returning the ID the server assigned it.
+    fn connect(server: &mut Socket, client: &mut Socket) -> ClientId {
+        let payload = ConnectionPayload(
+            Packet::CURRENT_VERSION,
+            client.id(),
+            5000,
+            0,
+            Packet::MIN_VERSION,
+        );
+        let request = Packet::with_payload(PacketLabel::Connect, client.id(), payload);
+        client
+            .send(Deliverable::new(SERVER_CLIENT_ID, request))
+            .unwrap();
+
+        let request = server
+            .try_recv()
+            .unwrap()
+            .expect("connect was not received");
+        assert_eq!(request.label(), PacketLabel::Connect);
+        let accept = client.try_recv().unwrap().expect("accept was not received");
+        assert_eq!(accept.label(), PacketLabel::ConnectAccept);
+
+        assert_eq!(request.source(), client.id());
+        client.id()
+    }
+
+    /// Waits long enough for tasks with the interval to be due, then runs them.
+    fn run_tasks_after(socket: &mut Socket, interval_ms: u64) -> Result<()> {
+        thread::sleep(Duration::from_millis(interval_ms + 20));
+        socket.run_tasks(false)
+    }
+
+    #[test]
+    fn disconnect_sweep_removes_silent_client() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+        assert!(server.is_connected(client_id));
+
+        // The client never pings, so the sweep times it out.
+        run_tasks_after(&mut server, 500).unwrap();
+        assert!(!server.is_connected(client_id));
+
+        let notice = server
+            .try_recv()
+            .unwrap()
+            .expect("timeout was not surfaced");
+        assert_eq!(notice.label(), PacketLabel::Disconnect);
+        assert_eq!(notice.source(), client_id);
+
+        let notice = client.try_recv().unwrap().expect("client was not notified");
+        assert_eq!(notice.label(), PacketLabel::Disconnect);
+    }
+
+    #[test]
+    fn capacity_warns_before_the_server_is_full() {
+        let opts = SocketOptions::for_loopback_test(true).max_clients(10);
+        let (mut server, _client) = loopback_pair_with(&opts);
+        for id in 1..=8 {
+            server.clients.add(ClientAddr::Local(ClientId(id))).unwrap();
+        }
+        assert_eq!(server.capacity(), (8, 10));
+        assert!(!server.is_near_capacity());
+
+        server.clients.add(ClientAddr::Local(ClientId(9))).unwrap();
+        assert!(server.is_near_capacity());
+    }
+
+    #[test]
+    fn connected_clients_follow_connects_and_disconnects() {
+        let (mut hub, mut server) = Socket::new_local_hub().unwrap();
+        let mut clients = [(); 2].map(|()| Socket::new_local_client(&mut hub).unwrap());
+        assert_eq!(server.client_count(), 0);
+        let remaining = server.capacity_remaining();
+
+        let ids = clients
+            .each_mut()
+            .map(|client| connect(&mut server, client));
+        assert_eq!(server.client_count(), 2);
+        assert_eq!(server.capacity_remaining(), remaining - 2);
+        assert!(ids.iter().all(|id| server.is_connected(*id)));
+        assert_eq!(server.capacity(), (2, remaining));
+        let mut connected = server.connected_clients();
+        connected.sort_unstable();
+        assert_eq!(connected, ids);
+
+        server.disconnect_client(ids[0], false).unwrap();
+        assert_eq!(server.client_count(), 1);
+        assert_eq!(server.capacity_remaining(), remaining - 1);
+        assert!(!server.is_connected(ids[0]));
+        assert_eq!(server.capacity(), (1, remaining));
+        assert_eq!(server.connected_clients(), [ids[1]]);
+    }
+
+    #[test]
+    fn archive_drain_frees_the_address() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+        let addr = *server.clients.get_addr(client_id).unwrap();
+
+        server.disconnect_client(client_id, false).unwrap();
+        if cfg!(not(feature = "shared_ip")) {
+            // The archived address cannot take a new slot until it is drained.
+            assert_eq!(server.clients.add(addr), Err(StorageError::ClientExists));
+        }
+
+        run_tasks_after(&mut server, 250).unwrap();
+        assert_eq!(server.clients.add(addr), Ok(client_id));
+    }
+
+    #[test]
+    fn ping_task_measures_rtt() {
+        let (mut server, mut client) = loopback_pair();
+        connect(&mut server, &mut client);
+        assert_eq!(client.rtt(SERVER_CLIENT_ID), None);
+
+        run_tasks_after(&mut client, 100).unwrap();
+        let ping = server.try_recv().unwrap().expect("ping was not sent");
+        assert_eq!(ping.label(), PacketLabel::Ping);
+
+        let reply = client.try_recv().unwrap().expect("pong was not sent");
+        assert_eq!(reply.label(), PacketLabel::Ping);
+        assert!(client.rtt(SERVER_CLIENT_ID).is_some());
+    }
+
+    /// Counter shared with task callbacks.
+    fn counter() -> Arc<AtomicUsize> {
+        Arc::new(AtomicUsize::new(0))
+    }
+
+    /// Waits for tasks with the interval to be due, then runs them regardless of the scheduler.
+    fn force_tasks_after(socket: &mut Socket, interval_ms: u64) {
+        thread::sleep(Duration::from_millis(interval_ms + 5));
+        socket.run_tasks(true).unwrap();
+    }
+
+    #[test]
+    fn one_shot_task_runs_exactly_once() {
+        let (mut server, _client) = loopback_pair();
+        let runs = counter();
+        let task_runs = Arc::clone(&runs);
+        let task_id = server.register_task_once("once", 10, move |_| {
+            task_runs.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        });
+
+        for _ in 0..3 {
+            force_tasks_after(&mut server, 10);
+        }
+
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert!(!server.cancel_task(task_id), "one-shot task was kept");
+    }
+
+    #[test]
+    fn cancelled_tasks_stop_running() {
+        let (mut server, _client) = loopback_pair();
+        let runs = counter();
+        let task_runs = Arc::clone(&runs);
+        let recurring = server.register_task("recurring", 10, move |_| {
+            task_runs.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        });
+
+        // A task that cancels itself on its first run.
+        let own_id = counter();
+        let task_id = Arc::clone(&own_id);
+        let own_runs = counter();
+        let task_runs = Arc::clone(&own_runs);
+        let own = server.register_task("self cancel", 10, move |sock| {
+            task_runs.fetch_add(1, Ordering::Relaxed);
+            assert!(sock.cancel_task(task_id.load(Ordering::Relaxed)));
+            Ok(())
+        });
+        own_id.store(own, Ordering::Relaxed);
+
+        force_tasks_after(&mut server, 10);
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert!(server.cancel_task(recurring));
+
+        force_tasks_after(&mut server, 10);
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(own_runs.load(Ordering::Relaxed), 1);
+        assert!(!server.cancel_task(own));
+    }
+
+    #[test]
+    fn remote_sockets_report_the_port_they_were_given() {
+        let socket = Socket::new_remote(&SocketOptions::default_client()).unwrap();
+        let addr = socket
+            .local_socket_addr()
+            .expect("remote socket has no address");
+        assert_ne!(addr.port(), 0);
+        assert_eq!(socket.addr(), addr.to_string());
+
+        let (server, _client) = loopback_pair();
+        assert_eq!(server.local_socket_addr(), None);
+    }
+
+    #[test]
+    fn loopback_packets_are_received_back() {
+        let (mut server, _client) = loopback_pair();
+        let label = PacketLabel::Extension(0x40);
+
+        // Sending to self by mistake is still rejected.
+        let mistake = Packet::with_payload(label, server.id(), 1_u32);
+        let result = server.send(Deliverable::new(server.id(), mistake));
+        assert_eq!(result, Err(NetError::NothingToDo));
+        assert!(server.try_recv().unwrap().is_none());
+
+        server.send_loopback(Packet::with_payload(label, ClientId(9), 7_u32));
+        let echo = server
+            .try_recv()
+            .unwrap()
+            .expect("loopback was not received");
+        assert_eq!(echo.label(), label);
+        assert_eq!(echo.source(), server.id());
+        assert_eq!(echo.payload::<u32>().unwrap(), 7);
+        assert!(server.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn shutdown_notifies_clients_once() {
+        let (mut server, mut client) = Socket::new_local_pair().unwrap();
+        let client_id = connect(&mut server, &mut client);
+
+        server.shutdown().unwrap();
+        let notice = client.try_recv().unwrap().expect("client was not notified");
+        assert_eq!(notice.label(), PacketLabel::Disconnect);
+
+        // Shutting down again sends nothing, and the socket no longer sends.
+        server.shutdown().unwrap();
+        assert!(client.try_recv().unwrap().is_none());
+
+        let packet = Packet::new(PacketLabel::Message, server.id());
+        let result = server.send(Deliverable::new(client_id, packet));
+        assert!(matches!(result, Err(NetError::Disconnected)));
+    }
+
+    #[test]
+    fn pongs_update_the_smoothed_rtt() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+
+        // Pongs echo the time the ping was sent, so stamp them in the past.
+        for sent_ago in [80, 160] {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            let sent = now.saturating_sub(Duration::from_millis(sent_ago));
+            let pong =
+                Packet::with_payload(PacketLabel::Ping, client.id(), PingPayload(sent, false));
+            client
+                .send(Deliverable::new(SERVER_CLIENT_ID, pong))
+                .unwrap();
+            server.try_recv().unwrap();
+        }
+
+        // 80ms, then an eighth of the way to 160ms.
+        let rtt = server.rtt(client_id).expect("rtt was not measured");
+        assert!(rtt >= Duration::from_millis(90), "rtt of {rtt:?}");
+        assert!(rtt < Duration::from_millis(110), "rtt of {rtt:?}");
+
+        // The server never measures a round trip to itself.
+        assert_eq!(server.rtt(SERVER_CLIENT_ID), None);
+    }
+
+    #[test]
+    fn low_priority_packets_are_dropped_over_the_bandwidth_budget() {
+        let opts = SocketOptions::for_loopback_test(true).max_bytes_per_sec(400);
+        let (mut server, mut client) = loopback_pair_with(&opts);
+        let client_id = connect(&mut server, &mut client);
+
+        let state =
+            Packet::with_payload(PacketLabel::Extension(0x40), server.id(), vec![0_u8; 100]);
+        let mut sent = 0;
+        while server
+            .send(Deliverable::new(client_id, state.clone()))
+            .is_ok()
+        {
+            sent += 1;
+        }
+        assert!(sent > 0 && sent < 4, "{sent} packets fit the budget");
+        assert!(matches!(
+            server.send(Deliverable::new(client_id, state.clone())),
+            Err(NetError::NothingToDo)
+        ));
+
+        // Acknowledgements, control, and reliable packets are still sent.
+        server.acknowledge(client_id, 1).unwrap();
+        let ping = server.ping_packet();
+        server.send(Deliverable::new(client_id, ping)).unwrap();
+        server
+            .send_reliable(Deliverable::new(client_id, state))
+            .unwrap();
+
+        let mut labels = vec![];
+        while let Ok(Some(packet)) = client.try_recv() {
+            labels.push(packet.label());
+        }
+        assert_eq!(labels.len(), sent + 3, "received {labels:?}");
+        assert!(labels.contains(&PacketLabel::Acknowledge));
+    }
+
+    #[test]
+    fn keepalive_pings_every_client() {
+        for keepalive in [true, false] {
+            let opts = SocketOptions::for_loopback_test(true)
+                .ping_interval(100)
+                .server_keepalive(keepalive);
+            let (mut hub, server) = LocalHub::new();
+            let mut server = Socket::new(SocketType::Local(server), &opts, None).unwrap();
+            let mut clients = [(); 2].map(|()| Socket::new_local_client(&mut hub).unwrap());
+            for client in &mut clients {
+                connect(&mut server, client);
             }
-            Ok(None) => Ok(None),
-            Err(why) => {
-                self.handle_invalid_packet_err(&why)?;
-                flee!(why)
+
+            run_tasks_after(&mut server, 100).unwrap();
+            for client in &mut clients {
+                let ping = client.try_recv().unwrap();
+                assert_eq!(
+                    ping.map(|ping| ping.label()),
+                    keepalive.then_some(PacketLabel::Ping),
+                    "keepalive: {keepalive}"
+                );
             }
         }
     }
+
+    #[test]
+    fn replayed_sequences_are_rejected() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+        let addr = *server.clients.get_addr(client_id).unwrap();
+        let newest = *server.clients.get_recv_sequence(client_id).unwrap();
+
+        let packet_at = |sequence: u16| {
+            let mut packet = Packet::new(PacketLabel::Ping, client_id);
+            packet.set_sequence(sequence);
+            packet
+        };
+
+        // Newer sequences are accepted once, skipped ones can still arrive late.
+        let ahead = newest.wrapping_add(3);
+        let late = newest.wrapping_add(1);
+        assert!(server.validate_sequence(&addr, &packet_at(ahead)).is_ok());
+        assert!(server.validate_sequence(&addr, &packet_at(late)).is_ok());
+
+        for sequence in [ahead, late, newest] {
+            assert!(
+                server
+                    .validate_sequence(&addr, &packet_at(sequence))
+                    .is_err(),
+                "sequence {sequence} was replayed"
+            );
+        }
+
+        // Jumping a whole window ahead is tracked the same as any newer sequence.
+        let far = ahead.wrapping_add(SEQUENCE_WINDOW);
+        assert!(server.validate_sequence(&addr, &packet_at(far)).is_ok());
+        assert!(server.validate_sequence(&addr, &packet_at(far)).is_err());
+        let recent = far.wrapping_sub(1);
+        assert!(server.validate_sequence(&addr, &packet_at(recent)).is_ok());
+    }
+
+    #[test]
+    fn packet_loss_is_tracked_across_the_sequence_wrap() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+        let addr = *server.clients.get_addr(client_id).unwrap();
+        *server.clients.get_recv_sequence_mut(client_id).unwrap() = u16::MAX - 1;
+        assert_eq!(server.packet_loss(client_id), None);
+
+        let packet_at = |sequence: u16| {
+            let mut packet = Packet::new(PacketLabel::Ping, client_id);
+            packet.set_sequence(sequence);
+            packet
+        };
+
+        // Sequences 0 and 1 are skipped as the sequence rolls over.
+        for sequence in [u16::MAX, 2, 3] {
+            server
+                .validate_sequence(&addr, &packet_at(sequence))
+                .unwrap();
+        }
+        assert!((server.packet_loss(client_id).unwrap() - 0.4).abs() < 1e-6);
+
+        server.validate_sequence(&addr, &packet_at(0)).unwrap();
+        assert!((server.packet_loss(client_id).unwrap() - 0.2).abs() < 1e-6);
+        assert_eq!(server.packet_loss(SERVER_CLIENT_ID), None);
+    }
+
+    #[test]
+    fn reliable_retransmits_are_delivered_once() {
+        let (mut server, mut client) = loopback_pair();
+        connect(&mut server, &mut client);
+
+        let label = PacketLabel::Extension(PacketLabel::EXTENSION_MIN);
+        let packet = Packet::new(label, client.id());
+        client
+            .send_reliable(Deliverable::new(SERVER_CLIENT_ID, packet))
+            .unwrap();
+
+        let received = server
+            .try_recv()
+            .unwrap()
+            .expect("reliable was not received");
+        assert_eq!(received.label(), label);
+
+        // The client has not read the acknowledgement yet, so it resends the packet.
+        run_tasks_after(&mut client, 50).unwrap();
+        assert!(
+            server.try_recv().unwrap().is_none(),
+            "duplicate was delivered"
+        );
+
+        // Both copies were acknowledged, so nothing is left to resend.
+        for _ in 0..2 {
+            let ack = client
+                .try_recv()
+                .unwrap()
+                .expect("copy was not acknowledged");
+            assert_eq!(ack.label(), PacketLabel::Acknowledge);
+        }
+        let channel = client.clients.get_reliable_mut(SERVER_CLIENT_ID).unwrap();
+        assert!(channel.expired(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn dropped_reliable_packets_are_resent_in_order() {
+        let (mut server, mut client) = loopback_pair();
+        connect(&mut server, &mut client);
+
+        let label = PacketLabel::Extension(PacketLabel::EXTENSION_MIN);
+        for _ in 0..2 {
+            let packet = Packet::new(label, client.id());
+            client
+                .send_reliable(Deliverable::new(SERVER_CLIENT_ID, packet))
+                .unwrap();
+        }
+
+        // The first transmission is lost, so the second is held behind the gap.
+        server.raw.try_recv().unwrap().expect("packet was not sent");
+        assert!(
+            server.try_recv().unwrap().is_none(),
+            "delivered out of order"
+        );
+
+        // Both are resent, the held copy of the second is a duplicate.
+        run_tasks_after(&mut client, 50).unwrap();
+        let mut sequences = vec![];
+        while let Some(packet) = server.try_recv().unwrap() {
+            assert_eq!(packet.label(), label);
+            sequences.extend(packet.reliable());
+        }
+        assert_eq!(sequences, [0, 1]);
+    }
+
+    #[test]
+    fn peers_that_never_acknowledge_are_disconnected() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+
+        let packet = Packet::new(PacketLabel::Extension(0x40), server.id());
+        server
+            .send_reliable(Deliverable::new(client_id, packet))
+            .unwrap();
+
+        // Every resend is lost.
+        let channel = server.clients.get_reliable_mut(client_id).unwrap();
+        while !channel.expired(Duration::ZERO).is_empty() {}
+        run_tasks_after(&mut server, 50).unwrap();
+        assert!(!server.is_connected(client_id));
+    }
+
+    #[test]
+    fn reliable_packets_are_acknowledged_by_sequence() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+
+        let label = PacketLabel::Extension(PacketLabel::EXTENSION_MIN);
+        for _ in 0..2 {
+            let packet = Packet::new(label, server.id());
+            server
+                .send_reliable(Deliverable::new(client_id, packet))
+                .unwrap();
+        }
+        let unreliable = Packet::new(label, server.id());
+        server
+            .send(Deliverable::new(client_id, unreliable))
+            .unwrap();
+
+        let mut sequences = vec![];
+        while let Some(packet) = client.try_recv().unwrap() {
+            sequences.extend(packet.reliable());
+        }
+        assert_eq!(sequences.len(), 2);
+
+        // One acknowledgement for each reliable packet, carrying its sequence.
+        let mut acked = vec![];
+        while let Some(packet) = server.try_recv().unwrap() {
+            assert_eq!(packet.label(), PacketLabel::Acknowledge);
+            acked.push(packet.payload::<AckPayload>().unwrap().0);
+        }
+        assert_eq!(acked, sequences);
+        let channel = server.clients.get_reliable_mut(client_id).unwrap();
+        assert!(channel.expired(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn outdated_clients_are_told_the_server_version() {
+        let (mut server, mut client) = loopback_pair();
+        let behind = Packet::CURRENT_VERSION - 1;
+        let payload = ConnectionPayload(behind, client.id(), 5000, 0, behind);
+        let request = Packet::with_payload(PacketLabel::Connect, client.id(), payload);
+        client
+            .send(Deliverable::new(SERVER_CLIENT_ID, request))
+            .unwrap();
+
+        assert!(!matches!(server.try_recv(), Ok(Some(_))));
+        assert!(!server.is_connected(client.id()));
+
+        let Err(NetError::SocketError(why)) = client.try_recv() else {
+            panic!("client was not told about the version");
+        };
+        let expected = format!("requires packet version {}", Packet::CURRENT_VERSION);
+        assert!(why.contains(&expected), "unexpected error: {why}");
+    }
+
+    #[test]
+    fn handshakes_sent_to_the_wrong_side_are_ignored() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+
+        // A stray request must not make the client adopt a new identity.
+        let payload = ConnectionPayload(Packet::CURRENT_VERSION, ClientId(99), 5000, 0, 0);
+        let stray = Packet::with_payload(PacketLabel::Connect, server.id(), payload);
+        server.send(Deliverable::new(client_id, stray)).unwrap();
+        assert!(matches!(client.try_recv(), Err(NetError::NothingToDo)));
+        assert_eq!(client.id(), client_id);
+
+        // A stray accept must not be answered or register anyone.
+        let payload = ConnectionPayload(Packet::CURRENT_VERSION, client_id, 5000, 0, 0);
+        let stray = Packet::with_payload(PacketLabel::ConnectAccept, client_id, payload);
+        client
+            .send(Deliverable::new(SERVER_CLIENT_ID, stray))
+            .unwrap();
+        assert!(matches!(server.try_recv(), Err(NetError::NothingToDo)));
+        assert_eq!(server.client_count(), 1);
+        assert!(client.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn servers_receive_their_own_loopback_pings() {
+        let (_hub, mut server) = Socket::new_local_hub().unwrap();
+        let ping = server.ping_packet();
+        server.send_loopback(ping);
+
+        let packet = server
+            .try_recv()
+            .unwrap()
+            .expect("ping was not looped back");
+        assert_eq!(packet.label(), PacketLabel::Ping);
+        assert_eq!(packet.source(), server.id());
+        assert!(server.try_recv().unwrap().is_none(), "ping was answered");
+    }
+
+    #[test]
+    fn servers_cannot_blacklist_themselves() {
+        let (_hub, mut server) = Socket::new_local_hub().unwrap();
+        assert!(matches!(
+            server.blacklist_client(SERVER_CLIENT_ID, Duration::from_secs(30)),
+            Err(NetError::NotConnected(..))
+        ));
+    }
+
+    #[test]
+    fn hub_broadcasts_reach_every_client() {
+        let (mut hub, mut server) = Socket::new_local_hub().unwrap();
+        let mut clients: Vec<Socket> = (0..3)
+            .map(|_| Socket::new_local_client(&mut hub).unwrap())
+            .collect();
+        let ids: Vec<ClientId> = clients
+            .iter_mut()
+            .map(|client| connect(&mut server, client))
+            .collect();
+        assert_eq!(ids, vec![ClientId(1), ClientId(2), ClientId(3)]);
+
+        server
+            .broadcast(&Packet::new(PacketLabel::Message, server.id()))
+            .unwrap();
+        for client in &mut clients {
+            let packet = client
+                .try_recv()
+                .unwrap()
+                .expect("broadcast was not received");
+            assert_eq!(packet.label(), PacketLabel::Message);
+            assert!(
+                client.try_recv().unwrap().is_none(),
+                "received more than one copy"
+            );
+        }
+
+        // Packets sent to one client are routed to it alone.
+        let direct = Packet::new(PacketLabel::Message, server.id());
+        server.send(Deliverable::new(ids[1], direct)).unwrap();
+        assert!(clients[0].try_recv().unwrap().is_none());
+        assert!(clients[1].try_recv().unwrap().is_some());
+        assert!(clients[2].try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn try_recv_all_drains_into_the_buffer() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+        for _ in 0..4 {
+            let packet = Packet::new(PacketLabel::Message, client_id);
+            client
+                .send(Deliverable::new(SERVER_CLIENT_ID, packet))
+                .unwrap();
+        }
+
+        // Packets already in the buffer are kept, only the new ones are counted.
+        let mut out = vec![Packet::new(PacketLabel::Ping, client_id)];
+        assert_eq!(server.try_recv_all(&mut out).unwrap(), 4);
+        assert_eq!(out.len(), 5);
+        assert!(
+            out[1..]
+                .iter()
+                .all(|packet| packet.label() == PacketLabel::Message)
+        );
+        assert_eq!(server.try_recv_all(&mut out).unwrap(), 0);
+    }
+
+    #[test]
+    fn out_of_range_sources_are_rejected_early() {
+        let (mut server, client) = loopback_pair();
+        let addr = ClientAddr::Local(client.id());
+
+        // The server takes 4 clients, so 1 to 4 are the only IDs it can assign.
+        for source in [ClientId(5), ClientId(u16::MAX - 1)] {
+            for label in [PacketLabel::Ping, PacketLabel::Connect] {
+                let packet = Packet::with_payload(
+                    label,
+                    source,
+                    ConnectionPayload(Packet::CURRENT_VERSION, source, 5000, 0, 0),
+                );
+                let Err(NetError::InvalidPacket(_, InvalidPacketError::Source, _)) =
+                    server.process(addr, packet)
+                else {
+                    panic!("{label:?} from {source} was not rejected");
+                };
+            }
+        }
+
+        assert_eq!(server.client_count(), 0);
+        assert_eq!(server.clients.get_id(&addr), None);
+    }
+
+    #[test]
+    fn fragments_from_unconnected_clients_are_not_buffered() {
+        let (mut server, mut client) = loopback_pair();
+        let payload = ConnectionPayload(Packet::CURRENT_VERSION, client.id(), 5000, 0, 0);
+        let request = Packet::with_payload(PacketLabel::Connect, client.id(), payload);
+
+        let parts = client.fragments.split(request, 4).unwrap();
+        assert!(parts.len() > 1);
+        for part in parts {
+            let addr = ClientAddr::Local(client.id());
+            assert!(server.process(addr, part).unwrap().is_none());
+        }
+
+        assert_eq!(
+            server.fragments.drain_expired(0),
+            0,
+            "fragments were buffered"
+        );
+        assert!(!server.is_connected(client.id()));
+    }
+
+    #[test]
+    fn large_payloads_are_fragmented_and_reassembled() {
+        let (mut server, mut client) = loopback_pair();
+        let client_id = connect(&mut server, &mut client);
+
+        let label = PacketLabel::Extension(PacketLabel::EXTENSION_MIN);
+        let payload: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        let packet = Packet::with_payload(label, client_id, payload.clone());
+        client
+            .send(Deliverable::new(SERVER_CLIENT_ID, packet))
+            .unwrap();
+
+        let whole = server
+            .try_recv()
+            .unwrap()
+            .expect("packet was not reassembled");
+        assert_eq!(whole.label(), label);
+        assert_eq!(whole.payload::<Vec<u8>>().unwrap(), payload);
+    }
+
+    #[test]
+    fn large_payloads_are_fragmented_over_udp() {
+        // The server binds to a port picked by the OS so tests never fight over the default one.
+        let server_opts = SocketOptions::for_loopback_test(true);
+        let raw = RemoteSocket::new(false, server_opts.recv_buffer_size).unwrap();
+        let port = raw.local_addr().port();
+        let mut server =
+            Socket::new(SocketType::Remote(Box::new(raw)), &server_opts, None).unwrap();
+
+        let client_opts =
+            SocketOptions::for_loopback_test(false).server_address(format!("127.0.0.1:{port}"));
+        let mut client = Socket::new_remote(&client_opts).unwrap();
+
+        let payload = ConnectionPayload(
+            Packet::CURRENT_VERSION,
+            client.id(),
+            5000,
+            0,
+            Packet::MIN_VERSION,
+        );
+        let request = Packet::with_payload(PacketLabel::Connect, client.id(), payload);
+        client
+            .send(Deliverable::new(SERVER_CLIENT_ID, request))
+            .unwrap();
+        server.recv().unwrap().expect("connect was not received");
+        let accept = client.recv().unwrap().expect("accept was not received");
+        assert_eq!(accept.label(), PacketLabel::ConnectAccept);
+
+        let label = PacketLabel::Extension(PacketLabel::EXTENSION_MIN);
+        let payload: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        assert!(payload.len() > client_opts.max_datagram_size);
+        let packet = Packet::with_payload(label, client.id(), payload.clone());
+        client
+            .send(Deliverable::new(SERVER_CLIENT_ID, packet))
+            .unwrap();
+
+        let whole = server.recv().unwrap().expect("packet was not reassembled");
+        assert_eq!(whole.label(), label);
+        assert_eq!(whole.payload::<Vec<u8>>().unwrap(), payload);
+    }
 }
@@ -3,19 +3,36 @@ use std::time::{Duration, Instant};
 
 use crate::utils::SparseSet;
 
-use super::ClientId;
+use super::reliable::ReliableChannel;
+use super::{ClientId, Packet};
 
 type Result<T> = std::result::Result<T, StorageError>;
 
+/// Weight given to the previous round-trip time estimate, out of `RTT_SMOOTHING_TOTAL`.
+const RTT_SMOOTHING: u32 = 7;
+const RTT_SMOOTHING_TOTAL: u32 = 8;
+
+/// Packets expected from a client before the loss counters are halved, so the loss ratio
+/// reflects recent traffic.
+const LOSS_WINDOW: u32 = 256;
+
+/// Number of sequences behind the newest one that are remembered as received, so replays within
+/// the window are rejected.
+const RECEIVED_WINDOW: usize = 1024;
+
+/// Length of the window that outgoing bytes are counted over for rate limiting.
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
 /// Error types for the client storage.
 #[allow(dead_code)]
 #[derive(Debug, PartialEq)]
-pub(crate) enum StorageError {
+pub enum StorageError {
     OffsetOverflow,           // Offset overflow when creating the storage.
     InvalidClientIdCollision, // Invalid client ID collision when creating the storage.
     AtCapacity,               // Storage is at capacity when adding a new client.
     ClientExists,             // Client already exists in the storage.
     TimedOut,                 // Client timed out.
+    ReservedId,               // Assigning the client ID would reuse a reserved ID.
 }
 
 impl std::fmt::Display for StorageError {
@@ -26,26 +43,82 @@ impl std::fmt::Display for StorageError {
             StorageError::AtCapacity => write!(f, "capacity reached"),
             StorageError::ClientExists => write!(f, "client already exists"),
             StorageError::TimedOut => write!(f, "client timed out"),
+            StorageError::ReservedId => write!(f, "client ID is reserved"),
         }
     }
 }
 
+impl std::error::Error for StorageError {}
+
+/// Bitmask of the sequences received from a client, bit `n` being the sequence `n` behind the
+/// newest one received.
+#[derive(Clone, Copy)]
+pub(crate) struct ReceivedWindow([u64; RECEIVED_WINDOW / 64]);
+
+impl ReceivedWindow {
+    /// Creates a window where only the newest sequence has been received.
+    fn new() -> Self {
+        let mut seen = [0; RECEIVED_WINDOW / 64];
+        seen[0] = 1;
+        Self(seen)
+    }
+
+    /// Moves the newest sequence `count` ahead, ageing every sequence already received.
+    fn advance(&mut self, count: usize) {
+        let words = self.0.len();
+        let (shift, bits) = (count / 64, count % 64);
+        for i in (0..words).rev() {
+            let mut word = 0;
+            if i >= shift {
+                word = self.0[i - shift] << bits;
+                if bits > 0 && i > shift {
+                    word |= self.0[i - shift - 1] >> (64 - bits);
+                }
+            }
+            self.0[i] = word;
+        }
+    }
+
+    /// Marks the sequence `age` behind the newest one as received. Returns false if it was
+    /// already received or is too old to tell.
+    fn mark(&mut self, age: usize) -> bool {
+        if age >= RECEIVED_WINDOW {
+            return false;
+        }
+
+        let (word, bit) = (age / 64, 1 << (age % 64));
+        if self.0[word] & bit != 0 {
+            return false;
+        }
+
+        self.0[word] |= bit;
+        true
+    }
+}
+
 /// Information about the clients connected to the server.
 pub(crate) struct ClientStorage<T> {
     id_offset: ClientId,   // Offset to add to the client ID.
     max_clients: usize,    // Maximum number of clients.
     invalid_key: ClientId, // Invalid key for the sparse set.
 
-    addr_id: HashMap<T, usize>, // Maps socket address to ID.
-    addr: SparseSet<T>,         // Maps ID to socket address.
-    sequence: SparseSet<u16>,   // Maps ID to sequence number.
-    ping: SparseSet<Instant>,   // Maps ID to ping.
+    addr_id: HashMap<T, usize>,           // Maps socket address to ID.
+    addr: SparseSet<T>,                   // Maps ID to socket address.
+    sequence: SparseSet<u16>,             // Maps ID to sequence number.
+    recv_seq: SparseSet<u16>,             // Maps ID to the newest sequence number received.
+    received: SparseSet<ReceivedWindow>,  // Maps ID to the sequences recently received.
+    ping: SparseSet<Instant>,             // Maps ID to ping.
+    rtt: SparseSet<Duration>,             // Maps ID to the smoothed round-trip time.
+    reliable: SparseSet<ReliableChannel>, // Maps ID to reliable delivery state.
+    sent: SparseSet<(Instant, usize)>,    // Maps ID to the window start and bytes sent within it.
+    loss: SparseSet<(u32, u32)>,          // Maps ID to packets expected and lost recently.
 
     archive: HashMap<T, (usize, Instant)>, // Cache for archiving clients.
     errors: HashMap<T, (usize, Instant)>,  // Cache for error counts.
-    blacklist: HashMap<T, Instant>,        // Blacklist for clients.
+    blacklist: HashMap<T, Instant>,        // Blacklist for clients, mapped to when they expire.
 
-    pool: Vec<usize>, // Pool of IDs to use for new clients.
+    pool: Vec<usize>,  // Pool of IDs to use for new clients.
+    next_fresh: usize, // Next internal ID that has never been assigned.
 }
 
 impl<T> ClientStorage<T>
@@ -72,7 +145,13 @@ where
             addr_id: HashMap::with_capacity(max_clients),
             addr: SparseSet::new(max_clients, usize::from(invalid_key)),
             sequence: SparseSet::new(max_clients, usize::from(invalid_key)),
+            recv_seq: SparseSet::new(max_clients, usize::from(invalid_key)),
+            received: SparseSet::new(max_clients, usize::from(invalid_key)),
             ping: SparseSet::new(max_clients, usize::from(invalid_key)),
+            rtt: SparseSet::new(max_clients, usize::from(invalid_key)),
+            reliable: SparseSet::new(max_clients, usize::from(invalid_key)),
+            sent: SparseSet::new(max_clients, usize::from(invalid_key)),
+            loss: SparseSet::new(max_clients, usize::from(invalid_key)),
 
             // archive: Cache::new(max_clients, usize::from(invalid_key)),
             archive: HashMap::new(),
@@ -80,6 +159,7 @@ where
             blacklist: HashMap::new(),
 
             pool: Vec::with_capacity(max_clients),
+            next_fresh: 0,
         })
     }
 
@@ -89,6 +169,38 @@ where
         self.invalid_key
     }
 
+    /// Number of connected clients.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.addr.length()
+    }
+
+    /// Maximum number of clients allowed.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.max_clients
+    }
+
+    /// Checks if the maximum number of clients are connected.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.max_clients
+    }
+
+    /// Checks if an ID is reserved and must never be assigned to a client. This covers the
+    /// IDs below the offset (such as the server's ID) and the invalid ID.
+    #[inline]
+    fn is_reserved(&self, id: ClientId) -> bool {
+        id < self.id_offset || id == self.invalid_key
+    }
+
+    /// Checks if an ID could be assigned to a client, meaning it is not reserved and falls below
+    /// the offset plus the maximum number of clients.
+    #[inline]
+    pub fn is_assignable(&self, id: ClientId) -> bool {
+        !self.is_reserved(id) && self.map_internal(id) < self.max_clients
+    }
+
     /// Maps an external ID to an internal ID.
     #[inline]
     fn map_internal(&self, id: ClientId) -> usize {
@@ -124,12 +236,11 @@ where
         }
     }
 
-    /// Drains the blacklist cache of expired entries. This will remove clients that have been timed out.
-    pub fn task_drain_blacklist(&mut self, timeout_ms: u64) {
+    /// Drains the blacklist cache of expired entries. This will remove clients whose timeout has ended.
+    pub fn task_drain_blacklist(&mut self) {
         if !self.blacklist.is_empty() {
-            self.blacklist.retain(|_addr, timestamp| {
-                timestamp.elapsed().as_millis() < u128::from(timeout_ms)
-            });
+            let now = Instant::now();
+            self.blacklist.retain(|_addr, expires| *expires > now);
         }
     }
 
@@ -143,9 +254,20 @@ where
         }
     }
 
-    /// Checks if a client is currently timed out.
+    /// Checks if a client is currently timed out. Expired entries that have not been drained yet
+    /// are not blacklisted.
     pub fn is_blacklisted(&self, addr: &T) -> bool {
-        self.blacklist.contains_key(addr)
+        self.blacklist
+            .get(addr)
+            .is_some_and(|expires| *expires > Instant::now())
+    }
+
+    /// Removes a client from the blacklist before its timeout ends. Returns true if it was
+    /// blacklisted.
+    pub fn unblacklist(&mut self, addr: &T) -> bool {
+        self.blacklist
+            .remove(addr)
+            .is_some_and(|expires| expires > Instant::now())
     }
 
     /// Obtains the sequence number for a client.
@@ -158,6 +280,49 @@ where
         self.sequence.get_mut(self.map_internal(client_id))
     }
 
+    /// Obtains the newest sequence number received from a client.
+    pub fn get_recv_sequence(&self, client_id: ClientId) -> Option<&u16> {
+        self.recv_seq.get(self.map_internal(client_id))
+    }
+
+    /// Obtains a mutable reference for the newest sequence number received from a client.
+    pub fn get_recv_sequence_mut(&mut self, client_id: ClientId) -> Option<&mut u16> {
+        self.recv_seq.get_mut(self.map_internal(client_id))
+    }
+
+    /// Obtains a mutable reference for the reliable delivery state of a client.
+    pub fn get_reliable_mut(&mut self, client_id: ClientId) -> Option<&mut ReliableChannel> {
+        self.reliable.get_mut(self.map_internal(client_id))
+    }
+
+    /// Obtains the reliable packets for every client that have not been acknowledged within
+    /// `retry_ms`, marking them as resent.
+    pub fn expired_reliable(&mut self, retry_ms: u64) -> Vec<(ClientId, Packet)> {
+        let retry = Duration::from_millis(retry_ms);
+        let keys: Vec<usize> = self.reliable.iter().map(|(key, _)| *key).collect();
+
+        let mut expired = vec![];
+        for key in keys {
+            let client_id = self.map_external(key);
+            if let Some(channel) = self.reliable.get_mut(key) {
+                expired.extend(channel.expired(retry).into_iter().map(|p| (client_id, p)));
+            }
+        }
+
+        expired
+    }
+
+    /// Obtains the clients with a reliable packet that was resent too many times without being
+    /// acknowledged, meaning they are unreachable.
+    pub fn exhausted_reliable(&self, retry_ms: u64) -> Vec<ClientId> {
+        let retry = Duration::from_millis(retry_ms);
+        self.reliable
+            .iter()
+            .filter(|(_, channel)| channel.is_exhausted(retry))
+            .map(|(key, _)| self.map_external(*key))
+            .collect()
+    }
+
     /// Obtains the ping for a client.
     #[allow(dead_code)]
     pub fn get_ping(&self, client_id: ClientId) -> Option<&Instant> {
@@ -170,6 +335,111 @@ where
         self.ping.get_mut(self.map_internal(client_id))
     }
 
+    /// Obtains the smoothed round-trip time for a client. None until a pong has been received.
+    pub fn get_rtt(&self, client_id: ClientId) -> Option<&Duration> {
+        if self.is_reserved(client_id) {
+            return None;
+        }
+
+        self.rtt.get(self.map_internal(client_id))
+    }
+
+    /// Folds a new round-trip time sample into the client's exponentially weighted average.
+    pub fn update_rtt(&mut self, client_id: ClientId, sample: Duration) {
+        if !self.contains(client_id) {
+            return; // Not connected.
+        }
+
+        let key = self.map_internal(client_id);
+        let smoothed = match self.rtt.get(key) {
+            Some(rtt) => (*rtt * RTT_SMOOTHING + sample) / RTT_SMOOTHING_TOTAL,
+            None => sample, // First sample, nothing to smooth against.
+        };
+        self.rtt.insert(key, smoothed);
+    }
+
+    /// Marks a sequence from a client as received, `delta` being how far it is ahead of the newest
+    /// one received. Returns false if the sequence was already received, meaning it is a replay.
+    /// Clients that are not connected have nothing to replay.
+    pub fn mark_received(&mut self, client_id: ClientId, delta: i16) -> bool {
+        let Some(window) = self.received.get_mut(self.map_internal(client_id)) else {
+            return true;
+        };
+
+        if delta > 0 {
+            window.advance(usize::from(delta.unsigned_abs()));
+            window.mark(0)
+        } else {
+            window.mark(usize::from(delta.unsigned_abs()))
+        }
+    }
+
+    /// Records a packet received from a client, `delta` being how far its sequence is ahead of
+    /// the newest one received. Skipped sequences are counted as lost until they arrive late.
+    pub fn record_sequence(&mut self, client_id: ClientId, delta: i16) {
+        if self.is_reserved(client_id) {
+            return;
+        }
+
+        let Some((expected, lost)) = self.loss.get_mut(self.map_internal(client_id)) else {
+            return;
+        };
+
+        match delta {
+            1.. => {
+                let delta = u32::from(delta.unsigned_abs());
+                *expected += delta;
+                *lost += delta - 1;
+            }
+            ..0 => *lost = lost.saturating_sub(1), // Arrived late, it was not lost after all.
+            0 => {}                                // Duplicate.
+        }
+
+        if *expected > LOSS_WINDOW {
+            *expected /= 2;
+            *lost /= 2;
+        }
+    }
+
+    /// Ratio of recent packets from a client that were lost, between 0 and 1. None until a
+    /// packet has been received.
+    pub fn get_packet_loss(&self, client_id: ClientId) -> Option<f32> {
+        if self.is_reserved(client_id) {
+            return None;
+        }
+
+        match self.loss.get(self.map_internal(client_id)) {
+            Some(&(expected, lost)) if expected > 0 =>
+            {
+                #[allow(clippy::cast_precision_loss)]
+                Some(lost.min(expected) as f32 / expected as f32)
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks if sending `bytes` more to a client would exceed `limit` bytes within the current
+    /// window. Clients that are not connected are never limited.
+    pub fn exceeds_bandwidth(&self, client_id: ClientId, bytes: usize, limit: usize) -> bool {
+        match self.sent.get(self.map_internal(client_id)) {
+            Some((start, sent)) if start.elapsed() < BANDWIDTH_WINDOW => sent + bytes > limit,
+            Some(_) => bytes > limit,
+            None => false,
+        }
+    }
+
+    /// Records `bytes` sent to a client, starting a new window if the current one has elapsed.
+    pub fn record_sent(&mut self, client_id: ClientId, bytes: usize) {
+        if let Some((start, sent)) = self.sent.get_mut(self.map_internal(client_id)) {
+            if start.elapsed() >= BANDWIDTH_WINDOW {
+                *start = Instant::now();
+                *sent = 0;
+            }
+
+            *sent += bytes;
+        }
+    }
+
     /// Obtains the error count for a client.
     pub fn get_errors(&mut self, addr: &T) -> Option<&usize> {
         self.errors.get(addr).map(|(count, _)| count)
@@ -185,8 +455,12 @@ where
         }
     }
 
-    /// Obtains the address from a clients ID.
+    /// Obtains the address from a clients ID. Reserved IDs never have an address.
     pub fn get_addr(&self, client_id: ClientId) -> Option<&T> {
+        if self.is_reserved(client_id) {
+            return None;
+        }
+
         self.addr.get(self.map_internal(client_id))
     }
 
@@ -203,25 +477,26 @@ where
         }
     }
 
-    ///  Blacklists a client and allows its `ClientId` to be reused.
-    pub fn blacklist_client(&mut self, client_id: ClientId, addr: &T) {
+    ///  Blacklists a client for a duration and allows its `ClientId` to be reused.
+    pub fn blacklist_client(&mut self, client_id: ClientId, addr: &T, duration: Duration) {
         if let Some(addr) = self.remove(client_id) {
-            self.blacklist.insert(addr, Instant::now());
+            self.blacklist.insert(addr, Instant::now() + duration);
             self.pool.push(self.map_internal(client_id));
         } else if self.archive.remove(addr).is_some() {
-            self.blacklist.insert(*addr, Instant::now());
+            self.blacklist.insert(*addr, Instant::now() + duration);
             self.pool.push(self.map_internal(client_id));
         }
     }
 
-    /// Blacklists a client by its address.
-    pub fn blacklist_client_addr(&mut self, addr: &T) {
+    /// Blacklists a client by its address for a duration. Blacklisting an address again replaces
+    /// its previous duration.
+    pub fn blacklist_client_addr(&mut self, addr: &T, duration: Duration) {
         if let Some(client_id) = self.addr_id.get(addr) {
-            self.blacklist_client(self.map_external(*client_id), addr);
+            self.blacklist_client(self.map_external(*client_id), addr, duration);
         } else if let Some((client_id, _)) = self.archive.get(addr) {
-            self.blacklist_client(self.map_external(*client_id), addr);
+            self.blacklist_client(self.map_external(*client_id), addr, duration);
         } else {
-            self.blacklist.insert(*addr, Instant::now());
+            self.blacklist.insert(*addr, Instant::now() + duration);
         }
     }
 
@@ -245,24 +520,40 @@ where
         if let Some(addr) = self.addr.remove(self.map_internal(client_id)) {
             self.addr_id.remove(&addr);
             self.sequence.remove(self.map_internal(client_id));
+            self.recv_seq.remove(self.map_internal(client_id));
+            self.received.remove(self.map_internal(client_id));
+            self.reliable.remove(self.map_internal(client_id));
             self.ping.remove(self.map_internal(client_id));
+            self.rtt.remove(self.map_internal(client_id));
+            self.sent.remove(self.map_internal(client_id));
+            self.loss.remove(self.map_internal(client_id));
             return Some(addr);
         }
 
         None
     }
 
-    /// Inserts a client into the storage.
-    pub fn insert(&mut self, client_id: ClientId, addr: T) {
+    /// Inserts a client into the storage. Both directions of the session start counting from
+    /// `sequence`, the base established during the handshake.
+    pub fn insert(&mut self, client_id: ClientId, addr: T, sequence: u16) {
         self.addr_id.insert(addr, self.map_internal(client_id));
         self.addr.insert(self.map_internal(client_id), addr);
-        self.sequence.insert(self.map_internal(client_id), 0);
+        self.sequence.insert(self.map_internal(client_id), sequence);
+        self.recv_seq.insert(self.map_internal(client_id), sequence);
+        self.received
+            .insert(self.map_internal(client_id), ReceivedWindow::new());
+        self.reliable
+            .insert(self.map_internal(client_id), ReliableChannel::new());
         self.ping
             .insert(self.map_internal(client_id), Instant::now());
+        self.sent
+            .insert(self.map_internal(client_id), (Instant::now(), 0));
+        self.loss.insert(self.map_internal(client_id), (0, 0));
     }
 
     /// Adds a client to the storage. Returns the Client ID assigned.
-    /// Returns `Self::INVALID_CLIENT_ID` if the maximum number of clients has been reached.
+    /// Returns `StorageError::AtCapacity` if the maximum number of clients has been reached, and
+    /// `StorageError::ReservedId` if the ID to assign is reserved, leaving the storage unchanged.
     pub fn add(&mut self, addr: T) -> Result<ClientId> {
         if self.is_blacklisted(&addr) {
             return Err(StorageError::TimedOut); // Client timed out.
@@ -278,23 +569,37 @@ where
             return Ok(self.map_external(*id)); // Client already exists.
         }
 
-        let internal_id = if let Some((id, _)) = self.archive.remove(&addr) {
-            id // Reuse an ID from the archive.
-        } else if let Some(id) = self.pool.pop() {
-            id // Reuse an ID form the pool.
+        let internal_id = if let Some((id, _)) = self.archive.get(&addr) {
+            *id // Reuse an ID from the archive.
+        } else if let Some(id) = self.pool.last() {
+            *id // Reuse an ID form the pool.
+        } else if self.next_fresh < self.max_clients {
+            self.next_fresh // Never assigned before.
         } else {
-            self.addr.length() // Current length of the sparse set.
+            return Err(StorageError::AtCapacity);
         };
 
-        if internal_id >= self.max_clients && self.pool.is_empty() {
-            return Err(StorageError::AtCapacity);
+        let client_id = self.map_external(internal_id);
+        if self.is_reserved(client_id) {
+            return Err(StorageError::ReservedId);
         }
 
-        let client_id = self.map_external(internal_id);
-        self.insert(client_id, addr);
+        // Only claim the ID once it is known to be assignable.
+        if self.archive.remove(&addr).is_none() && self.pool.pop().is_none() {
+            // Archived IDs are not in use, but are not free either.
+            self.next_fresh += 1;
+        }
+
+        // Random base so sequences are unpredictable and differ between sessions.
+        self.insert(client_id, addr, rand::random());
         Ok(client_id)
     }
 
+    /// Checks if a client is connected. Reserved IDs are never connected.
+    pub fn contains(&self, client_id: ClientId) -> bool {
+        !self.is_reserved(client_id) && self.addr.has_key(self.map_internal(client_id))
+    }
+
     /// Obtains the IDs and Socket Addresses of all clients.
     pub fn addr_iter(&self) -> impl Iterator<Item = (ClientId, &T)> + '_ {
         self.addr
@@ -303,12 +608,195 @@ where
     }
 
     /// Obtains the next ID to use for a new client.
+    ///
+    /// # Errors
+    ///
+    /// - `StorageError::ReservedId` if the next ID is reserved and cannot be assigned.
     #[allow(dead_code)]
-    pub fn next_id(&self) -> ClientId {
-        if let Some(id) = self.pool.last() {
+    pub fn next_id(&self) -> Result<ClientId> {
+        let client_id = if let Some(id) = self.pool.last() {
             self.map_external(*id)
         } else {
-            self.map_external(self.addr.length())
+            self.map_external(self.next_fresh)
+        };
+
+        if self.is_reserved(client_id) {
+            return Err(StorageError::ReservedId);
         }
+
+        Ok(client_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::error::{self as net, NetError};
+
+    use super::*;
+
+    /// Storage whose IDs start after the server's ID, the same as a server socket.
+    fn storage(max_clients: u16) -> ClientStorage<u32> {
+        ClientStorage::new(ClientId(1), ClientId(max_clients), ClientId::INVALID).unwrap()
+    }
+
+    fn assert_assignable(storage: &ClientStorage<u32>, client_id: ClientId) {
+        assert_ne!(client_id, ClientId(0), "server ID was assigned");
+        assert_ne!(client_id, ClientId::INVALID, "invalid ID was assigned");
+        assert!(storage.is_assignable(client_id));
+    }
+
+    #[test]
+    fn fresh_ids_skip_reserved() {
+        let mut storage = storage(4);
+        for addr in 0..4 {
+            let next = storage.next_id().unwrap();
+            let client_id = storage.add(addr).unwrap();
+            assert_eq!(next, client_id);
+            assert_assignable(&storage, client_id);
+        }
+
+        assert_eq!(storage.add(4), Err(StorageError::AtCapacity));
+    }
+
+    #[test]
+    fn blacklist_entries_expire_on_their_own_duration() {
+        let mut storage = storage(4);
+        let short = storage.add(10).unwrap();
+        storage.blacklist_client_addr(&10, Duration::from_millis(20));
+        storage.blacklist_client_addr(&11, Duration::from_secs(30));
+        assert_eq!(storage.get_addr(short), None);
+        assert!(storage.is_blacklisted(&10) && storage.is_blacklisted(&11));
+        assert_eq!(storage.add(11), Err(StorageError::TimedOut));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!storage.is_blacklisted(&10));
+        assert!(storage.is_blacklisted(&11));
+        storage.add(10).unwrap();
+
+        // Operators can lift a ban early, an expired one is not reported as lifted.
+        assert!(storage.unblacklist(&11));
+        assert!(!storage.unblacklist(&11));
+        storage.add(11).unwrap();
+    }
+
+    #[test]
+    fn occupancy_tracks_adds_and_removals() {
+        let mut storage = storage(3);
+        assert_eq!((storage.len(), storage.capacity()), (0, 3));
+
+        let ids: Vec<ClientId> = (0..3).map(|addr| storage.add(addr).unwrap()).collect();
+        assert_eq!(storage.len(), 3);
+        assert!(storage.is_full());
+
+        storage.archive_client(ids[1]);
+        assert_eq!(storage.len(), 2);
+        assert!(!storage.is_full());
+    }
+
+    #[test]
+    #[cfg(feature = "shared_ip")]
+    fn archive_reuse_skips_reserved() {
+        let mut storage = storage(2);
+        let first = storage.add(10).unwrap();
+        storage.add(11).unwrap();
+
+        // Reconnecting from the same address reuses the archived ID.
+        storage.archive_client(first);
+        let again = storage.add(10).unwrap();
+        assert_eq!(again, first);
+        assert_assignable(&storage, again);
+    }
+
+    #[test]
+    fn pool_reuse_skips_reserved() {
+        let mut storage = storage(2);
+        let first = storage.add(10).unwrap();
+        storage.add(11).unwrap();
+
+        // Drained archive entries return their IDs to the pool for any address.
+        storage.archive_client(first);
+        storage.task_drain_archive(0);
+        assert_eq!(storage.next_id(), Ok(first));
+
+        let reused = storage.add(12).unwrap();
+        assert_eq!(reused, first);
+        assert_assignable(&storage, reused);
+        assert_eq!(storage.add(13), Err(StorageError::AtCapacity));
+    }
+
+    #[test]
+    fn largest_range_skips_invalid() {
+        let max = u16::MAX - 1;
+        let mut storage = storage(max);
+        for addr in 0..u32::from(max) {
+            let client_id = storage.add(addr).unwrap();
+            assert_assignable(&storage, client_id);
+        }
+
+        assert_eq!(storage.add(u32::from(max)), Err(StorageError::AtCapacity));
+    }
+
+    #[test]
+    fn rtt_is_smoothed() {
+        let mut storage = storage(2);
+        let client_id = storage.add(10).unwrap();
+        assert_eq!(storage.get_rtt(client_id), None);
+
+        // The first sample is taken as is, later ones move it an eighth of the way.
+        storage.update_rtt(client_id, Duration::from_millis(80));
+        assert_eq!(storage.get_rtt(client_id), Some(&Duration::from_millis(80)));
+        storage.update_rtt(client_id, Duration::from_millis(160));
+        assert_eq!(storage.get_rtt(client_id), Some(&Duration::from_millis(90)));
+        storage.update_rtt(client_id, Duration::from_millis(10));
+        assert_eq!(storage.get_rtt(client_id), Some(&Duration::from_millis(80)));
+
+        // Clients that are not connected are not tracked, nor is the server.
+        storage.update_rtt(ClientId(2), Duration::from_millis(80));
+        assert_eq!(storage.get_rtt(ClientId(2)), None);
+        storage.update_rtt(ClientId(0), Duration::from_millis(80));
+        assert_eq!(storage.get_rtt(ClientId(0)), None);
+    }
+
+    #[test]
+    fn packet_loss_counts_skipped_sequences() {
+        let mut storage = storage(2);
+        let client_id = storage.add(10).unwrap();
+        assert_eq!(storage.get_packet_loss(client_id), None);
+
+        // Two sequences are skipped out of six expected.
+        for delta in [1, 3, 1, 1] {
+            storage.record_sequence(client_id, delta);
+        }
+        assert!((storage.get_packet_loss(client_id).unwrap() - 2.0 / 6.0).abs() < 1e-6);
+
+        // A skipped sequence arriving late was not lost, duplicates change nothing.
+        storage.record_sequence(client_id, -2);
+        storage.record_sequence(client_id, 0);
+        assert!((storage.get_packet_loss(client_id).unwrap() - 1.0 / 6.0).abs() < 1e-6);
+
+        // Old losses fade as the window moves on.
+        for _ in 0..2 * LOSS_WINDOW {
+            storage.record_sequence(client_id, 1);
+        }
+        assert!(storage.get_packet_loss(client_id).unwrap() < 0.01);
+
+        // The server's own ID is never tracked.
+        storage.record_sequence(ClientId(0), 2);
+        assert_eq!(storage.get_packet_loss(ClientId(0)), None);
+    }
+
+    #[test]
+    fn errors_keep_their_kind_as_net_errors() {
+        fn add(storage: &mut ClientStorage<u32>, addr: u32) -> net::Result<ClientId> {
+            Ok(storage.add(addr)?)
+        }
+
+        let mut storage = storage(2);
+        add(&mut storage, 10).unwrap();
+        add(&mut storage, 11).unwrap();
+        assert!(matches!(
+            add(&mut storage, 12),
+            Err(NetError::Storage(StorageError::AtCapacity))
+        ));
     }
 }
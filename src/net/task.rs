@@ -12,6 +12,7 @@ pub(crate) struct Task {
     name: String,           // Name of the task.
     frequency_ms: u64,      // Frequency of the task in milliseconds.
     next_run: Instant,      // Next run time of the task.
+    once: bool,             // Removed after its first run instead of being rescheduled.
     callback: TaskCallback, // Callback function to execute when the task runs.
 }
 
@@ -26,10 +27,23 @@ impl Task {
             name: name.into(),
             frequency_ms,
             next_run: Instant::now() + Duration::from_millis(frequency_ms),
+            once: false,
             callback: Box::new(callback),
         }
     }
 
+    /// ID of the task.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Checks if the task only runs once.
+    #[inline]
+    pub fn is_once(&self) -> bool {
+        self.once
+    }
+
     /// Checks if the task is ready to run based on the current time.
     #[inline]
     pub fn is_ready(&self) -> bool {
@@ -49,11 +63,16 @@ impl Task {
     }
 }
 
-/// Represents a task scheduler that manages multiple tasks.
+/// Represents a task scheduler that manages multiple tasks. Tasks that are due are taken out
+/// of the scheduler while they run, so they are free to register or unregister tasks
+/// (including themselves) through the socket.
 pub(crate) struct TaskScheduler {
-    frequency_ms: u64, // Frequency of running the scheduler in milliseconds.
-    next_run: Instant, // Next run time for the scheduler.
-    tasks: Vec<Task>,  // List of tasks to be scheduled.
+    frequency_ms: u64,     // Frequency of running the scheduler in milliseconds.
+    next_run: Instant,     // Next run time for the scheduler.
+    tasks: Vec<Task>,      // List of tasks to be scheduled.
+    next_id: usize,        // ID of the next task registered.
+    running: Vec<usize>,   // IDs of the tasks taken out to run.
+    cancelled: Vec<usize>, // IDs of running tasks unregistered before they were restored.
 }
 
 impl Default for TaskScheduler {
@@ -68,20 +87,62 @@ impl TaskScheduler {
             frequency_ms,
             next_run: Instant::now() + Duration::from_millis(frequency_ms),
             tasks: Vec::new(),
+            next_id: 1,
+            running: Vec::new(),
+            cancelled: Vec::new(),
         }
     }
 
-    /// Adds a new task to the scheduler.
+    /// Adds a new task to the scheduler, returning its ID.
     pub fn register<F, N: Into<String>>(&mut self, name: N, freq_ms: u64, callback: F) -> usize
     where
         F: Fn(&mut Socket) -> Result<()> + Send + Sync + 'static,
     {
-        let task_id = self.tasks.len() + 1;
-        self.tasks.push(Task::new(task_id, name, freq_ms, callback));
+        self.insert(Task::new(self.next_id, name, freq_ms, callback))
+    }
+
+    /// Adds a task that runs once after `delay_ms` and is then removed, returning its ID.
+    pub fn register_once<F, N: Into<String>>(
+        &mut self,
+        name: N,
+        delay_ms: u64,
+        callback: F,
+    ) -> usize
+    where
+        F: FnOnce(&mut Socket) -> Result<()> + Send + Sync + 'static,
+    {
+        // The callback is only ever called once, so it can be moved out on the first run.
+        let mut callback = Some(callback);
+        let mut task = Task::new(self.next_id, name, delay_ms, move |socket| {
+            callback.take().map_or(Ok(()), |callback| callback(socket))
+        });
+        task.once = true;
+        self.insert(task)
+    }
+
+    /// Inserts a task, returning its ID.
+    fn insert(&mut self, task: Task) -> usize {
+        let task_id = task.id;
+        self.next_id += 1;
+        self.tasks.push(task);
         self.sort();
         task_id
     }
 
+    /// Removes a task, including one that is currently running. Returns false if there is no
+    /// task with the ID.
+    pub fn unregister(&mut self, task_id: usize) -> bool {
+        if let Some(idx) = self.tasks.iter().position(|task| task.id == task_id) {
+            self.tasks.remove(idx);
+            true
+        } else if self.running.contains(&task_id) && !self.cancelled.contains(&task_id) {
+            self.cancelled.push(task_id);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Sorts the tasks based on their next run time.
     pub fn sort(&mut self) {
         self.tasks.sort_by(|a, b| a.next_run.cmp(&b.next_run));
@@ -92,26 +153,62 @@ impl TaskScheduler {
         Instant::now() >= self.next_run
     }
 
-    /// Executes the tasks that are ready to run.
-    pub fn run(&mut self, socket: &mut Socket) -> Result<()> {
-        let mut exec = false;
-
-        for task in &mut self.tasks {
-            if task.is_ready() {
-                task.run(socket)?;
-                task.reset();
-                exec = true;
-            } else {
-                break;
-            }
-        }
+    /// Takes the tasks that are ready to run out of the scheduler. They must be handed back with
+    /// `restore` once they have run. Every task is checked rather than stopping at the first
+    /// one that is not ready, so a stale order can never starve a task that is due.
+    pub fn take_ready(&mut self) -> Vec<Task> {
+        let (ready, waiting): (Vec<Task>, Vec<Task>) = std::mem::take(&mut self.tasks)
+            .into_iter()
+            .partition(Task::is_ready);
+
+        self.tasks = waiting;
+        self.running = ready.iter().map(Task::id).collect();
+        ready
+    }
 
-        if exec {
-            self.sort(); // Re-sort the tasks after execution.
-        }
+    /// Returns tasks taken by `take_ready`, dropping those unregistered while they ran. Updates
+    /// the next run time of the scheduler.
+    pub fn restore(&mut self, tasks: Vec<Task>) {
+        let cancelled = std::mem::take(&mut self.cancelled);
+        self.tasks.extend(
+            tasks
+                .into_iter()
+                .filter(|task| !cancelled.contains(&task.id)),
+        );
+        self.running.clear();
+        self.sort();
 
         // Update the next run time for the scheduler itself.
         self.next_run = Instant::now() + Duration::from_millis(self.frequency_ms);
-        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use super::*;
+
+    #[test]
+    fn short_interval_task_is_not_starved() {
+        let mut scheduler = TaskScheduler::new(0);
+        let slow = scheduler.register("slow", 60_000, |_| Ok(()));
+        let fast = scheduler.register("fast", 10, |_| Ok(()));
+
+        for _ in 0..3 {
+            // A stale order puts the task that is far from due first.
+            scheduler.tasks.sort_by_key(|task| task.id != slow);
+            assert!(scheduler.take_ready().is_empty(), "ran before its interval");
+            scheduler.restore(vec![]);
+
+            sleep(Duration::from_millis(15));
+            let mut ready = scheduler.take_ready();
+            assert_eq!(ready.iter().map(Task::id).collect::<Vec<_>>(), [fast]);
+
+            ready.iter_mut().for_each(Task::reset);
+            scheduler.restore(ready);
+        }
+
+        assert_eq!(scheduler.tasks.len(), 2);
     }
 }
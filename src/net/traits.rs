@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::hash::Hash;
 use std::time::Duration;
 
 use super::error::{NetError, Result};
@@ -20,12 +22,164 @@ pub(crate) trait SocketHandler {
 pub trait NetEncoder {
     /// Encodes the object into a byte vector.
     fn encode(self) -> Vec<u8>;
+
+    /// Encodes the object into a byte vector, failing instead of panicking if it cannot be
+    /// represented on the wire. Types made of other fields encode each of them the same way.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NetCode` if a collection has more entries than its length prefix can hold.
+    fn try_encode(self) -> Result<Vec<u8>>
+    where
+        Self: Sized,
+    {
+        Ok(self.encode())
+    }
+}
+
+/// Encodes the `u32` length prefix of a collection, failing if `len` does not fit.
+fn length_prefix(len: usize, collection: &str) -> Result<Vec<u8>> {
+    u32::try_from(len)
+        .map(NetEncoder::encode)
+        .map_err(|_| NetError::NetCode(format!("{collection} length {len} exceeds u32::MAX")))
+}
+
+/// Bytes a decode may still read. Shared by every nested field of a payload, so a length prefix
+/// deep inside it is checked against what the whole payload has left rather than the field alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeBudget(usize);
+
+impl DecodeBudget {
+    /// Creates a budget allowing `max_len` bytes to be read.
+    pub fn new(max_len: usize) -> Self {
+        Self(max_len)
+    }
+
+    /// Truncates `data` to the bytes that may still be read.
+    pub fn limit(self, data: &[u8]) -> &[u8] {
+        &data[..data.len().min(self.0)]
+    }
+
+    /// Charges `used` bytes against the budget.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NetCode` if more bytes were used than remain.
+    pub fn consume(&mut self, used: usize) -> Result<()> {
+        let Some(remaining) = self.0.checked_sub(used) else {
+            return Err(NetError::NetCode(format!(
+                "Decoded {used} bytes but only {} were available",
+                self.0
+            )));
+        };
+
+        self.0 = remaining;
+        Ok(())
+    }
+
+    /// Ensures a declared count of elements could fit in the bytes remaining, checked before
+    /// anything is allocated for them. Every element takes at least a byte, except zero-sized ones
+    /// whose count is bounded all the same.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NetCode` if the count exceeds the bytes remaining.
+    pub fn reserve(self, count: usize) -> Result<()> {
+        if count > self.0 {
+            return Err(NetError::NetCode(format!(
+                "Declared {count} elements but only {} bytes remain",
+                self.0
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 /// Custom decoder to receive a packet from the network.
 pub trait NetDecoder: Sized {
     /// Decodes the object from a byte slice. Returns a tuple of the decoded object and the number of bytes consumed.
     fn decode(data: &[u8]) -> Result<(Self, usize)>;
+
+    /// Decodes the object without reading more than `budget` allows, charging the bytes used
+    /// against it. Types made of other fields pass the same budget down to each of them.
+    fn decode_budgeted(data: &[u8], budget: &mut DecodeBudget) -> Result<(Self, usize)> {
+        let (value, used) = Self::decode(budget.limit(data))?;
+        budget.consume(used)?;
+        Ok((value, used))
+    }
+
+    /// Decodes the object from at most `max_len` bytes of `data`. The object can never read past
+    /// this budget, even if more data follows, and reporting more bytes consumed than the budget
+    /// allows is treated as an error.
+    #[allow(dead_code)]
+    fn decode_bounded(data: &[u8], max_len: usize) -> Result<(Self, usize)> {
+        Self::decode_budgeted(data, &mut DecodeBudget::new(max_len))
+    }
+}
+
+/// Payload whose wire format has changed over time. Wrapped in `Versioned` so the receiver knows
+/// which format was sent and can upgrade older ones.
+pub trait VersionedPayload: Sized {
+    /// Version of the current wire format, starting at 1.
+    const VERSION: u8;
+
+    /// Decodes an older `version` of the payload, filling the fields it lacks with defaults.
+    ///
+    /// # Errors
+    ///
+    /// - `NetError::NetCode` if the version is not supported or the payload fails to decode.
+    fn decode_version(version: u8, _data: &[u8]) -> Result<(Self, usize)> {
+        Err(NetError::NetCode(format!(
+            "Unsupported payload version {version}, current is {}",
+            Self::VERSION
+        )))
+    }
+}
+
+/// Envelope that prefixes a payload with the version of its wire format. Decoding accepts the
+/// current version and any older version the payload knows how to upgrade.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Versioned<T>(pub T);
+
+impl<T: VersionedPayload + NetEncoder> NetEncoder for Versioned<T> {
+    fn encode(self) -> Vec<u8> {
+        let mut out = vec![T::VERSION];
+        out.extend(self.0.encode());
+        out
+    }
+
+    fn try_encode(self) -> Result<Vec<u8>> {
+        let mut out = vec![T::VERSION];
+        out.extend(self.0.try_encode()?);
+        Ok(out)
+    }
+}
+
+impl<T: VersionedPayload + NetDecoder> NetDecoder for Versioned<T> {
+    fn decode(data: &[u8]) -> Result<(Self, usize)> {
+        Self::decode_budgeted(data, &mut DecodeBudget::new(data.len()))
+    }
+
+    fn decode_budgeted(data: &[u8], budget: &mut DecodeBudget) -> Result<(Self, usize)> {
+        let (version, offset) = u8::decode_budgeted(data, budget)?;
+        let (payload, used) = match version {
+            v if v == T::VERSION => T::decode_budgeted(&data[offset..], budget)?,
+            v if v > T::VERSION => {
+                return Err(NetError::NetCode(format!(
+                    "Payload version {v} is newer than the supported {}",
+                    T::VERSION
+                )));
+            }
+            v => {
+                let (payload, used) = T::decode_version(v, budget.limit(&data[offset..]))?;
+                budget.consume(used)?;
+                (payload, used)
+            }
+        };
+
+        Ok((Self(payload), offset + used))
+    }
 }
 
 #[macro_export]
@@ -92,6 +246,113 @@ impl NetDecoder for Vec<u8> {
     }
 }
 
+/// Vector of elements encoded with a `u32` length prefix, so unlike `Vec<u8>` it does not have
+/// to be the last field of a payload.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetVec<T>(pub Vec<T>);
+
+impl<T> From<Vec<T>> for NetVec<T> {
+    fn from(items: Vec<T>) -> Self {
+        Self(items)
+    }
+}
+
+impl<T: NetEncoder> NetEncoder for NetVec<T> {
+    /// # Panics
+    ///
+    /// If there are more than `u32::MAX` elements, use `try_encode` to handle it as an error.
+    fn encode(self) -> Vec<u8> {
+        match self.try_encode() {
+            Ok(out) => out,
+            Err(why) => panic!("{why}"),
+        }
+    }
+
+    fn try_encode(self) -> Result<Vec<u8>> {
+        let mut out = length_prefix(self.0.len(), "NetVec")?;
+        for item in self.0 {
+            out.extend(item.try_encode()?);
+        }
+        Ok(out)
+    }
+}
+
+impl<T: NetDecoder> NetDecoder for NetVec<T> {
+    fn decode(data: &[u8]) -> Result<(Self, usize)> {
+        Self::decode_budgeted(data, &mut DecodeBudget::new(data.len()))
+    }
+
+    fn decode_budgeted(data: &[u8], budget: &mut DecodeBudget) -> Result<(Self, usize)> {
+        let (len, mut offset) = u32::decode_budgeted(data, budget)?;
+        let len = len as usize;
+
+        // Never trust the prefix for the allocation, it cannot exceed the bytes remaining.
+        budget.reserve(len)?;
+        let mut items = Vec::with_capacity(len);
+        for idx in 0..len {
+            let (item, used) = T::decode_budgeted(&data[offset..], budget).map_err(|why| {
+                NetError::NetCode(format!(
+                    "Not enough bytes to decode element {idx} of {len}: {why}"
+                ))
+            })?;
+
+            items.push(item);
+            offset += used;
+        }
+
+        Ok((Self(items), offset))
+    }
+}
+
+impl<K: NetEncoder + Eq + Hash, V: NetEncoder> NetEncoder for HashMap<K, V> {
+    /// # Panics
+    ///
+    /// If there are more than `u32::MAX` entries, use `try_encode` to handle it as an error.
+    fn encode(self) -> Vec<u8> {
+        match self.try_encode() {
+            Ok(out) => out,
+            Err(why) => panic!("{why}"),
+        }
+    }
+
+    fn try_encode(self) -> Result<Vec<u8>> {
+        let mut out = length_prefix(self.len(), "HashMap")?;
+        for (key, value) in self {
+            out.extend(key.try_encode()?);
+            out.extend(value.try_encode()?);
+        }
+        Ok(out)
+    }
+}
+
+impl<K: NetDecoder + Eq + Hash, V: NetDecoder> NetDecoder for HashMap<K, V> {
+    fn decode(data: &[u8]) -> Result<(Self, usize)> {
+        Self::decode_budgeted(data, &mut DecodeBudget::new(data.len()))
+    }
+
+    fn decode_budgeted(data: &[u8], budget: &mut DecodeBudget) -> Result<(Self, usize)> {
+        let (len, mut offset) = u32::decode_budgeted(data, budget)?;
+        let len = len as usize;
+
+        // Never trust the prefix for the allocation, it cannot exceed the bytes remaining.
+        budget.reserve(len)?;
+        let mut map = HashMap::with_capacity(len);
+        for idx in 0..len {
+            let ((key, value), used) = <(K, V)>::decode_budgeted(&data[offset..], budget)?;
+            if map.insert(key, value).is_some() {
+                return Err(NetError::NetCode(format!(
+                    "Duplicate key in entry {idx} of {len}"
+                )));
+            }
+
+            offset += used;
+        }
+
+        Ok((map, offset))
+    }
+}
+
 impl NetEncoder for &[u8] {
     fn encode(self) -> Vec<u8> {
         self.to_vec()
@@ -132,26 +393,132 @@ impl<T: NetEncoder> NetEncoder for Option<T> {
             }
         }
     }
+
+    fn try_encode(self) -> Result<Vec<u8>> {
+        match self {
+            Some(inner) => {
+                let mut out = vec![1];
+                out.extend(inner.try_encode()?);
+                Ok(out)
+            }
+            None => Ok(vec![0]),
+        }
+    }
 }
 
 impl<T: NetDecoder> NetDecoder for Option<T> {
     fn decode(data: &[u8]) -> Result<(Self, usize)> {
+        Self::decode_budgeted(data, &mut DecodeBudget::new(data.len()))
+    }
+
+    fn decode_budgeted(data: &[u8], budget: &mut DecodeBudget) -> Result<(Self, usize)> {
         // Need at least 1 byte to see if it's Some or None
+        let data = budget.limit(data);
         if data.is_empty() {
             return Err(NetError::NetCode(
                 "Not enough bytes to decode Option".to_string(),
             ));
         }
 
+        budget.consume(1)?;
         if data[0] == 0 {
             Ok((None, 1)) // 0 means None, read 1 byte.
         } else {
-            let (value, size) = T::decode(&data[1..])?;
+            let (value, size) = T::decode_budgeted(&data[1..], budget)?;
             Ok((Some(value), size + 1)) // +1 for the tag byte.
         }
     }
 }
 
+impl<T: NetEncoder, const N: usize> NetEncoder for [T; N] {
+    fn encode(self) -> Vec<u8> {
+        self.into_iter().flat_map(NetEncoder::encode).collect()
+    }
+
+    fn try_encode(self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for item in self {
+            out.extend(item.try_encode()?);
+        }
+        Ok(out)
+    }
+}
+
+impl<T: NetDecoder, const N: usize> NetDecoder for [T; N] {
+    fn decode(data: &[u8]) -> Result<(Self, usize)> {
+        Self::decode_budgeted(data, &mut DecodeBudget::new(data.len()))
+    }
+
+    fn decode_budgeted(data: &[u8], budget: &mut DecodeBudget) -> Result<(Self, usize)> {
+        let mut items = Vec::with_capacity(N);
+        let mut offset = 0;
+
+        for idx in 0..N {
+            let (item, used) = T::decode_budgeted(&data[offset..], budget).map_err(|why| {
+                NetError::NetCode(format!(
+                    "Not enough bytes to decode array element {idx} of {N}: {why}"
+                ))
+            })?;
+
+            items.push(item);
+            offset += used;
+        }
+
+        // Exactly `N` elements were decoded, so the conversion cannot fail.
+        let Ok(array) = <[T; N]>::try_from(items) else {
+            unreachable!("Decoded array length does not match {N}");
+        };
+
+        Ok((array, offset))
+    }
+}
+
+/// Implements the codec for tuples, encoding the fields in order like a derived tuple struct.
+macro_rules! impl_netcode_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: NetEncoder),+> NetEncoder for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode(self) -> Vec<u8> {
+                let ($($name,)+) = self;
+                let mut out = Vec::new();
+                $(out.extend($name.encode());)+
+                out
+            }
+
+            #[allow(non_snake_case)]
+            fn try_encode(self) -> Result<Vec<u8>> {
+                let ($($name,)+) = self;
+                let mut out = Vec::new();
+                $(out.extend($name.try_encode()?);)+
+                Ok(out)
+            }
+        }
+
+        impl<$($name: NetDecoder),+> NetDecoder for ($($name,)+) {
+            fn decode(data: &[u8]) -> Result<(Self, usize)> {
+                Self::decode_budgeted(data, &mut DecodeBudget::new(data.len()))
+            }
+
+            fn decode_budgeted(data: &[u8], budget: &mut DecodeBudget) -> Result<(Self, usize)> {
+                let mut offset = 0;
+                let value = ($({
+                    let (field, used) = $name::decode_budgeted(&data[offset..], budget)?;
+                    offset += used;
+                    field
+                },)+);
+
+                Ok((value, offset))
+            }
+        }
+    };
+}
+
+impl_netcode_tuple!(A, B);
+impl_netcode_tuple!(A, B, C);
+impl_netcode_tuple!(A, B, C, D);
+impl_netcode_tuple!(A, B, C, D, E);
+impl_netcode_tuple!(A, B, C, D, E, F);
+
 impl NetEncoder for Duration {
     fn encode(self) -> Vec<u8> {
         let mut out = vec![0; 12];
@@ -187,3 +554,312 @@ impl NetDecoder for () {
         Ok(((), 0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::netcode_derive::{NetDecode, NetEncode};
+    use crate::vec2f::Vec2f;
+
+    #[derive(NetDecode, NetEncode, Debug, Clone, PartialEq)]
+    struct Nested {
+        id: u8,
+        groups: NetVec<NetVec<u16>>,
+    }
+
+    #[derive(NetDecode, NetEncode, Debug, Clone, PartialEq)]
+    struct Cached {
+        x: f32,
+        #[netcode(skip)]
+        length_squared: f32,
+        y: f32,
+    }
+
+    #[derive(NetDecode, NetEncode, Debug, Clone, PartialEq)]
+    struct CachedTuple(u16, #[netcode(skip)] u32, u8);
+
+    #[test]
+    fn skipped_fields_reset_to_default() {
+        let bytes = Cached {
+            x: 3.0,
+            length_squared: 25.0,
+            y: 4.0,
+        }
+        .encode();
+        assert_eq!(bytes.len(), 8, "skipped field was encoded");
+
+        let expected = Cached {
+            x: 3.0,
+            length_squared: 0.0,
+            y: 4.0,
+        };
+        assert_eq!(Cached::decode(&bytes).unwrap(), (expected, 8));
+
+        let bytes = CachedTuple(1, 99, 2).encode();
+        assert_eq!(
+            CachedTuple::decode(&bytes).unwrap(),
+            (CachedTuple(1, 0, 2), 3)
+        );
+    }
+
+    #[derive(NetDecode, NetEncode, Debug, PartialEq)]
+    enum Deployed {
+        #[netcode(tag = 4)]
+        Move(u8),
+        #[netcode(tag = 9)]
+        Stop,
+    }
+
+    #[derive(NetDecode, NetEncode, Debug, PartialEq)]
+    enum Reordered {
+        #[netcode(tag = 9)]
+        Stop,
+        Idle,
+        #[netcode(tag = 4)]
+        Move(u8),
+    }
+
+    #[test]
+    fn explicit_tags_survive_reordering() {
+        let bytes = Deployed::Move(3).encode();
+        assert_eq!(bytes, vec![4, 3]);
+        assert_eq!(Reordered::decode(&bytes).unwrap(), (Reordered::Move(3), 2));
+
+        let bytes = Deployed::Stop.encode();
+        assert_eq!(Reordered::decode(&bytes).unwrap(), (Reordered::Stop, 1));
+
+        // Untagged variants keep their declaration index.
+        assert_eq!(Reordered::Idle.encode(), vec![1]);
+    }
+
+    #[test]
+    fn nested_fields_round_trip() {
+        let value = Nested {
+            id: 7,
+            groups: NetVec(vec![NetVec(vec![1, 2]), NetVec(vec![]), NetVec(vec![3])]),
+        };
+
+        let bytes = value.clone().encode();
+        assert_eq!(Nested::decode(&bytes).unwrap(), (value, bytes.len()));
+    }
+
+    #[test]
+    fn hostile_length_prefix_is_rejected_before_allocating() {
+        // One group, which declares u32::MAX elements with only two bytes following it.
+        let mut bytes = vec![7];
+        bytes.extend(1u32.encode());
+        bytes.extend(u32::MAX.encode());
+        bytes.extend(1u16.encode());
+
+        let Err(NetError::NetCode(why)) = Nested::decode(&bytes) else {
+            panic!("hostile length prefix was decoded");
+        };
+        assert!(why.contains("only 2 bytes remain"), "{why}");
+    }
+
+    #[test]
+    fn budget_is_shared_across_fields() {
+        // Three elements fit in the data, but not in the budget left after the prefix.
+        let mut bytes = 3u32.encode();
+        bytes.extend([1, 2, 3]);
+        assert!(NetVec::<u8>::decode_bounded(&bytes, 6).is_err());
+        assert_eq!(
+            NetVec::<u8>::decode_bounded(&bytes, 7).unwrap(),
+            (NetVec(vec![1, 2, 3]), 7)
+        );
+
+        let mut budget = DecodeBudget::new(bytes.len());
+        assert!(<(u32, u8)>::decode_budgeted(&bytes, &mut budget).is_ok());
+        assert_eq!(budget, DecodeBudget::new(2));
+    }
+
+    #[test]
+    fn arrays_round_trip() {
+        let numbers = [1u32, 2, 3];
+        let bytes = numbers.encode();
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(<[u32; 3]>::decode(&bytes).unwrap(), (numbers, 12));
+
+        let points = [Vec2f(1.5, -2.0), Vec2f(0.0, 8.25)];
+        let bytes = points.encode();
+        assert_eq!(<[Vec2f; 2]>::decode(&bytes).unwrap(), (points, 16));
+    }
+
+    #[test]
+    fn array_errors_when_elements_run_out() {
+        let bytes = [1u32, 2].encode();
+        let Err(NetError::NetCode(why)) = <[u32; 3]>::decode(&bytes) else {
+            panic!("array decoded from too few elements");
+        };
+        assert!(why.contains("element 2 of 3"), "{why}");
+    }
+
+    #[test]
+    fn net_vec_can_be_followed_by_other_fields() {
+        let bytes = (NetVec(vec![1u16, 2, 3]), 9u8).encode();
+        assert_eq!(bytes.len(), 4 + 6 + 1);
+
+        let ((items, trailing), used) = <(NetVec<u16>, u8)>::decode(&bytes).unwrap();
+        assert_eq!(items, NetVec(vec![1, 2, 3]));
+        assert_eq!(trailing, 9);
+        assert_eq!(used, bytes.len());
+    }
+
+    #[test]
+    fn tuples_round_trip() {
+        let bytes = (7u8, 0xDEAD_BEEF_u32).encode();
+        assert_eq!(<(u8, u32)>::decode(&bytes).unwrap(), ((7, 0xDEAD_BEEF), 5));
+
+        let value = (Vec2f(1.5, -2.0), true, 513u16);
+        let bytes = value.encode();
+        assert_eq!(<(Vec2f, bool, u16)>::decode(&bytes).unwrap(), (value, 11));
+    }
+
+    #[test]
+    fn tuple_fields_share_the_budget() {
+        // The second prefix fits the data, but not what the budget has left after the first field.
+        let bytes = (NetVec(vec![1u8, 2]), NetVec(vec![3u8, 4, 5, 6, 7])).encode();
+        let Err(NetError::NetCode(why)) =
+            <(NetVec<u8>, NetVec<u8>)>::decode_bounded(&bytes, bytes.len() - 1)
+        else {
+            panic!("tuple read past its budget");
+        };
+        assert!(why.contains("only 4 bytes remain"), "{why}");
+
+        let mut budget = DecodeBudget::new(bytes.len() + 3);
+        let (_, used) = <(NetVec<u8>, NetVec<u8>)>::decode_budgeted(&bytes, &mut budget).unwrap();
+        assert_eq!(used, bytes.len());
+        assert_eq!(budget, DecodeBudget::new(3));
+    }
+
+    #[test]
+    fn hash_maps_round_trip() {
+        let map = HashMap::from([(1u16, Vec2f(1.0, 2.0)), (9, Vec2f(-3.0, 0.5))]);
+        let bytes = map.clone().try_encode().unwrap();
+        assert_eq!(bytes, map.clone().encode());
+        assert_eq!(
+            HashMap::<u16, Vec2f>::decode(&bytes).unwrap(),
+            (map, 4 + 2 * 10)
+        );
+    }
+
+    #[test]
+    fn hash_map_duplicate_keys_are_rejected() {
+        let mut bytes = 2u32.encode();
+        bytes.extend((5u16, 1u8).encode());
+        bytes.extend((5u16, 2u8).encode());
+
+        let Err(NetError::NetCode(why)) = HashMap::<u16, u8>::decode(&bytes) else {
+            panic!("duplicate key was decoded");
+        };
+        assert!(why.contains("Duplicate key in entry 1 of 2"), "{why}");
+    }
+
+    #[test]
+    fn hash_map_errors_are_not_relabeled() {
+        // The second entry is missing its value, the error names what actually failed.
+        let mut bytes = 2u32.encode();
+        bytes.extend((5u16, 1u8).encode());
+        bytes.extend(6u16.encode());
+
+        let Err(NetError::NetCode(why)) = HashMap::<u16, u8>::decode(&bytes) else {
+            panic!("truncated entry was decoded");
+        };
+        assert!(why.contains("decode u8"), "{why}");
+
+        // Hostile prefixes are rejected against the budget before allocating.
+        let mut bytes = u32::MAX.encode();
+        bytes.extend((5u16, 1u8).encode());
+        let Err(NetError::NetCode(why)) = HashMap::<u16, u8>::decode(&bytes) else {
+            panic!("hostile length prefix was decoded");
+        };
+        assert!(why.contains("only 3 bytes remain"), "{why}");
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn oversized_length_prefix_is_an_error() {
+        let too_long = u32::MAX as usize + 1;
+        let Err(NetError::NetCode(why)) = length_prefix(too_long, "HashMap") else {
+            panic!("length over u32::MAX was encoded");
+        };
+        assert!(why.contains("exceeds u32::MAX"), "{why}");
+        assert_eq!(length_prefix(3, "NetVec").unwrap(), 3u32.encode());
+    }
+
+    #[test]
+    fn try_encode_matches_encode_for_derived_types() {
+        let value = Nested {
+            id: 7,
+            groups: NetVec(vec![NetVec(vec![1, 2]), NetVec(vec![3])]),
+        };
+        assert_eq!(value.clone().try_encode().unwrap(), value.encode());
+        assert_eq!(
+            Deployed::Move(3).try_encode().unwrap(),
+            Deployed::Move(3).encode()
+        );
+        assert_eq!(
+            Some((NetVec(vec![1u8]), [2u16; 2])).try_encode().unwrap(),
+            Some((NetVec(vec![1u8]), [2u16; 2])).encode()
+        );
+    }
+
+    /// Second version of a payload that added `name` after the first.
+    #[derive(NetDecode, NetEncode, Debug, PartialEq)]
+    struct Greeting {
+        id: u16,
+        name: String,
+    }
+
+    impl VersionedPayload for Greeting {
+        const VERSION: u8 = 2;
+
+        fn decode_version(version: u8, data: &[u8]) -> Result<(Self, usize)> {
+            match version {
+                1 => {
+                    let (id, used) = u16::decode(data)?;
+                    let name = String::new();
+                    Ok((Self { id, name }, used))
+                }
+                _ => Err(NetError::NetCode(format!("Unsupported version {version}"))),
+            }
+        }
+    }
+
+    #[test]
+    fn older_versions_are_upgraded_with_defaults() {
+        let mut v1 = vec![1];
+        v1.extend(9u16.encode());
+        let (Versioned(greeting), used) = Versioned::<Greeting>::decode(&v1).unwrap();
+        assert_eq!(used, v1.len());
+        assert_eq!(
+            greeting,
+            Greeting {
+                id: 9,
+                name: String::new()
+            }
+        );
+
+        let current = Greeting {
+            id: 4,
+            name: "hi".to_string(),
+        };
+        let bytes = Versioned(current).encode();
+        assert_eq!(bytes[0], 2);
+        let (Versioned(decoded), _) = Versioned::<Greeting>::decode(&bytes).unwrap();
+        assert_eq!(
+            decoded,
+            Greeting {
+                id: 4,
+                name: "hi".to_string()
+            }
+        );
+
+        // Newer and unknown older versions cannot be understood.
+        for version in [0, 3] {
+            let mut bytes = vec![version];
+            bytes.extend(9u16.encode());
+            assert!(Versioned::<Greeting>::decode(&bytes).is_err(), "{version}");
+        }
+    }
+}
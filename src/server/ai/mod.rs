@@ -1,3 +1,6 @@
+use crate::server::world_map::WorldMap;
+use crate::vec2f::Vec2f;
+
 /// Current state of the AI.
 pub(crate) enum AiState {
     Pursue,          // Entity to target.
@@ -8,16 +11,144 @@ pub(crate) enum AiState {
 /// Basic AI that can be modified.
 pub(crate) struct BasicAi {
     pub state: AiState,
+    dwell: u32,               // Updates remaining before the state can change on its own.
+    path: Vec<Vec2f>,         // Waypoints left to follow, the next is last.
+    path_goal: Option<usize>, // Cell of the target the path was planned towards.
 }
 
 impl BasicAi {
+    /// Distance a target must come within to start being pursued.
+    pub const PURSUE_RANGE: f32 = 5.0;
+    /// Distance a target must get beyond to stop being pursued. Larger than `PURSUE_RANGE` so a
+    /// target near the edge does not flip the state back and forth.
+    pub const ESCAPE_RANGE: f32 = 7.0;
+    /// Updates a state is kept for before `try_set_state` can change it.
+    pub const MIN_DWELL: u32 = 10;
+    /// Distance a waypoint is considered reached within.
+    const WAYPOINT_REACHED: f32 = 0.05;
+
     pub fn new() -> Self {
         Self {
             state: AiState::Idle,
+            dwell: 0,
+            path: Vec::new(),
+            path_goal: None,
         }
     }
 
+    /// Changes the state, ignoring the dwell time.
     pub fn set_state(&mut self, state: AiState) {
         self.state = state;
+        self.dwell = Self::MIN_DWELL;
+        self.path.clear();
+    }
+
+    /// Changes the state only if the current one has been kept for `MIN_DWELL` updates.
+    /// Returns `true` if the state was changed.
+    pub fn try_set_state(&mut self, state: AiState) -> bool {
+        if self.dwell > 0 {
+            return false;
+        }
+
+        self.set_state(state);
+        true
+    }
+
+    /// Counts down the dwell time of the current state, called once per update.
+    pub fn tick(&mut self) {
+        self.dwell = self.dwell.saturating_sub(1);
+    }
+
+    /// Obtains the next waypoint towards `to`, following the path planned on an earlier update.
+    /// A new path is only planned once the target moves to another cell, a cell along the path
+    /// becomes blocked, or no path was found last time. None if there is no path.
+    pub fn next_waypoint(&mut self, map: &WorldMap, from: Vec2f, to: Vec2f) -> Option<Vec2f> {
+        let goal = map.cell(to);
+        let stale = goal != self.path_goal
+            || self.path.is_empty()
+            || self.path.iter().any(|waypoint| map.is_blocked(*waypoint));
+
+        if stale {
+            self.path_goal = goal;
+            self.path = map.path(from, to).unwrap_or_default();
+            self.path.reverse();
+        } else {
+            // Same cell, the last waypoint follows the target within it.
+            self.path[0] = to;
+        }
+
+        while self.path.len() > 1
+            && self
+                .path
+                .last()
+                .is_some_and(|waypoint| waypoint.distance(from) < Self::WAYPOINT_REACHED)
+        {
+            self.path.pop();
+        }
+
+        self.path.last().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pursuer with a 5x5 map whose cells span `(0, 0)` to `(5, 5)`.
+    fn pursuer() -> (BasicAi, WorldMap) {
+        let mut ai = BasicAi::new();
+        ai.set_state(AiState::Pursue);
+        (ai, WorldMap::new(Vec2f(2.5, 2.5), 5.0, 5.0))
+    }
+
+    #[test]
+    fn path_is_kept_while_the_target_stays_in_its_cell() {
+        let (mut ai, map) = pursuer();
+        let from = Vec2f(0.5, 0.5);
+        assert_eq!(
+            ai.next_waypoint(&map, from, Vec2f(3.5, 0.5)),
+            Some(Vec2f(1.5, 0.5))
+        );
+
+        // A detour that planning would never produce shows the cached path is followed.
+        ai.path = vec![Vec2f(3.5, 0.5), Vec2f(0.5, 1.5)];
+        assert_eq!(
+            ai.next_waypoint(&map, from, Vec2f(3.2, 0.8)),
+            Some(Vec2f(0.5, 1.5))
+        );
+        assert_eq!(
+            ai.path[0],
+            Vec2f(3.2, 0.8),
+            "target was not followed within its cell"
+        );
+
+        // Moving to another cell plans again.
+        assert_eq!(
+            ai.next_waypoint(&map, from, Vec2f(0.5, 3.5)),
+            Some(Vec2f(0.5, 1.5))
+        );
+        assert_eq!(ai.path.len(), 3);
+    }
+
+    #[test]
+    fn blocked_path_is_planned_again() {
+        let (mut ai, mut map) = pursuer();
+        let (from, to) = (Vec2f(0.5, 0.5), Vec2f(2.5, 0.5));
+        assert_eq!(ai.next_waypoint(&map, from, to), Some(Vec2f(1.5, 0.5)));
+
+        map.set_blocked(Vec2f(1.5, 0.5), true);
+        assert_eq!(ai.next_waypoint(&map, from, to), Some(Vec2f(0.5, 1.5)));
+    }
+
+    #[test]
+    fn reached_waypoints_are_dropped() {
+        let (mut ai, map) = pursuer();
+        let to = Vec2f(2.5, 0.5);
+        assert_eq!(
+            ai.next_waypoint(&map, Vec2f(0.5, 0.5), to),
+            Some(Vec2f(1.5, 0.5))
+        );
+        assert_eq!(ai.next_waypoint(&map, Vec2f(1.5, 0.5), to), Some(to));
+        assert_eq!(ai.next_waypoint(&map, to, to), Some(to));
     }
 }
@@ -1,28 +1,43 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crate::error::AppError;
-use crate::net::{Packet, PacketLabel, Socket};
+use crate::net::traits::Versioned;
+use crate::net::{ClientId, Packet, PacketLabel, Socket};
 use crate::server::ai::AiState;
 use crate::shared::payload::{
-    Connect, Movement, PayloadId, Position as PositionPayload, ServerState,
+    Connect, Despawn, Movement, MovementInput, PayloadId, ServerState, SnapshotAck,
 };
-use crate::shared::shape::Rectangle;
+use crate::shared::shape::{Circle, Rectangle};
+use crate::shared::snapshot::SnapshotState;
 use crate::shared::transform::Transform;
-use crate::utils::{SpatialHash, Timestep, decode};
+use crate::utils::log::LogLevel;
+use crate::utils::{Rng, SpatialHash, Timestep, decode};
 use crate::vec2f::Vec2f;
+use crate::{debugln, logln};
 
 use super::ClientEntityMap;
 use super::ai::BasicAi;
 use super::ecs::{Entity, World};
+use super::history::PositionHistory;
+use super::link::NetLink;
+use super::snapshot::SnapshotTracker;
 use super::socket::ServerSocket;
 use super::spawner::{Owner, Spawner};
-use super::sys;
+use super::sys::{self, ArrivedEvent, CollisionEvent};
 use super::world_map::WorldMap;
 
 struct Name(pub String);
 pub(crate) struct LastTarget(pub Option<Entity>);
 
+/// Input sequences from the client controlling an entity. Only inputs whose movement was fully
+/// applied are echoed back, so the client keeps replaying the rest.
+pub(crate) struct InputSeq {
+    received: u32, // Newest input received.
+    applied: u32,  // Newest input whose movement was fully applied.
+}
+
 pub(crate) struct Slime;
 impl Slime {
     pub fn spawn(world: &mut World, pos: Vec2f) -> Entity {
@@ -40,42 +55,53 @@ impl Slime {
 
 /// Core of the server loop.
 pub struct ServerCore {
-    socket: ServerSocket,            // Socket for network communication.
+    socket: NetLink, // Link to the network, direct or on a separate thread.
     sigint: Option<Arc<AtomicBool>>, // Optional signal interrupt handler.
+    catch_up: bool,  // Skips sending snapshots while ticks are behind schedule.
+    skipped: u32,    // Snapshots skipped in a row while catching up.
+    world: World,    // Entities and components of the simulation.
+    world_map: WorldMap, // Map the entities move within.
+    gps: SpatialHash, // Spatial hash for tracking entity positions and detecting collisions.
+    client_entity: ClientEntityMap, // Allows for bi-directional mapping between clients and entities.
+    snapshots: SnapshotTracker,     // Snapshots sent to each client, so only changes are sent.
+    history: PositionHistory,       // Recent positions of every entity, for lag compensation.
+    slime: Entity,                  // Test entity that pursues the newest player.
 }
 
 impl ServerCore {
+    /// Ticks of entity positions retained for lag compensation.
+    const HISTORY_TICKS: usize = 64;
+    /// Furthest an entity can be from a client's entity and still be sent to the client.
+    const INTEREST_RANGE: f32 = 12.0;
+    /// Most snapshots skipped in a row while catching up, so a server that stays behind still
+    /// sends updates.
+    const MAX_SKIPPED_SNAPSHOTS: u32 = 3;
+
     /// Creates a new `ServerCore` instance with the given socket and optional signal interrupt handler.
     pub fn new(socket: Socket, sigint: Option<Arc<AtomicBool>>) -> Self {
-        Self {
-            socket: ServerSocket::new(socket),
-            sigint,
-        }
+        Self::with_link(NetLink::direct(ServerSocket::new(socket)), sigint)
     }
 
-    /// Runs the main server loop. Processes incoming packets and updates the game state.
-    pub fn run(&mut self, ticks_per_second: u16) -> Result<(), AppError> {
-        let mut step = Timestep::new(f32::from(ticks_per_second));
-
-        // Allows for bi-directional mapping between clients and entities.
-        let mut client_entity = ClientEntityMap::new();
+    /// Creates a new `ServerCore` instance that processes the network on a separate thread, so a
+    /// slow simulation tick does not delay packet processing and network jitter does not stall the simulation.
+    pub fn new_threaded(socket: Socket, sigint: Option<Arc<AtomicBool>>) -> Self {
+        Self::with_link(NetLink::threaded(ServerSocket::new(socket)), sigint)
+    }
 
-        // Spatial hash for tracking entitiy positions and detecting collisions.
+    /// Creates a new `ServerCore` instance over a network link, with the world populated.
+    fn with_link(socket: NetLink, sigint: Option<Arc<AtomicBool>>) -> Self {
+        let mut world = Self::create_world();
+        let mut world_map = WorldMap::new(Vec2f(10.0, 10.0), 18.0, 18.0);
+        for offset in [
+            Vec2f(-3.0, -3.0),
+            Vec2f(3.0, -3.0),
+            Vec2f(-3.0, 3.0),
+            Vec2f(3.0, 3.0),
+        ] {
+            world_map.add_spawn_point(*world_map.spawn_point() + offset);
+        }
         let mut gps = SpatialHash::new(1.0);
 
-        // Create a new world instance to manage entities and components.
-        let mut world = World::new();
-        world.register_component::<Transform>();
-        world.register_component::<Rectangle>();
-        world.register_component::<Movement>();
-        world.register_component::<Owner>();
-        world.register_component::<BasicAi>();
-        world.register_component::<LastTarget>();
-        world.register_component::<Name>();
-        world.register_component::<Spawner>();
-
-        let world_map = WorldMap::new(Vec2f(10.0, 10.0), 18.0, 18.0);
-
         // Create a spawner to generate test entities.
         // world
         //     .spawn_entity()
@@ -86,97 +112,498 @@ impl ServerCore {
         let slime = Slime::spawn(&mut world, *world_map.spawn_point() + Vec2f(10.0, 10.0));
         gps.insert(*world_map.spawn_point(), slime.into());
 
+        Self {
+            socket,
+            sigint,
+            catch_up: true,
+            skipped: 0,
+            world,
+            world_map,
+            gps,
+            client_entity: ClientEntityMap::new(),
+            snapshots: SnapshotTracker::default(),
+            history: PositionHistory::new(Self::HISTORY_TICKS),
+            slime,
+        }
+    }
+
+    /// Sets whether snapshots are skipped while ticks are more than a tick behind schedule,
+    /// giving the simulation the time to catch up. Enabled by default.
+    pub fn set_catch_up(&mut self, enabled: bool) {
+        self.catch_up = enabled;
+    }
+
+    /// Removes a client from the server, such as a cheater, optionally blacklisting it for a
+    /// duration. Its entity is removed when the disconnect is received on the next step. Banning a
+    /// client that is not connected fails, as there is no address to blacklist.
+    pub fn kick(&mut self, client: ClientId, ban: Option<Duration>) -> Result<(), AppError> {
+        match ban {
+            Some(duration) => self.socket.kick_and_blacklist(client, duration),
+            None => self.socket.kick(client, true),
+        }
+    }
+
+    /// Obtains where an entity was `ticks_ago` ticks before the latest step, such as to check a
+    /// hit against what a client saw. None if the tick is no longer retained or the entity did
+    /// not exist at the time.
+    pub fn position_at(&self, entity: Entity, ticks_ago: usize) -> Option<Vec2f> {
+        self.history.position_at(entity, ticks_ago)
+    }
+
+    /// Obtains the world being simulated.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Obtains the world being simulated, mutably.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Runs the main server loop. Processes incoming packets and updates the game state.
+    pub fn run(&mut self, ticks_per_second: u16) -> Result<(), AppError> {
+        let mut step = Timestep::new(f32::from(ticks_per_second));
+
         'core_loop: loop {
             // Ensure a kill command has not been sent.
             if let Some(sigint) = &self.sigint {
                 if sigint.load(Ordering::Relaxed) {
+                    self.socket.shutdown()?;
                     break 'core_loop;
                 }
             }
 
             // Send the server state to all clients at the specified tick rate.
             if step.tick() % u64::from(ticks_per_second) == 0 {
-                for client in client_entity.iter_clients() {
-                    // Send the server state to the client.
-                    let mut to_send = Packet::new(
-                        PacketLabel::Extension(u8::from(PayloadId::State)),
-                        self.socket.id(),
-                    );
-
-                    to_send.set_payload(ServerState {
+                debugln!("Systems: {}", Self::format_profile(&self.world));
+                self.world.reset_profile();
+
+                let to_send = Packet::with_payload(
+                    PacketLabel::Extension(u8::from(PayloadId::State)),
+                    self.socket.id(),
+                    ServerState {
                         tps: ticks_per_second,
                         tick_id: step.tick(),
-                    });
+                    },
+                );
+
+                self.socket.broadcast(&to_send)?;
+            }
+
+            self.step(step.fixed_dt())?;
+
+            // Send new positions to the clients, unless catching up on an overrun. Snapshots
+            // always carry the latest state, so a skipped one is covered by the next.
+            if self.skip_snapshots(step.is_behind_by(1)) {
+                debugln!(
+                    "Tick {} is {:.1}ms behind, skipping snapshots.",
+                    step.tick(),
+                    step.lag().as_secs_f64() * 1000.0
+                );
+            } else {
+                self.broadcast_snapshots()?;
+            }
+
+            step.wait();
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the snapshots of this tick are skipped to catch up, which requires catching up
+    /// to be enabled and the ticks to be `behind` by more than a tick. At most
+    /// `MAX_SKIPPED_SNAPSHOTS` are skipped in a row.
+    fn skip_snapshots(&mut self, behind: bool) -> bool {
+        if self.catch_up && behind && self.skipped < Self::MAX_SKIPPED_SNAPSHOTS {
+            self.skipped += 1;
+            true
+        } else {
+            self.skipped = 0;
+            false
+        }
+    }
+
+    /// Advances the simulation by a single tick of `dt` seconds. Processes the incoming packets,
+    /// runs the systems, then records the positions for `position_at`. Does not wait for the
+    /// next tick or send snapshots, the caller drives both, such as with `broadcast_snapshots`.
+    pub fn step(&mut self, dt: f32) -> Result<(), AppError> {
+        // Process all incoming packets.
+        let packets = self.socket.run_step()?;
+        for packet in packets {
+            self.handle_packet(&packet)?;
+        }
 
-                    self.socket.send(*client, to_send)?;
+        // Trigger a run on the systems.
+        let (world_map, gps) = (&self.world_map, &mut self.gps);
+        self.world
+            .time_system("ai", |world| sys::ai(world, world_map));
+        let mut changes = self
+            .world
+            .time_system("movement", |world| sys::movement(world, world_map, gps, dt));
+        changes.extend(
+            self.world
+                .time_system("spawn", |world| sys::spawn(world, world_map)),
+        );
+
+        // Inputs are acknowledged once the movement they set has been travelled in full.
+        self.world
+            .fetch_components(|_, movement: &Movement, seq: &mut InputSeq| {
+                if movement.0 == Vec2f::ZERO {
+                    seq.applied = seq.received;
+                }
+            });
+
+        // Consume the events so they do not accumulate between ticks.
+        for ArrivedEvent(entity) in self.world.drain_events() {
+            debugln!("Entity {} arrived at its destination.", u32::from(entity));
+        }
+
+        for CollisionEvent { a, b } in self.world.drain_events() {
+            debugln!("Entity {} collided with {}.", u32::from(a), u32::from(b));
+        }
+
+        self.history.record(&self.world);
+
+        Ok(())
+    }
+
+    /// Handles a packet received from a client.
+    fn handle_packet(&mut self, packet: &Packet) -> Result<(), AppError> {
+        match packet.label() {
+            PacketLabel::Connect => {
+                logln!(LogLevel::Info, "Client connected: {}", packet.source());
+
+                // Spawn a new entity for the client.
+                let spawn_point = self.world_map.next_spawn_point();
+                let entity = self.world.spawn_entity().build();
+                self.world
+                    .attach_component(entity, Rectangle::new(1.0, 1.0));
+                self.world
+                    .attach_component(entity, Transform::with_position(spawn_point));
+                self.client_entity.add(packet.source(), entity);
+
+                // Make the slime follow the player.
+                if let Some(mut ai) = self.world.fetch_component::<&mut BasicAi>(self.slime) {
+                    self.world
+                        .attach_component(self.slime, LastTarget(Some(entity)));
+                    ai.set_state(AiState::Pursue);
                 }
+
+                // Send initial position to the client.
+                let to_send = Packet::with_payload(
+                    PacketLabel::Extension(u8::from(PayloadId::Connect)),
+                    packet.source(),
+                    Connect(u32::from(entity), spawn_point),
+                );
+                self.socket.send(packet.source(), to_send)?;
             }
 
-            // Process all incoming packets.
-            let packets = self.socket.run_step()?;
-            for packet in packets {
-                match packet.label() {
-                    PacketLabel::Connect => {
-                        println!("Client connected: {}", packet.source());
-
-                        // Spawn a new entity for the client.
-                        let entity = world.spawn_entity().build();
-                        world.attach_component(entity, Rectangle::new(1.0, 1.0));
-                        world.attach_component(
+            PacketLabel::Disconnect => {
+                let Some(entity) = self.client_entity.get_entity(packet.source()) else {
+                    return Ok(());
+                };
+
+                // Remove the client's entity, the remaining clients are told on the next snapshot.
+                self.client_entity.remove(packet.source());
+                self.snapshots.remove(packet.source());
+                self.world.kill_entity(entity);
+                self.gps.remove(entity.into());
+            }
+
+            PacketLabel::Extension(id) if id == u8::from(PayloadId::Movement) => {
+                let Versioned(MovementInput(movement, input_seq)) =
+                    decode::<Versioned<MovementInput>>(packet)?;
+                if let Some(entity) = self.client_entity.get_entity(packet.source()) {
+                    self.world.attach_component(entity, movement);
+                    match self.world.fetch_component::<&mut InputSeq>(entity) {
+                        Some(mut seq) => seq.received = input_seq,
+                        None => self.world.attach_component(
                             entity,
-                            Transform::with_position(*world_map.spawn_point()),
-                        );
-                        client_entity.add(packet.source(), entity);
-
-                        // Make the slime follow the player.
-                        if let Some(mut ai) = world.fetch_component::<&mut BasicAi>(slime) {
-                            world.attach_component(slime, LastTarget(Some(entity)));
-                            ai.set_state(AiState::Pursue);
-                        }
-
-                        // Send initial position to the client.
-                        let mut to_send = Packet::new(
-                            PacketLabel::Extension(u8::from(PayloadId::Connect)),
-                            packet.source(),
-                        );
-                        to_send.set_payload(Connect(u32::from(entity), *world_map.spawn_point()));
-                        self.socket.send(packet.source(), to_send)?;
+                            InputSeq {
+                                received: input_seq,
+                                applied: 0,
+                            },
+                        ),
                     }
+                }
+            }
 
-                    PacketLabel::Extension(id) if id == u8::from(PayloadId::Movement) => {
-                        let payload = decode::<Movement>(&packet)?;
-                        if let Some(entity) = client_entity.get_entity(packet.source()) {
-                            world.attach_component(entity, payload);
-                        }
-                    }
+            PacketLabel::Extension(id) if id == u8::from(PayloadId::SnapshotAck) => {
+                let SnapshotAck(snapshot) = decode::<SnapshotAck>(packet)?;
+                self.snapshots.acknowledge(packet.source(), snapshot);
+            }
 
-                    _ => {}
-                }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Creates a world with every component and resource used by the systems registered.
+    pub(crate) fn create_world() -> World {
+        let mut world = World::new();
+        world.register_component::<Transform>();
+        world.register_component::<Rectangle>();
+        world.register_component::<Circle>();
+        world.register_component::<Movement>();
+        world.register_component::<Owner>();
+        world.register_component::<BasicAi>();
+        world.register_component::<LastTarget>();
+        world.register_component::<InputSeq>();
+        world.register_component::<Name>();
+        world.register_component::<Spawner>();
+        world.register_resource(Rng::new(rand::random()));
+        world
+    }
+
+    /// Formats the per-system execution times, such as "movement: 3.2ms, ai: 0.4ms".
+    #[allow(dead_code)] // Only used by `debugln!`, which is compiled out of release builds.
+    fn format_profile(world: &World) -> String {
+        world
+            .profile()
+            .iter()
+            .map(|(name, total)| format!("{name}: {:.1}ms", total.as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Sends each client a snapshot of the moving entities within its interest range that changed
+    /// since the last snapshot it acknowledged. Entities the client was sent that have since been
+    /// removed or left its range are despawned first. Stops on the first send failure.
+    pub fn broadcast_snapshots(&mut self) -> Result<(), AppError> {
+        let label = PacketLabel::Extension(u8::from(PayloadId::Snapshot));
+        let despawn = PacketLabel::Extension(u8::from(PayloadId::Despawn));
+
+        // Collect the state first so no component set is borrowed while sending.
+        let world = &self.world;
+        let mut current = SnapshotState::new();
+        world.fetch_components(|entity, transform: &Transform| {
+            if let Some(movement) = world.fetch_component::<&Movement>(entity) {
+                current.insert(u32::from(entity), (transform.position, movement.0));
             }
+        });
 
-            // Trigger a run on the systems.
-            let label = PacketLabel::Extension(u8::from(PayloadId::Position));
-            sys::ai(&mut world);
-            let mut changes = sys::movement(&mut world, &world_map, &mut gps, step.fixed_dt());
-            changes.extend(sys::spawn(&mut world, &world_map));
-
-            // Send new positions to the clients.
-            world.fetch_components(|entity, transform: &Transform, movement: &Movement| {
-                for client in client_entity.iter_clients() {
-                    // Send the updated position to all clients.
-                    let mut to_send = Packet::new(label, self.socket.id());
-                    to_send.set_payload(PositionPayload(
-                        u32::from(entity),
-                        transform.position,
-                        movement.0,
-                    ));
-                    self.socket.send(*client, to_send).unwrap();
-                }
-            });
+        for client in self.client_entity.iter_clients() {
+            let entity = self.client_entity.get_entity(*client);
+            let visible = Self::interest(world, entity, &current);
 
-            step.wait();
+            for gone in self.snapshots.despawned(*client, &visible) {
+                let to_send = Packet::with_payload(despawn, self.socket.id(), Despawn(gone));
+                self.socket.send(*client, to_send)?;
+            }
+
+            // Echo the client's last applied input, so it can replay the inputs after it.
+            let input_seq = entity
+                .and_then(|entity| world.fetch_component::<&InputSeq>(entity))
+                .map_or(0, |seq| seq.applied);
+
+            let Some(snapshot) = self.snapshots.snapshot(*client, &visible, input_seq) else {
+                continue; // Nothing changed since the last snapshot.
+            };
+
+            let to_send = Packet::with_payload(label, self.socket.id(), snapshot);
+            self.socket.send(*client, to_send)?;
         }
 
         Ok(())
     }
+
+    /// Obtains the part of `current` within `INTEREST_RANGE` of the client's entity. Everything
+    /// is of interest to a client without a positioned entity.
+    fn interest(world: &World, entity: Option<Entity>, current: &SnapshotState) -> SnapshotState {
+        let Some(center) = entity
+            .and_then(|entity| world.fetch_component::<&Transform>(entity))
+            .map(|transform| transform.position)
+        else {
+            return current.clone();
+        };
+
+        let range = Self::INTEREST_RANGE * Self::INTEREST_RANGE;
+        current
+            .iter()
+            .filter(|(_, (position, _))| position.distance_squared(center) <= range)
+            .map(|(entity, state)| (*entity, *state))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use crate::net::builtins::ConnectionPayload;
+    use crate::net::{Deliverable, LocalHub};
+    use crate::shared::snapshot::Snapshot;
+    use crate::utils::log;
+
+    use super::*;
+
+    /// Seconds simulated by each step of the tests.
+    const DT: f32 = 0.05;
+
+    /// Creates a server over a local hub that clients can be joined through.
+    fn local_server() -> (ServerCore, LocalHub) {
+        let (hub, socket) = Socket::new_local_hub().unwrap();
+        (ServerCore::new(socket, None), hub)
+    }
+
+    /// Creates a new client and asks the server of the hub to connect it.
+    fn request_connect(hub: &mut LocalHub) -> Socket {
+        let mut client = Socket::new_local_client(hub).unwrap();
+        let payload = ConnectionPayload(
+            Packet::CURRENT_VERSION,
+            client.id(),
+            5000,
+            0,
+            Packet::MIN_VERSION,
+        );
+        let request = Packet::with_payload(PacketLabel::Connect, client.id(), payload);
+        client.send(Deliverable::new(ClientId(0), request)).unwrap();
+        client
+    }
+
+    /// Connects a new client and steps the server, returning the client and the entity it was
+    /// given.
+    fn join(server: &mut ServerCore, hub: &mut LocalHub) -> (Socket, Entity) {
+        let mut client = request_connect(hub);
+        server.step(DT).unwrap();
+
+        let connect = received(&mut client, PayloadId::Connect)
+            .pop()
+            .expect("entity was not assigned");
+        let Connect(entity, _) = decode::<Connect>(&connect).unwrap();
+        (client, Entity::from(entity))
+    }
+
+    /// Obtains the packets waiting for a client with the payload.
+    fn received(client: &mut Socket, id: PayloadId) -> Vec<Packet> {
+        let label = PacketLabel::Extension(u8::from(id));
+        let mut packets = vec![];
+        while let Some(packet) = client.try_recv().unwrap() {
+            if packet.label() == label {
+                packets.push(packet);
+            }
+        }
+
+        packets
+    }
+
+    #[test]
+    fn threaded_servers_connect_clients_and_stop_when_dropped() {
+        let (mut hub, socket) = Socket::new_local_hub().unwrap();
+        let mut server = ServerCore::new_threaded(socket, None);
+        let mut client = request_connect(&mut hub);
+
+        // The network thread hands the connect over on a later step.
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let connect = loop {
+            server.step(DT).unwrap();
+            if let Some(connect) = received(&mut client, PayloadId::Connect).pop() {
+                break connect;
+            }
+
+            assert!(Instant::now() < deadline, "entity was not assigned");
+            thread::sleep(Duration::from_millis(5));
+        };
+        let Connect(entity, _) = decode::<Connect>(&connect).unwrap();
+        assert!(server.world().is_alive(Entity::from(entity)));
+
+        // Dropping the server joins the network thread.
+        drop(server);
+    }
+
+    #[test]
+    fn connects_are_logged_at_info() {
+        let (mut server, mut hub) = local_server();
+        let ((client, _), logged) = log::capture(|| join(&mut server, &mut hub));
+
+        let expected = (LogLevel::Info, format!("Client connected: {}", client.id()));
+        assert!(logged.contains(&expected), "{logged:?}");
+    }
+
+    #[test]
+    fn step_moves_entities() {
+        let (mut server, mut hub) = local_server();
+        let (_client, entity) = join(&mut server, &mut hub);
+        let position = |server: &ServerCore| {
+            server
+                .world()
+                .fetch_component::<&Transform>(entity)
+                .map(|transform| transform.position)
+                .unwrap()
+        };
+
+        let start = position(&server);
+        server
+            .world_mut()
+            .attach_component(entity, Movement(Vec2f(2.0, 0.0), 1));
+        for _ in 0..3 {
+            server.step(DT).unwrap();
+        }
+
+        // Speed 1 covers a unit per second.
+        let moved = position(&server) - start;
+        assert!((moved.0 - 3.0 * DT).abs() < 1e-5, "moved {moved:?}");
+        assert!(moved.1.abs() < f32::EPSILON, "moved {moved:?}");
+    }
+
+    #[test]
+    fn inputs_are_acknowledged_once_their_movement_is_applied() {
+        let (mut server, mut hub) = local_server();
+        let (mut client, _) = join(&mut server, &mut hub);
+
+        // Takes two steps to travel at speed 1.
+        let input = Versioned(MovementInput(Movement(Vec2f(1.5 * DT, 0.0), 1), 5));
+        let label = PacketLabel::Extension(u8::from(PayloadId::Movement));
+        let to_send = Packet::with_payload(label, client.id(), input);
+        client.send(Deliverable::new(ClientId(0), to_send)).unwrap();
+
+        let mut acked = vec![];
+        for _ in 0..2 {
+            server.step(DT).unwrap();
+            server.broadcast_snapshots().unwrap();
+            let snapshot = received(&mut client, PayloadId::Snapshot).pop().unwrap();
+            acked.push(decode::<Snapshot>(&snapshot).unwrap().input_seq);
+        }
+
+        assert_eq!(acked, [0, 5]);
+    }
+
+    #[test]
+    fn dropped_client_is_despawned_for_the_others() {
+        let (mut server, mut hub) = local_server();
+        let (mut stays, _) = join(&mut server, &mut hub);
+        let (leaves, entity) = join(&mut server, &mut hub);
+
+        // Give the entity movement so it is sent in snapshots.
+        server
+            .world_mut()
+            .attach_component(entity, Movement(Vec2f::ZERO, 1));
+        server.broadcast_snapshots().unwrap();
+        assert_eq!(received(&mut stays, PayloadId::Snapshot).len(), 1);
+
+        // The server drops the client, the removal is handled on the next step.
+        server.kick(leaves.id(), None).unwrap();
+        server.step(DT).unwrap();
+        assert!(!server.world().is_alive(entity));
+
+        server.broadcast_snapshots().unwrap();
+        let despawns = received(&mut stays, PayloadId::Despawn);
+        assert_eq!(despawns.len(), 1);
+        let Despawn(gone) = decode::<Despawn>(&despawns[0]).unwrap();
+        assert_eq!(gone, u32::from(entity));
+    }
+
+    #[test]
+    fn snapshots_are_not_skipped_for_long() {
+        let (mut server, _hub) = local_server();
+        let skipped: Vec<bool> = (0..5).map(|_| server.skip_snapshots(true)).collect();
+        assert_eq!(skipped, [true, true, true, false, true]);
+
+        // Catching up resets the count, and nothing is skipped when disabled.
+        assert!(!server.skip_snapshots(false));
+        server.set_catch_up(false);
+        assert!(!server.skip_snapshots(true));
+    }
 }
@@ -12,6 +12,13 @@ pub(crate) trait ComponentRef<'a> {
 
     /// Fetch a component from the world for the given entity.
     fn fetch(world: &'a World, entity: Entity) -> Option<Self::Output>;
+
+    /// Fetch a component from the world for the given entity. Returns None instead of panicking
+    /// if the component set is already borrowed in a conflicting way.
+    fn try_fetch(world: &'a World, entity: Entity) -> Option<Self::Output>;
+
+    /// Collects the entities that have the component.
+    fn entities(world: &'a World) -> Vec<Entity>;
 }
 
 impl<'a, C: 'static> ComponentRef<'a> for &'a C {
@@ -21,6 +28,17 @@ impl<'a, C: 'static> ComponentRef<'a> for &'a C {
         let guard = world.components.get::<C>()?;
         Ref::filter_map(guard, |set| set.get(entity.into())).ok()
     }
+
+    fn try_fetch(world: &'a World, entity: Entity) -> Option<Self::Output> {
+        let guard = world.components.try_get::<C>()?;
+        Ref::filter_map(guard, |set| set.get(entity.into())).ok()
+    }
+
+    fn entities(world: &'a World) -> Vec<Entity> {
+        world.components.get::<C>().map_or_else(Vec::new, |set| {
+            set.iter().map(|(e, _)| Entity::from(e)).collect()
+        })
+    }
 }
 
 impl<'a, C: 'static> ComponentRef<'a> for &'a mut C {
@@ -30,8 +48,24 @@ impl<'a, C: 'static> ComponentRef<'a> for &'a mut C {
         let guard = world.components.get_mut::<C>()?;
         RefMut::filter_map(guard, |set| set.get_mut(entity.into())).ok()
     }
+
+    fn try_fetch(world: &'a World, entity: Entity) -> Option<Self::Output> {
+        let guard = world.components.try_get_mut::<C>()?;
+        RefMut::filter_map(guard, |set| set.get_mut(entity.into())).ok()
+    }
+
+    fn entities(world: &'a World) -> Vec<Entity> {
+        world.components.get::<C>().map_or_else(Vec::new, |set| {
+            set.iter().map(|(e, _)| Entity::from(e)).collect()
+        })
+    }
 }
 
+/// Component references that only read, so any number of them can be held at once.
+pub(crate) trait ReadOnly {}
+
+impl<C> ReadOnly for &C {}
+
 /// Accesses the underlying sparse set for a component type.
 pub(crate) trait SetAccess {
     type Output<'b>; // Output type for the component set.
@@ -48,6 +82,9 @@ pub(crate) trait SetAccess {
 
     /// Returns the length of the sparse set.
     fn length(iter: &'_ Self::Guard<'_>) -> usize;
+
+    /// Collects the entities that have a component in the sparse set.
+    fn entities(iter: &'_ Self::Guard<'_>) -> Vec<Entity>;
 }
 
 impl<C: 'static> SetAccess for &C {
@@ -69,6 +106,10 @@ impl<C: 'static> SetAccess for &C {
     fn length(iter: &'_ Self::Guard<'_>) -> usize {
         iter.length()
     }
+
+    fn entities(iter: &'_ Self::Guard<'_>) -> Vec<Entity> {
+        iter.iter().map(|(e, _)| Entity::from(e)).collect()
+    }
 }
 
 impl<C: 'static> SetAccess for &mut C {
@@ -90,6 +131,10 @@ impl<C: 'static> SetAccess for &mut C {
     fn length(iter: &'_ Self::Guard<'_>) -> usize {
         iter.length()
     }
+
+    fn entities(iter: &'_ Self::Guard<'_>) -> Vec<Entity> {
+        iter.iter().map(|(e, _)| Entity::from(e)).collect()
+    }
 }
 
 /// A trait for managing sparse sets of components.
@@ -116,11 +161,23 @@ impl<C: 'static> Set for SparseSet<C> {
     }
 }
 
+/// Copies the component of one entity onto another within a type-erased set.
+type Cloner = fn(&mut dyn Set, Entity, Entity);
+
+/// Copies the component of `src` onto `dst` within a set of `C`, if `src` has one.
+fn clone_component<C: Clone + 'static>(set: &mut dyn Set, src: Entity, dst: Entity) {
+    let set = set.as_any_mut().downcast_mut::<SparseSet<C>>().unwrap();
+    if let Some(component) = set.get(src.into()).cloned() {
+        set.insert(dst.into(), component);
+    }
+}
+
 /// Storage for components in the world.
 #[derive(Default)]
 pub(crate) struct ComponentStorage {
     pub(crate) lookup: HashMap<TypeId, usize>, // Lookup table for component types.
     pub(crate) sets: Vec<Box<RefCell<dyn Set>>>, // Sets of components for entities.
+    cloners: HashMap<TypeId, Cloner>,          // Copy functions for cloneable component types.
 }
 
 impl ComponentStorage {
@@ -136,8 +193,16 @@ impl ComponentStorage {
             .push(Box::new(RefCell::new(SparseSet::<C>::new())));
     }
 
+    /// Creates a new set for the given component type, allowing its components to be copied
+    /// with `clone_entity`.
+    pub fn create_cloneable<C: Clone + 'static>(&mut self) {
+        self.create::<C>();
+        self.cloners.insert(TypeId::of::<C>(), clone_component::<C>);
+    }
+
     /// Destroys the set for the given component type where `C` is the component type.
     pub fn destroy<C: 'static>(&mut self) {
+        self.cloners.remove(&TypeId::of::<C>());
         if let Some(index) = self.lookup.remove(&TypeId::of::<C>()) {
             self.sets.remove(index);
             for v in self.lookup.values_mut() {
@@ -148,6 +213,15 @@ impl ComponentStorage {
         }
     }
 
+    /// Copies every cloneable component of `src` onto `dst`.
+    pub fn clone_entity(&self, src: Entity, dst: Entity) {
+        for (id, cloner) in &self.cloners {
+            if let Some(set) = self.lookup.get(id).and_then(|&idx| self.sets.get(idx)) {
+                cloner(&mut *set.borrow_mut(), src, dst);
+            }
+        }
+    }
+
     /// Removes an entity and its components from all sparse sets.
     pub fn remove_entity(&mut self, entity: Entity) {
         for set in &mut self.sets {
@@ -174,6 +248,30 @@ impl ComponentStorage {
         }))
     }
 
+    /// Obtains a readable reference to the sparse set for the given component type. Returns None
+    /// if the set is not registered or is already being accessed mutably.
+    pub fn try_get<C: 'static>(&self) -> Option<Ref<'_, SparseSet<C>>> {
+        let idx = *self.lookup.get(&TypeId::of::<C>())?;
+        let ref_set = self.sets.get(idx)?.try_borrow().ok()?;
+
+        // Downcast to the concrete type.
+        Some(Ref::map(ref_set, |set| {
+            set.as_any().downcast_ref::<SparseSet<C>>().unwrap()
+        }))
+    }
+
+    /// Obtains a mutable reference to the sparse set for the given component type. Returns None
+    /// if the set is not registered or is already being accessed.
+    pub fn try_get_mut<C: 'static>(&self) -> Option<RefMut<'_, SparseSet<C>>> {
+        let idx = *self.lookup.get(&TypeId::of::<C>())?;
+        let ref_set = self.sets.get(idx)?.try_borrow_mut().ok()?;
+
+        // Downcast to the concrete type.
+        Some(RefMut::map(ref_set, |set| {
+            set.as_any_mut().downcast_mut::<SparseSet<C>>().unwrap()
+        }))
+    }
+
     /// Obtains a mutable reference to the sparse set for the given component type where `C` is the component type.
     pub fn get_mut<C: 'static>(&self) -> Option<RefMut<SparseSet<C>>> {
         let idx = *self.lookup.get(&TypeId::of::<C>())?;
@@ -1,58 +1,89 @@
 use std::hash::Hash;
 use std::ops;
 
-/// Represents a unique entity identifier.
+/// Represents a unique entity identifier. The index identifies the slot of the entity and is
+/// reused after the entity is killed, while the generation distinguishes each reuse so that stale
+/// handles can be detected.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Entity(u32);
+pub struct Entity {
+    index: u32,      // Slot of the entity, used to store its components.
+    generation: u32, // Number of times the slot has been reused.
+}
+
+impl Entity {
+    /// Creates a new entity handle for the index and generation.
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation }
+    }
+
+    /// Slot of the entity, used to store its components.
+    #[inline]
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    /// Number of times the slot has been reused.
+    #[inline]
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
 
+/// Converts to the index of the entity, dropping the generation.
 impl From<Entity> for usize {
     fn from(value: Entity) -> Self {
-        value.0 as usize
+        value.index as usize
     }
 }
 
+/// Converts to the index of the entity, dropping the generation.
 impl From<Entity> for u32 {
     fn from(value: Entity) -> Self {
-        value.0
+        value.index
     }
 }
 
+/// Creates a handle for the first generation of the index. Use `World::resolve` to obtain the
+/// handle of the entity currently occupying the index.
 impl From<usize> for Entity {
     fn from(val: usize) -> Self {
         match u32::try_from(val) {
-            Ok(value) => Entity(value),
+            Ok(value) => Entity::new(value, 0),
             Err(err) => panic!("Value {val} exceeds u32::MAX: {err}"),
         }
     }
 }
 
+/// Creates a handle for the first generation of the index. Use `World::resolve` to obtain the
+/// handle of the entity currently occupying the index.
 impl From<u32> for Entity {
     fn from(val: u32) -> Self {
-        Entity(val)
+        Entity::new(val, 0)
     }
 }
 
 impl std::fmt::Display for Entity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Entity({})", self.0)
+        write!(f, "Entity({}v{})", self.index, self.generation)
     }
 }
 
 impl Hash for Entity {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.0.hash(state);
+        self.index.hash(state);
+        self.generation.hash(state);
     }
 }
 
 impl std::cmp::PartialOrd for Entity {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.0.cmp(&other.0))
+        Some(self.cmp(other))
     }
 }
 
 impl std::cmp::Ord for Entity {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0)
+        (self.index, self.generation).cmp(&(other.index, other.generation))
     }
 }
 
@@ -60,7 +91,7 @@ impl ops::Add<u32> for Entity {
     type Output = Self;
 
     fn add(self, rhs: u32) -> Self::Output {
-        Entity(self.0 + rhs)
+        Entity::new(self.index + rhs, self.generation)
     }
 }
 
@@ -68,18 +99,18 @@ impl ops::Sub<u32> for Entity {
     type Output = Self;
 
     fn sub(self, rhs: u32) -> Self::Output {
-        Entity(self.0 - rhs)
+        Entity::new(self.index - rhs, self.generation)
     }
 }
 
 impl ops::AddAssign<u32> for Entity {
     fn add_assign(&mut self, rhs: u32) {
-        self.0 += rhs;
+        self.index += rhs;
     }
 }
 
 impl ops::SubAssign<u32> for Entity {
     fn sub_assign(&mut self, rhs: u32) {
-        self.0 -= rhs;
+        self.index -= rhs;
     }
 }
@@ -0,0 +1,23 @@
+/// Queue of events of type `E`, stored as a world resource. Systems send events during a tick and
+/// consumers drain them afterwards.
+pub struct Events<E> {
+    queue: Vec<E>, // Events waiting to be consumed.
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self { queue: Vec::new() }
+    }
+}
+
+impl<E> Events<E> {
+    /// Adds an event to the queue.
+    pub fn send(&mut self, event: E) {
+        self.queue.push(event);
+    }
+
+    /// Removes and returns all queued events in the order they were sent.
+    pub fn drain(&mut self) -> Vec<E> {
+        std::mem::take(&mut self.queue)
+    }
+}
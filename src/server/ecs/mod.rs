@@ -1,5 +1,6 @@
 mod component;
 mod entity;
+mod event;
 mod query;
 mod resource;
 mod sset;
@@ -1,4 +1,4 @@
-use super::component::SetAccess;
+use super::component::{ComponentRef, ReadOnly, SetAccess};
 use super::entity::Entity;
 use super::world::World;
 
@@ -8,6 +8,55 @@ pub(crate) trait Query<P>: Sized {
     fn fetch(world: &World, f: Self);
 }
 
+/// Components that can be iterated over together with `World::iter`. Only implemented for shared
+/// references, as every item holds its borrows and a mutable one would conflict with the next.
+pub(crate) trait QueryIter<'a> {
+    type Item: 'a; // Entity followed by the fetched components.
+
+    /// Collects the entities that may match the query, taken from its smallest component set.
+    fn candidates(world: &'a World) -> Vec<Entity>;
+
+    /// Fetches the components of the query for the entity. None if any are missing.
+    fn fetch(world: &'a World, entity: Entity) -> Option<Self::Item>;
+}
+
+/// Single component iteration.
+impl<'a, A: ComponentRef<'a> + ReadOnly> QueryIter<'a> for (A,) {
+    type Item = (Entity, A::Output);
+
+    fn candidates(world: &'a World) -> Vec<Entity> {
+        A::entities(world)
+    }
+
+    fn fetch(world: &'a World, entity: Entity) -> Option<Self::Item> {
+        Some((world.resolve(entity), A::fetch(world, entity)?))
+    }
+}
+
+/// Two component iteration.
+impl<'a, A, B> QueryIter<'a> for (A, B)
+where
+    A: ComponentRef<'a> + ReadOnly,
+    B: ComponentRef<'a> + ReadOnly,
+{
+    type Item = (Entity, A::Output, B::Output);
+
+    fn candidates(world: &'a World) -> Vec<Entity> {
+        let (set_a, set_b) = (A::entities(world), B::entities(world));
+        if set_a.len() <= set_b.len() {
+            set_a
+        } else {
+            set_b
+        }
+    }
+
+    fn fetch(world: &'a World, entity: Entity) -> Option<Self::Item> {
+        let first = A::fetch(world, entity)?;
+        let second = B::fetch(world, entity)?;
+        Some((world.resolve(entity), first, second))
+    }
+}
+
 /// Single component query.
 impl<T, F> Query<(T,)> for F
 where
@@ -17,7 +66,7 @@ where
     fn fetch(world: &World, mut f: F) {
         if let Some(mut storage) = T::set(world) {
             for (entity, component) in T::iter(&mut storage) {
-                f(entity, component);
+                f(world.resolve(entity), component);
             }
         }
     }
@@ -42,13 +91,13 @@ where
         if len_t <= len_u {
             for (entity, comp_t) in T::iter(&mut set_t) {
                 if let Some(comp_u) = U::component(&mut set_u, entity) {
-                    f(entity, comp_t, comp_u);
+                    f(world.resolve(entity), comp_t, comp_u);
                 }
             }
         } else {
             for (entity, comp_u) in U::iter(&mut set_u) {
                 if let Some(comp_t) = T::component(&mut set_t, entity) {
-                    f(entity, comp_t, comp_u);
+                    f(world.resolve(entity), comp_t, comp_u);
                 }
             }
         }
@@ -79,7 +128,7 @@ where
             for (entity, comp_t) in T::iter(&mut set_t) {
                 if let Some(comp_u) = U::component(&mut set_u, entity) {
                     if let Some(comp_v) = V::component(&mut set_v, entity) {
-                        f(entity, comp_t, comp_u, comp_v);
+                        f(world.resolve(entity), comp_t, comp_u, comp_v);
                     }
                 }
             }
@@ -87,7 +136,7 @@ where
             for (entity, comp_u) in U::iter(&mut set_u) {
                 if let Some(comp_t) = T::component(&mut set_t, entity) {
                     if let Some(comp_v) = V::component(&mut set_v, entity) {
-                        f(entity, comp_t, comp_u, comp_v);
+                        f(world.resolve(entity), comp_t, comp_u, comp_v);
                     }
                 }
             }
@@ -95,7 +144,7 @@ where
             for (entity, comp_v) in V::iter(&mut set_v) {
                 if let Some(comp_t) = T::component(&mut set_t, entity) {
                     if let Some(comp_u) = U::component(&mut set_u, entity) {
-                        f(entity, comp_t, comp_u, comp_v);
+                        f(world.resolve(entity), comp_t, comp_u, comp_v);
                     }
                 }
             }
@@ -131,7 +180,7 @@ where
                 if let Some(comp_u) = U::component(&mut set_u, entity) {
                     if let Some(comp_v) = V::component(&mut set_v, entity) {
                         if let Some(comp_w) = W::component(&mut set_w, entity) {
-                            f(entity, comp_t, comp_u, comp_v, comp_w);
+                            f(world.resolve(entity), comp_t, comp_u, comp_v, comp_w);
                         }
                     }
                 }
@@ -141,7 +190,7 @@ where
                 if let Some(comp_t) = T::component(&mut set_t, entity) {
                     if let Some(comp_v) = V::component(&mut set_v, entity) {
                         if let Some(comp_w) = W::component(&mut set_w, entity) {
-                            f(entity, comp_t, comp_u, comp_v, comp_w);
+                            f(world.resolve(entity), comp_t, comp_u, comp_v, comp_w);
                         }
                     }
                 }
@@ -151,7 +200,7 @@ where
                 if let Some(comp_t) = T::component(&mut set_t, entity) {
                     if let Some(comp_u) = U::component(&mut set_u, entity) {
                         if let Some(comp_w) = W::component(&mut set_w, entity) {
-                            f(entity, comp_t, comp_u, comp_v, comp_w);
+                            f(world.resolve(entity), comp_t, comp_u, comp_v, comp_w);
                         }
                     }
                 }
@@ -161,7 +210,7 @@ where
                 if let Some(comp_t) = T::component(&mut set_t, entity) {
                     if let Some(comp_u) = U::component(&mut set_u, entity) {
                         if let Some(comp_v) = V::component(&mut set_v, entity) {
-                            f(entity, comp_t, comp_u, comp_v, comp_w);
+                            f(world.resolve(entity), comp_t, comp_u, comp_v, comp_w);
                         }
                     }
                 }
@@ -169,3 +218,106 @@ where
         }
     }
 }
+
+/// Implements `Query` for larger tuples. Rather than a branch per candidate set, the entities of
+/// the smallest set are collected and every set is probed for each of them.
+macro_rules! impl_query {
+    ($doc:literal; $($t:ident $set:ident $comp:ident),+) => {
+        #[doc = $doc]
+        impl<$($t,)+ F> Query<($($t,)+)> for F
+        where
+            $($t: SetAccess,)+
+            F: FnMut(Entity, $($t::Output<'_>),+) + FnMut(Entity, $($t),+),
+        {
+            fn fetch(world: &World, mut f: F) {
+                let ($(Some(mut $set),)+) = ($($t::set(world),)+) else {
+                    return;
+                };
+
+                // Iterate over the smallest set to optimize performance.
+                let smallest = [$($t::length(&$set)),+].into_iter().min().unwrap_or(0);
+                let mut entities = None;
+                $(
+                    if entities.is_none() && $t::length(&$set) == smallest {
+                        entities = Some($t::entities(&$set));
+                    }
+                )+
+
+                for entity in entities.unwrap_or_default() {
+                    if let ($(Some($comp),)+) = ($($t::component(&mut $set, entity),)+) {
+                        f(world.resolve(entity), $($comp),+);
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl_query!("Five component query."; T set_t comp_t, U set_u comp_u, V set_v comp_v, W set_w comp_w, X set_x comp_x);
+impl_query!("Six component query."; T set_t comp_t, U set_u comp_u, V set_v comp_v, W set_w comp_w, X set_x comp_x, Y set_y comp_y);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct A(u32);
+    struct B;
+    struct C;
+    struct D;
+    struct E;
+    struct F;
+
+    /// World with six component types and entities holding different subsets of them. Returns
+    /// the entities that have the first five, and those that have all six.
+    fn world() -> (World, Vec<Entity>, Vec<Entity>) {
+        let mut world = World::new();
+        world.register_component::<A>();
+        world.register_component::<B>();
+        world.register_component::<C>();
+        world.register_component::<D>();
+        world.register_component::<E>();
+        world.register_component::<F>();
+
+        let (mut five, mut six) = (vec![], vec![]);
+        for idx in 0..12u32 {
+            let entity = world.spawn_entity().attach(A(idx)).attach(B).build();
+            if idx % 2 == 0 {
+                world.attach_component(entity, C);
+                world.attach_component(entity, D);
+                world.attach_component(entity, E);
+                five.push(entity);
+            }
+            if idx % 4 == 0 {
+                world.attach_component(entity, F);
+                six.push(entity);
+            }
+        }
+
+        (world, five, six)
+    }
+
+    #[test]
+    fn five_component_query_skips_partial_matches() {
+        let (world, five, _) = world();
+        let mut seen = vec![];
+        world.fetch_components(|entity: Entity, a: &mut A, _: &B, _: &C, _: &D, _: &E| {
+            a.0 += 100;
+            seen.push(entity);
+        });
+
+        seen.sort();
+        assert_eq!(seen, five);
+    }
+
+    #[test]
+    fn six_component_query_skips_partial_matches() {
+        let (world, _, six) = world();
+        let mut seen = vec![];
+        world.fetch_components(|entity: Entity, _: &A, _: &B, _: &C, _: &D, _: &E, _: &F| {
+            seen.push(entity);
+        });
+
+        seen.sort();
+        assert_eq!(seen, six);
+    }
+}
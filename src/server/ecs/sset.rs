@@ -1,16 +1,11 @@
 #![allow(dead_code)]
 
-#[derive(Debug)]
-struct Entry<T> {
-    key: usize,
-    value: T,
-}
-
 /// A sparse set is a data structure that allows for efficient insertion, deletion, and lookup of
 #[derive(Debug)]
 pub struct SparseSet<T> {
-    dense: Vec<Entry<T>>, // Dense set of values.
-    sparse: Vec<usize>,   // Sparse set of indices.
+    keys: Vec<usize>,   // Dense set of keys, parallel to `values`.
+    values: Vec<T>,     // Dense set of values.
+    sparse: Vec<usize>, // Sparse set of indices.
 }
 
 impl<T> SparseSet<T> {
@@ -20,7 +15,8 @@ impl<T> SparseSet<T> {
     /// Creates a new sparse set with the given capacity.
     pub fn new() -> Self {
         Self {
-            dense: vec![],
+            keys: vec![],
+            values: vec![],
             sparse: vec![],
         }
     }
@@ -35,8 +31,8 @@ impl<T> SparseSet<T> {
 
     /// Obtains the dense index for the key provided.
     fn get_dense_idx(&self, key: usize) -> Option<usize> {
-        let dense_idx = self.sparse[key];
-        if dense_idx < self.dense.len() {
+        let dense_idx = *self.sparse.get(key)?;
+        if dense_idx < self.values.len() {
             Some(dense_idx)
         } else {
             None
@@ -50,13 +46,13 @@ impl<T> SparseSet<T> {
 
     /// Gets the amount of elements in the sparse set.
     pub fn length(&self) -> usize {
-        self.dense.len()
+        self.values.len()
     }
 
     /// Obtains a reference for the value associated with the key.
     pub fn get(&self, key: usize) -> Option<&T> {
         if let Some(dense_idx) = self.get_dense_idx(key) {
-            Some(&self.dense[dense_idx].value)
+            Some(&self.values[dense_idx])
         } else {
             None
         }
@@ -65,7 +61,7 @@ impl<T> SparseSet<T> {
     /// Obtains a mutable reference for the value associated with the key.
     pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
         if let Some(dense_idx) = self.get_dense_idx(key) {
-            Some(&mut self.dense[dense_idx].value)
+            Some(&mut self.values[dense_idx])
         } else {
             None
         }
@@ -83,39 +79,52 @@ impl<T> SparseSet<T> {
             *stored = value;
         } else {
             // Key not present; store a new entry.
-            let dense_idx = self.dense.len();
-            self.dense.push(Entry { key, value });
-            self.sparse[key] = dense_idx;
+            self.sparse[key] = self.values.len();
+            self.keys.push(key);
+            self.values.push(value);
         }
     }
 
-    /// Removes a value based on the key provided.
-    pub fn remove(&mut self, key: usize) -> Option<T> {
-        if !self.has_key(key) {
-            return None;
-        }
+    /// Removes the entry at the dense index, moving the last entry into its place.
+    fn swap_remove(&mut self, dense_idx: usize) -> (usize, T) {
+        let key = self.keys.swap_remove(dense_idx);
+        let value = self.values.swap_remove(dense_idx);
 
-        let dense_idx = self.sparse[key];
-        let entry = self.dense.swap_remove(dense_idx);
-        if (dense_idx) < self.dense.len() {
-            let swapped = &self.dense[dense_idx];
-            self.sparse[swapped.key] = dense_idx;
+        // Update the sparse index for the swapped entry.
+        if dense_idx < self.keys.len() {
+            self.sparse[self.keys[dense_idx]] = dense_idx;
         }
 
+        // Mark the removed entry as invalid in the sparse set.
         self.sparse[key] = Self::INVALID_KEY;
-        Some(entry.value)
+        (key, value)
+    }
+
+    /// Removes a value based on the key provided.
+    pub fn remove(&mut self, key: usize) -> Option<T> {
+        let dense_idx = self.get_dense_idx(key)?;
+        Some(self.swap_remove(dense_idx).1)
     }
 
     /// Allows for iteration over the dense set.
     pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
-        self.dense.iter().map(|entry| (entry.key, &entry.value))
+        self.keys.iter().copied().zip(&self.values)
     }
 
-    /// Allows for iteration over the dense set.
+    /// Allows for mutable iteration over the dense set.
     pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
-        self.dense
-            .iter_mut()
-            .map(|entry| (entry.key, &mut entry.value))
+        self.keys.iter().copied().zip(&mut self.values)
+    }
+
+    /// Obtains the dense keys and values as parallel slices, in iteration order. The value at
+    /// each index belongs to the key at the same index.
+    pub fn as_slices(&self) -> (&[usize], &[T]) {
+        (&self.keys, &self.values)
+    }
+
+    /// Obtains the dense values as a mutable slice, in iteration order.
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
     }
 
     /// Removes all values that match the predicate `f`.
@@ -126,20 +135,9 @@ impl<T> SparseSet<T> {
         let mut removed = vec![];
         let mut dense_idx = 0;
 
-        while dense_idx < self.dense.len() {
-            if f(&self.dense[dense_idx].value) {
-                // Extract and save the key / value to be returned.
-                let Entry { key, value } = self.dense.swap_remove(dense_idx);
-                removed.push((key, value));
-
-                // Mark the removed entry as invalid in the sparse set.
-                self.sparse[key] = Self::INVALID_KEY;
-
-                // Update the sparse index for the swapped entry.
-                if dense_idx < self.dense.len() {
-                    let swapped_key = &self.dense[dense_idx].key;
-                    self.sparse[*swapped_key] = dense_idx;
-                }
+        while dense_idx < self.values.len() {
+            if f(&self.values[dense_idx]) {
+                removed.push(self.swap_remove(dense_idx));
             } else {
                 dense_idx += 1;
             }
@@ -148,3 +146,29 @@ impl<T> SparseSet<T> {
         removed.into_iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_follow_the_dense_order() {
+        let mut set = SparseSet::new();
+        for key in [6, 2, 4] {
+            set.insert(key, key * 10);
+        }
+        set.remove(6);
+
+        // The last value is swapped into the removed slot.
+        let (keys, values) = set.as_slices();
+        assert_eq!(keys, &[4, 2]);
+        assert_eq!(values, &[40, 20]);
+
+        for value in set.values_mut() {
+            *value += 1;
+        }
+        let mutated: Vec<_> = set.iter().map(|(key, value)| (key, *value)).collect();
+        assert_eq!(mutated, vec![(4, 41), (2, 21)]);
+        assert_eq!(set.get(2), Some(&21));
+    }
+}
@@ -2,10 +2,12 @@
 
 use std::any::TypeId;
 use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
 use super::component::{ComponentRef, ComponentStorage};
 use super::entity::Entity;
-use super::query::Query;
+use super::event::Events;
+use super::query::{Query, QueryIter};
 use super::resource::{ResourceRef, ResourceStorage};
 
 /// Command enum to represent actions that can be performed on entities.
@@ -49,6 +51,11 @@ pub struct World {
     next_entity_id: Entity,
     /// Recycled entities for reuse.
     recycled_entities: Vec<Entity>,
+    /// Current generation of each entity index and whether it is alive.
+    generations: Vec<(u32, bool)>,
+
+    /// Accumulated execution time per named system since the last reset.
+    timings: Vec<(&'static str, Duration)>,
 }
 
 impl World {
@@ -60,6 +67,9 @@ impl World {
 
             next_entity_id: Entity::from(1u32),
             recycled_entities: Vec::new(),
+            generations: Vec::new(),
+
+            timings: Vec::new(),
         }
     }
 
@@ -73,22 +83,91 @@ impl World {
         entity
     }
 
-    /// Creates a new entity in the world.
-    pub fn spawn_entity(&mut self) -> EntityBuilder {
+    /// Marks an entity as alive, reusing a recycled entity if one is available.
+    fn allocate_entity(&mut self) -> Entity {
         let entity = self
             .recycled_entities
             .pop()
             .map_or_else(|| self.generate_id(), |entity| entity);
+
+        let index = usize::from(entity);
+        if index >= self.generations.len() {
+            self.generations.resize(index + 1, (0, false));
+        }
+        self.generations[index] = (entity.generation(), true);
+
+        entity
+    }
+
+    /// Creates a new entity in the world.
+    pub fn spawn_entity(&mut self) -> EntityBuilder {
+        let entity = self.allocate_entity();
         EntityBuilder::new(self, entity)
     }
 
-    /// Kills an entity and removes all its components.
+    /// Creates an entity for each component, attaching the component to it. Recycled entities are
+    /// reused first and the component set is only borrowed once for the whole batch.
+    pub fn spawn_batch<C: 'static, I: IntoIterator<Item = C>>(&mut self, iter: I) -> Vec<Entity> {
+        let spawned: Vec<(Entity, C)> = iter
+            .into_iter()
+            .map(|component| (self.allocate_entity(), component))
+            .collect();
+
+        let Some(mut set) = self.components.get_mut::<C>() else {
+            panic!("No SparseSet found for component type. Did you forget to register?");
+        };
+
+        spawned
+            .into_iter()
+            .map(|(entity, component)| {
+                set.insert(entity.into(), component);
+                entity
+            })
+            .collect()
+    }
+
+    /// Creates a new entity with a copy of every component of `src` that was registered with
+    /// `register_cloneable_component`. Other components are not copied. None for stale handles.
+    pub fn clone_entity(&mut self, src: Entity) -> Option<Entity> {
+        if !self.is_alive(src) {
+            return None;
+        }
+
+        let entity = self.allocate_entity();
+        self.components.clone_entity(src, entity);
+        Some(entity)
+    }
+
+    /// Kills an entity and removes all its components. Stale handles are ignored.
     pub fn kill_entity(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
         // Remove all components associated with the entity.
         self.components.remove_entity(entity);
 
-        // Recycle the entity for future use.
-        self.recycled_entities.push(entity);
+        // Recycle the entity for future use, invalidating existing handles.
+        let generation = entity.generation().wrapping_add(1);
+        self.generations[usize::from(entity)] = (generation, false);
+        self.recycled_entities
+            .push(Entity::new(entity.index(), generation));
+    }
+
+    /// Checks if the entity is alive, meaning it has not been killed since the handle was obtained.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(usize::from(entity)) == Some(&(entity.generation(), true))
+    }
+
+    /// Obtains the handle of the entity currently occupying the index. The generation of the
+    /// provided value is ignored.
+    pub fn resolve(&self, index: impl Into<Entity>) -> Entity {
+        let index = index.into().index();
+        let generation = self
+            .generations
+            .get(index as usize)
+            .map_or(0, |(generation, _)| *generation);
+        Entity::new(index, generation)
     }
 
     /// Retrieves all entities that have a specific component type.
@@ -97,7 +176,7 @@ impl World {
 
         if let Some(set) = self.components.get::<C>() {
             for (entity, _) in set.iter() {
-                entities.insert(Entity::from(entity));
+                entities.insert(self.resolve(entity));
             }
         }
 
@@ -112,13 +191,22 @@ impl World {
         self.components.create::<C>();
     }
 
+    /// Registers a component type with the world, allowing it to be copied by `clone_entity`.
+    pub fn register_cloneable_component<C: Clone + 'static>(&mut self) {
+        self.components.create_cloneable::<C>();
+    }
+
     /// Deregisters a component type from the world.
     pub fn deregister_component<C: 'static>(&mut self) {
         self.components.destroy::<C>();
     }
 
-    /// Adds a component to an entity.
+    /// Adds a component to an entity. Stale handles are ignored.
     pub fn attach_component<C: 'static>(&self, entity: Entity, component: C) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
         if let Some(mut set) = self.components.get_mut::<C>() {
             set.insert(entity.into(), component);
         } else {
@@ -128,20 +216,64 @@ impl World {
 
     /// Removes a component from an entity.
     pub fn detach_component<C: 'static>(&self, entity: Entity) -> Option<C> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
         let mut set = self.components.get_mut::<C>()?;
         set.remove(entity.into())
     }
 
-    /// Retrieves a component from an entity. Can be mutable or immutable.
+    /// Retrieves a component from an entity. Can be mutable or immutable. None for stale handles.
     pub fn fetch_component<'a, R: ComponentRef<'a>>(&'a self, entity: Entity) -> Option<R::Output> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
         R::fetch(self, entity)
     }
 
+    /// Checks if an entity has a component without handing out a borrow of it. False for stale
+    /// handles, unregistered component types, or while the component set is accessed mutably.
+    pub fn has_component<C: 'static>(&self, entity: Entity) -> bool {
+        self.is_alive(entity)
+            && self
+                .components
+                .try_get::<C>()
+                .is_some_and(|set| set.has_key(entity.into()))
+    }
+
+    /// Retrieves two components from an entity at once. Each can be mutable or immutable. Returns
+    /// None if either is missing, the handle is stale, or the same component set would be
+    /// borrowed mutably twice.
+    pub fn fetch_two<'a, A: ComponentRef<'a>, B: ComponentRef<'a>>(
+        &'a self,
+        entity: Entity,
+    ) -> Option<(A::Output, B::Output)> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let first = A::try_fetch(self, entity)?;
+        let second = B::try_fetch(self, entity)?;
+        Some((first, second))
+    }
+
     /// Queries the world for components matching the query type.
     pub fn fetch_components<Q: Query<C>, C>(&self, f: Q) {
         Q::fetch(self, f);
     }
 
+    /// Iterates over the entities that have every component of the query, such as
+    /// `world.iter::<(&Transform, &Movement)>()`. Each item holds shared borrows of its
+    /// components, so items can be collected and held together. Components are only read, use
+    /// `fetch_components` to mutate them one entity at a time.
+    pub fn iter<'a, Q: QueryIter<'a>>(&'a self) -> impl Iterator<Item = Q::Item> + 'a {
+        Q::candidates(self)
+            .into_iter()
+            .filter_map(move |entity| Q::fetch(self, entity))
+    }
+
     // -----------------------------------------------------------------------
     // Resource management
 
@@ -160,6 +292,55 @@ impl World {
         R::fetch(self)
     }
 
+    /// Sends an event to the `Events<E>` resource, registering the resource on first use.
+    pub fn send_event<E: 'static>(&mut self, event: E) {
+        if let Some(mut events) = self.fetch_resource::<&mut Events<E>>() {
+            events.send(event);
+        } else {
+            let mut events = Events::default();
+            events.send(event);
+            self.register_resource(events);
+        }
+    }
+
+    /// Removes and returns every event of type `E` in the order they were sent. Empty if none
+    /// were ever sent.
+    pub fn drain_events<E: 'static>(&mut self) -> Vec<E> {
+        self.fetch_resource::<&mut Events<E>>()
+            .map(|mut events| events.drain())
+            .unwrap_or_default()
+    }
+
+    // -----------------------------------------------------------------------
+    // System profiling
+
+    /// Runs a system, adding its execution time to the profile under the given name.
+    pub fn time_system<T>(&mut self, name: &'static str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let start = Instant::now();
+        let output = f(self);
+        let elapsed = start.elapsed();
+
+        if let Some((_, total)) = self.timings.iter_mut().find(|(n, _)| *n == name) {
+            *total += elapsed;
+        } else {
+            self.timings.push((name, elapsed));
+        }
+
+        output
+    }
+
+    /// Snapshot of the accumulated execution time per system, in the order they were first timed.
+    pub fn profile(&self) -> &[(&'static str, Duration)] {
+        &self.timings
+    }
+
+    /// Resets the accumulated execution times, keeping the system order.
+    pub fn reset_profile(&mut self) {
+        for (_, total) in &mut self.timings {
+            *total = Duration::ZERO;
+        }
+    }
+
     // -----------------------------------------------------------------------
     // Apply commands.
 
@@ -168,6 +349,10 @@ impl World {
         for command in commands {
             match command {
                 Command::Detach(entity, type_id) => {
+                    if !self.is_alive(entity) {
+                        continue;
+                    }
+
                     if let Some(&idx) = self.components.lookup.get(&type_id) {
                         // SAFETY: idx is the right slot
                         let set = &self.components.sets[idx];
@@ -181,3 +366,227 @@ impl World {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::payload::Movement;
+    use crate::shared::transform::Transform;
+    use crate::vec2f::Vec2f;
+
+    /// World with `Transform` registered and three entities spawned with it.
+    fn world_with_three() -> (World, [Entity; 3]) {
+        let mut world = World::new();
+        world.register_component::<Transform>();
+        let entities = [0.0, 1.0, 2.0].map(|x| {
+            world
+                .spawn_entity()
+                .attach(Transform::with_position(Vec2f(x, 0.0)))
+                .build()
+        });
+        (world, entities)
+    }
+
+    #[test]
+    fn stale_handles_do_not_resolve() {
+        let (mut world, [_, killed, _]) = world_with_three();
+        world.kill_entity(killed);
+        assert!(!world.is_alive(killed));
+
+        let reused = world
+            .spawn_entity()
+            .attach(Transform::with_position(Vec2f(9.0, 9.0)))
+            .build();
+        assert_eq!(reused.index(), killed.index(), "slot was not reused");
+        assert_ne!(reused, killed);
+
+        assert!(world.fetch_component::<&Transform>(killed).is_none());
+        assert!(!world.has_component::<Transform>(killed));
+        world.attach_component(killed, Transform::with_position(Vec2f::ZERO));
+        let position = world
+            .fetch_component::<&Transform>(reused)
+            .map(|t| t.position);
+        assert_eq!(
+            position,
+            Some(Vec2f(9.0, 9.0)),
+            "stale handle overwrote the new entity"
+        );
+        assert_eq!(world.resolve(killed.index()), reused);
+    }
+
+    #[test]
+    fn batches_reuse_killed_slots_first() {
+        let (mut world, [_, killed, _]) = world_with_three();
+        world.kill_entity(killed);
+
+        let batch = world
+            .spawn_batch((0..100u8).map(|x| Transform::with_position(Vec2f(f32::from(x), 1.0))));
+        assert_eq!(batch.len(), 100);
+        assert_eq!(batch[0].index(), killed.index(), "slot was not reused");
+        assert_eq!(world.fetch_entities::<Transform>().len(), 102);
+
+        for (x, &entity) in (0..100u8).zip(&batch) {
+            assert!(world.is_alive(entity));
+            let position = world
+                .fetch_component::<&Transform>(entity)
+                .map(|t| t.position);
+            assert_eq!(position, Some(Vec2f(f32::from(x), 1.0)));
+        }
+
+        // Batched entities take further components like any other.
+        world.register_component::<Movement>();
+        world.attach_component(batch[99], Movement(Vec2f::ONE, 1));
+        assert!(world.has_component::<Movement>(batch[99]));
+    }
+
+    #[test]
+    fn clones_copy_cloneable_components_only() {
+        let mut world = World::new();
+        world.register_cloneable_component::<Transform>();
+        world.register_cloneable_component::<Movement>();
+        world.register_component::<u8>();
+        let src = world
+            .spawn_entity()
+            .attach(Transform::with_position(Vec2f(1.0, 2.0)))
+            .attach(Movement(Vec2f::ONE, 3))
+            .attach(7u8)
+            .build();
+
+        let copy = world.clone_entity(src).expect("entity was not cloned");
+        assert_ne!(copy, src);
+        assert!(!world.has_component::<u8>(copy));
+
+        // The copy is independent of the original.
+        if let Some(mut movement) = world.fetch_component::<&mut Movement>(copy) {
+            movement.0 = Vec2f::ZERO;
+        }
+        if let Some(mut transform) = world.fetch_component::<&mut Transform>(src) {
+            transform.position = Vec2f(5.0, 5.0);
+        }
+
+        let position = |entity| {
+            world
+                .fetch_component::<&Transform>(entity)
+                .map(|t| t.position)
+        };
+        let velocity = |entity| world.fetch_component::<&Movement>(entity).map(|m| m.0);
+        assert_eq!(position(copy), Some(Vec2f(1.0, 2.0)));
+        assert_eq!(velocity(copy), Some(Vec2f::ZERO));
+        assert_eq!(position(src), Some(Vec2f(5.0, 5.0)));
+        assert_eq!(velocity(src), Some(Vec2f::ONE));
+
+        world.kill_entity(src);
+        assert_eq!(world.clone_entity(src), None);
+    }
+
+    #[test]
+    fn fetch_two_borrows_both_components() {
+        let (mut world, [entity, other, _]) = world_with_three();
+        world.register_component::<Movement>();
+        world.attach_component(entity, Movement(Vec2f(1.0, 0.0), 1));
+
+        {
+            let (transform, mut movement) = world
+                .fetch_two::<&Transform, &mut Movement>(entity)
+                .expect("components were not fetched");
+            movement.0 = transform.position + Vec2f(0.0, 2.0);
+        }
+
+        let velocity = world.fetch_component::<&Movement>(entity).map(|m| m.0);
+        assert_eq!(velocity, Some(Vec2f(0.0, 2.0)));
+        assert!(world.fetch_two::<&Transform, &Movement>(other).is_none());
+    }
+
+    #[test]
+    fn fetch_two_refuses_a_second_mutable_borrow() {
+        let (world, [entity, ..]) = world_with_three();
+        assert!(
+            world
+                .fetch_two::<&mut Transform, &mut Transform>(entity)
+                .is_none()
+        );
+        assert!(world.fetch_two::<&Transform, &Transform>(entity).is_some());
+    }
+
+    #[test]
+    fn has_component_does_not_borrow_the_value() {
+        let (mut world, [entity, other, _]) = world_with_three();
+        world.register_component::<Movement>();
+        assert!(!world.has_component::<Movement>(entity));
+
+        world.attach_component(entity, Movement(Vec2f::ONE, 1));
+        assert!(world.has_component::<Movement>(entity));
+        assert!(!world.has_component::<Movement>(other));
+
+        // Checking one component while another is borrowed mutably is fine.
+        {
+            let _movement = world.fetch_component::<&mut Movement>(entity).unwrap();
+            assert!(world.has_component::<Transform>(entity));
+        }
+
+        world.detach_component::<Movement>(entity);
+        assert!(!world.has_component::<Movement>(entity));
+        assert!(
+            !world.has_component::<u8>(entity),
+            "unregistered types are never present"
+        );
+    }
+
+    #[test]
+    fn iter_collects_two_component_matches() {
+        let (mut world, [first, _, third]) = world_with_three();
+        world.register_component::<Movement>();
+        world.attach_component(first, Movement(Vec2f(1.0, 0.0), 1));
+        world.attach_component(third, Movement(Vec2f(0.0, 1.0), 1));
+
+        let mut matched: Vec<_> = world
+            .iter::<(&Transform, &Movement)>()
+            .map(|(entity, transform, movement)| (entity, transform.position + movement.0))
+            .collect();
+        matched.sort_by_key(|(entity, _)| entity.index());
+        assert_eq!(
+            matched,
+            vec![(first, Vec2f(1.0, 0.0)), (third, Vec2f(2.0, 1.0))]
+        );
+        assert_eq!(world.iter::<(&Transform,)>().count(), 3);
+    }
+
+    #[test]
+    fn iter_items_can_be_held_together() {
+        let (world, [first, second, third]) = world_with_three();
+
+        // Every item keeps its borrow, and other readers are still allowed while they are held.
+        let held: Vec<_> = world.iter::<(&Transform,)>().collect();
+        assert_eq!(held.len(), 3);
+        assert!(world.fetch_component::<&Transform>(second).is_some());
+        let mut positions: Vec<_> = held
+            .iter()
+            .map(|(entity, transform)| (*entity, transform.position))
+            .collect();
+        positions.sort_by_key(|(entity, _)| entity.index());
+        assert_eq!(positions[0], (first, Vec2f(0.0, 0.0)));
+        assert_eq!(positions[2].0, third);
+        drop(held);
+
+        // Mutation goes through the callback query once the items are dropped.
+        world.fetch_components(|_: Entity, transform: &mut Transform| transform.position.1 = 5.0);
+        assert!(
+            world
+                .iter::<(&Transform,)>()
+                .all(|(_, transform)| transform.position.1 > 4.9)
+        );
+    }
+
+    #[test]
+    fn events_of_each_type_are_drained_separately() {
+        let mut world = World::new();
+        world.send_event(1u32);
+        world.send_event("first");
+        world.send_event(2u32);
+
+        assert_eq!(world.drain_events::<u32>(), vec![1, 2]);
+        assert!(world.drain_events::<u32>().is_empty());
+        assert_eq!(world.drain_events::<&str>(), vec!["first"]);
+        assert!(world.drain_events::<u8>().is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::shared::transform::Transform;
+use crate::vec2f::Vec2f;
+
+use super::ecs::{Entity, World};
+
+/// Positions of every entity over the most recent ticks, newest first. Used to validate actions
+/// against where entities were at the time a client saw them.
+pub(crate) struct PositionHistory {
+    ticks: VecDeque<HashMap<Entity, Vec2f>>, // Positions recorded each tick, newest first.
+    capacity: usize,                         // Most ticks retained.
+}
+
+impl PositionHistory {
+    /// Creates a history that retains at most `capacity` ticks.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            ticks: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Records the position of every entity with a `Transform`, dropping the oldest tick if full.
+    pub fn record(&mut self, world: &World) {
+        let mut positions = if self.ticks.len() >= self.capacity {
+            // Reuse the allocation of the oldest tick.
+            let mut oldest = self.ticks.pop_back().unwrap_or_default();
+            oldest.clear();
+            oldest
+        } else {
+            HashMap::new()
+        };
+
+        world.fetch_components(|entity, transform: &Transform| {
+            positions.insert(entity, transform.position);
+        });
+
+        self.ticks.push_front(positions);
+    }
+
+    /// Obtains the position of an entity `ticks_ago` ticks before the newest recorded one. None
+    /// if the tick is no longer retained or the entity did not exist at the time.
+    pub fn position_at(&self, entity: Entity, ticks_ago: usize) -> Option<Vec2f> {
+        self.ticks.get(ticks_ago)?.get(&entity).copied()
+    }
+
+    /// Removes every recorded tick.
+    pub fn clear(&mut self) {
+        self.ticks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves the entity to `x` and records the tick.
+    fn record_at(history: &mut PositionHistory, world: &World, entity: Entity, x: f32) {
+        if let Some(mut transform) = world.fetch_component::<&mut Transform>(entity) {
+            transform.position = Vec2f(x, 0.0);
+        }
+        history.record(world);
+    }
+
+    #[test]
+    fn past_positions_are_kept_for_the_capacity() {
+        let mut world = World::new();
+        world.register_component::<Transform>();
+        let entity = world
+            .spawn_entity()
+            .attach(Transform::with_position(Vec2f::ZERO))
+            .build();
+
+        let mut history = PositionHistory::new(3);
+        for x in [1.0, 2.0, 3.0, 4.0] {
+            record_at(&mut history, &world, entity, x);
+        }
+
+        assert_eq!(history.position_at(entity, 0), Some(Vec2f(4.0, 0.0)));
+        assert_eq!(history.position_at(entity, 2), Some(Vec2f(2.0, 0.0)));
+        assert_eq!(history.position_at(entity, 3), None, "oldest tick was kept");
+        assert_eq!(history.ticks.len(), 3);
+
+        // Entities that did not exist yet have no past position.
+        let late = world
+            .spawn_entity()
+            .attach(Transform::with_position(Vec2f::ONE))
+            .build();
+        history.record(&world);
+        assert_eq!(history.position_at(late, 0), Some(Vec2f::ONE));
+        assert_eq!(history.position_at(late, 1), None);
+
+        history.clear();
+        assert_eq!(history.position_at(entity, 0), None);
+    }
+}
@@ -0,0 +1,216 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::error::{AppError, Result};
+use crate::net::error::{NetError, Result as NetResult};
+use crate::net::{ClientId, Packet};
+
+use super::socket::ServerSocket;
+
+/// Commands sent from the simulation to the network thread.
+enum NetCommand {
+    Send(ClientId, Packet),        // Send a packet to a single client.
+    Broadcast(Packet),             // Send a packet to all clients.
+    Kick(ClientId, bool),          // Kick a client, notifying it if set.
+    Blacklist(ClientId, Duration), // Kick a client and blacklist it for a duration.
+    Shutdown,                      // Notify all clients and stop the network thread.
+}
+
+/// Network side of the server running on its own thread.
+pub(crate) struct NetThread {
+    id: ClientId,                         // ID of the server socket.
+    inbound: Receiver<NetResult<Packet>>, // Packets and errors from the network thread.
+    outbound: Option<Sender<NetCommand>>, // Commands to the network thread. Dropped to stop it.
+    handle: Option<JoinHandle<()>>,       // Handle to join the network thread.
+}
+
+impl NetThread {
+    /// Time to wait between polls of the socket.
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    /// Moves the socket onto a new thread that processes packets independently of the simulation.
+    fn spawn(mut socket: ServerSocket) -> Self {
+        let id = socket.id();
+        let (inbound_tx, inbound) = mpsc::channel();
+        let (outbound, outbound_rx) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            if let Err(why) = Self::run(&mut socket, &inbound_tx, &outbound_rx) {
+                let _ = inbound_tx.send(Err(why));
+            }
+        });
+
+        Self {
+            id,
+            inbound,
+            outbound: Some(outbound),
+            handle: Some(handle),
+        }
+    }
+
+    /// Network loop. Forwards queued commands to the socket and received packets to the
+    /// simulation until either side of the channels is closed or a socket error occurs.
+    fn run(
+        socket: &mut ServerSocket,
+        inbound: &Sender<NetResult<Packet>>,
+        outbound: &Receiver<NetCommand>,
+    ) -> NetResult<()> {
+        loop {
+            loop {
+                let result = match outbound.try_recv() {
+                    Ok(NetCommand::Send(client, packet)) => socket.send(client, packet),
+                    Ok(NetCommand::Broadcast(packet)) => socket.broadcast(&packet),
+                    Ok(NetCommand::Kick(client, notify)) => socket.kick(client, notify),
+                    Ok(NetCommand::Blacklist(client, duration)) => {
+                        match socket.kick_and_blacklist(client, duration) {
+                            // The client left before the kick was processed.
+                            Err(AppError::Net(NetError::NotConnected(..))) => Ok(()),
+                            result => result,
+                        }
+                    }
+                    Ok(NetCommand::Shutdown) => {
+                        return socket.shutdown().map_err(Self::to_net_error);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                };
+
+                result.map_err(Self::to_net_error)?;
+            }
+
+            for packet in socket.run_step().map_err(Self::to_net_error)? {
+                if inbound.send(Ok(packet)).is_err() {
+                    return Ok(()); // Simulation has stopped.
+                }
+            }
+
+            thread::sleep(Self::POLL_INTERVAL);
+        }
+    }
+
+    /// Extracts the network error, the only kind the server socket produces.
+    fn to_net_error(error: AppError) -> NetError {
+        match error {
+            AppError::Net(why) => why,
+            why => NetError::SocketError(why.to_string()),
+        }
+    }
+
+    /// Queues a command for the network thread.
+    fn queue(&self, command: NetCommand) -> Result<()> {
+        match &self.outbound {
+            Some(outbound) if outbound.send(command).is_ok() => Ok(()),
+            _ => Err(AppError::Net(NetError::Disconnected)),
+        }
+    }
+}
+
+impl Drop for NetThread {
+    fn drop(&mut self) {
+        // Closing the channel signals the network thread to stop.
+        self.outbound.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Link between the simulation and the network. Either owns the socket directly or
+/// communicates with a dedicated network thread through channels.
+pub(crate) enum NetLink {
+    Direct(Box<ServerSocket>), // Networking runs on the simulation thread.
+    Threaded(NetThread),       // Networking runs on its own thread.
+}
+
+impl NetLink {
+    /// Creates a link that processes the socket on the calling thread.
+    pub fn direct(socket: ServerSocket) -> Self {
+        Self::Direct(Box::new(socket))
+    }
+
+    /// Creates a link that processes the socket on a separate thread.
+    pub fn threaded(socket: ServerSocket) -> Self {
+        Self::Threaded(NetThread::spawn(socket))
+    }
+
+    /// Obtains the ID of the server.
+    pub fn id(&self) -> ClientId {
+        match self {
+            Self::Direct(socket) => socket.id(),
+            Self::Threaded(net) => net.id,
+        }
+    }
+
+    /// Sends a packet to the client. Threaded links queue the packet and return immediately.
+    pub fn send(&mut self, dest: ClientId, packet: Packet) -> Result<()> {
+        match self {
+            Self::Direct(socket) => socket.send(dest, packet),
+            Self::Threaded(net) => net.queue(NetCommand::Send(dest, packet)),
+        }
+    }
+
+    /// Sends the packet to all clients. Threaded links queue the packet and return immediately.
+    pub fn broadcast(&mut self, packet: &Packet) -> Result<()> {
+        match self {
+            Self::Direct(socket) => socket.broadcast(packet),
+            Self::Threaded(net) => net.queue(NetCommand::Broadcast(packet.clone())),
+        }
+    }
+
+    /// Kicks a client, notifying it if requested. Threaded links queue the kick and return
+    /// immediately.
+    pub fn kick(&mut self, client: ClientId, notify: bool) -> Result<()> {
+        match self {
+            Self::Direct(socket) => socket.kick(client, notify),
+            Self::Threaded(net) => net.queue(NetCommand::Kick(client, notify)),
+        }
+    }
+
+    /// Kicks a client and blacklists it for a duration. Direct links fail if the client is not
+    /// connected, threaded links queue the kick and return immediately.
+    pub fn kick_and_blacklist(&mut self, client: ClientId, duration: Duration) -> Result<()> {
+        match self {
+            Self::Direct(socket) => socket.kick_and_blacklist(client, duration),
+            Self::Threaded(net) => net.queue(NetCommand::Blacklist(client, duration)),
+        }
+    }
+
+    /// Notifies all clients that the server is shutting down. Threaded links also stop the
+    /// network thread once the clients have been notified.
+    pub fn shutdown(&mut self) -> Result<()> {
+        match self {
+            Self::Direct(socket) => socket.shutdown(),
+            Self::Threaded(net) => {
+                net.queue(NetCommand::Shutdown)?;
+                // Wait for the network thread to finish notifying the clients.
+                net.outbound.take();
+                if let Some(handle) = net.handle.take() {
+                    let _ = handle.join();
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Obtains all packets received since the last step.
+    pub fn run_step(&mut self) -> Result<Vec<Packet>> {
+        let net = match self {
+            Self::Direct(socket) => return socket.run_step(),
+            Self::Threaded(net) => net,
+        };
+
+        let mut out = vec![];
+        loop {
+            match net.inbound.try_recv() {
+                Ok(Ok(packet)) => out.push(packet),
+                Ok(Err(why)) => return Err(AppError::Net(why)),
+                Err(TryRecvError::Empty) => return Ok(out),
+                Err(TryRecvError::Disconnected) => {
+                    return Err(AppError::Net(NetError::Disconnected));
+                }
+            }
+        }
+    }
+}
@@ -3,6 +3,9 @@
 mod ai;
 mod core;
 mod ecs;
+mod history;
+mod link;
+mod snapshot;
 mod socket;
 mod spawner;
 mod sys;
@@ -37,7 +40,6 @@ impl ClientEntityMap {
     }
 
     /// Removes a client ID and entity from the map.
-    #[allow(dead_code)]
     fn remove(&mut self, client_id: ClientId) {
         if let Some(entity) = self.client_entity.remove(&client_id) {
             self.entity_client.remove(&entity);
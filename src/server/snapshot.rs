@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::net::ClientId;
+use crate::shared::snapshot::{Snapshot, SnapshotHistory, SnapshotState};
+
+/// Snapshots sent to a client and the newest one it has acknowledged.
+#[derive(Default)]
+struct ClientSnapshots {
+    next_id: u32,                        // Id of the next snapshot to send.
+    input_seq: u32,                      // Input sequence echoed by the last sent snapshot.
+    acked: Option<(u32, SnapshotState)>, // Newest acknowledged snapshot, the delta baseline.
+    pending: SnapshotHistory,            // Sent snapshots waiting to be acknowledged.
+    known: HashSet<u32>,                 // Entities the client was sent and not told to despawn.
+}
+
+/// Tracks the snapshots of each client so only changes since the last acknowledged snapshot
+/// are sent.
+#[derive(Default)]
+pub(crate) struct SnapshotTracker {
+    clients: HashMap<ClientId, ClientSnapshots>,
+}
+
+impl SnapshotTracker {
+    /// Creates the next snapshot of `current` for a client, a delta against its newest
+    /// acknowledged snapshot. A full snapshot is created if there is no acknowledged snapshot, or
+    /// if acknowledgements stopped arriving and every pending snapshot has gone unanswered.
+    /// `input_seq` is the client's last processed input, echoed back in the snapshot. None if
+    /// nothing changed and the input sequence was already sent, no Id is used in that case.
+    pub fn snapshot(
+        &mut self,
+        client_id: ClientId,
+        current: &SnapshotState,
+        input_seq: u32,
+    ) -> Option<Snapshot> {
+        let client = self.clients.entry(client_id).or_default();
+        if client.pending.is_full() {
+            // Acknowledgements are missing, start over with a full snapshot.
+            client.acked = None;
+            client.pending.clear();
+        }
+
+        let id = client.next_id;
+        let baseline = client.acked.as_ref().map(|(id, state)| (*id, state));
+        let (mut snapshot, state) = Snapshot::diff(id, baseline, current);
+        if snapshot.entries.0.is_empty() && input_seq == client.input_seq {
+            return None;
+        }
+
+        snapshot.input_seq = input_seq;
+        client.input_seq = input_seq;
+        client.next_id = client.next_id.wrapping_add(1);
+        client.pending.push(id, state);
+        client.known.extend(current.keys());
+        Some(snapshot)
+    }
+
+    /// Obtains the entities a client was sent that are missing from `current`, such as when they
+    /// were killed or left its interest range, and forgets them so each is only reported once.
+    /// They are also removed from the baseline and pending states, so one that appears again is
+    /// sent in full.
+    pub fn despawned(&mut self, client_id: ClientId, current: &SnapshotState) -> Vec<u32> {
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return vec![];
+        };
+
+        let gone: Vec<u32> = client
+            .known
+            .iter()
+            .filter(|entity| !current.contains_key(entity))
+            .copied()
+            .collect();
+
+        for &entity in &gone {
+            client.known.remove(&entity);
+            client.pending.forget(entity);
+            if let Some((_, state)) = client.acked.as_mut() {
+                state.remove(&entity);
+            }
+        }
+
+        gone
+    }
+
+    /// Records that a client applied a snapshot, making it the baseline for later snapshots.
+    /// Acknowledgements for unknown or superseded snapshots are ignored.
+    pub fn acknowledge(&mut self, client_id: ClientId, id: u32) {
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+
+        if let Some(acked) = client.pending.take(id) {
+            client.acked = Some(acked);
+        }
+    }
+
+    /// Removes the snapshots of a client.
+    pub fn remove(&mut self, client_id: ClientId) {
+        self.clients.remove(&client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::snapshot::SnapshotPosition;
+    use crate::vec2f::Vec2f;
+
+    const CLIENT: ClientId = ClientId(1);
+
+    fn state(entities: &[(u32, Vec2f)]) -> SnapshotState {
+        entities
+            .iter()
+            .map(|&(entity, position)| (entity, (position, Vec2f::ZERO)))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_snapshots_are_skipped_without_using_an_id() {
+        let mut tracker = SnapshotTracker::default();
+        let current = state(&[(1, Vec2f::ZERO)]);
+
+        let first = tracker.snapshot(CLIENT, &current, 0).unwrap();
+        tracker.acknowledge(CLIENT, first.id);
+        assert!(tracker.snapshot(CLIENT, &current, 0).is_none());
+
+        let moved = state(&[(1, Vec2f(1.0, 0.0))]);
+        let next = tracker.snapshot(CLIENT, &moved, 0).unwrap();
+        assert_eq!(next.id, first.id + 1);
+        assert_eq!(next.baseline, Some(first.id));
+    }
+
+    #[test]
+    fn new_input_sequence_is_sent_without_changes() {
+        let mut tracker = SnapshotTracker::default();
+        let current = state(&[(1, Vec2f::ZERO)]);
+
+        let first = tracker.snapshot(CLIENT, &current, 3).unwrap();
+        tracker.acknowledge(CLIENT, first.id);
+        assert!(tracker.snapshot(CLIENT, &current, 3).is_none());
+
+        let echo = tracker.snapshot(CLIENT, &current, 4).unwrap();
+        assert!(echo.entries.0.is_empty());
+        assert_eq!(echo.input_seq, 4);
+        assert!(tracker.snapshot(CLIENT, &current, 4).is_none());
+    }
+
+    #[test]
+    fn despawned_entities_are_sent_in_full_again() {
+        let mut tracker = SnapshotTracker::default();
+        let current = state(&[(1, Vec2f(2.0, 2.0))]);
+
+        let first = tracker.snapshot(CLIENT, &current, 0).unwrap();
+        tracker.acknowledge(CLIENT, first.id);
+        assert_eq!(tracker.despawned(CLIENT, &SnapshotState::new()), vec![1]);
+        assert!(tracker.despawned(CLIENT, &SnapshotState::new()).is_empty());
+
+        let again = tracker.snapshot(CLIENT, &current, 0).unwrap();
+        assert_eq!(again.baseline, Some(first.id));
+        assert_eq!(again.entries.0.len(), 1);
+        assert_eq!(
+            again.entries.0[0].1,
+            SnapshotPosition::Full(Vec2f(2.0, 2.0))
+        );
+    }
+}
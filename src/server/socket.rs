@@ -1,4 +1,7 @@
+use std::time::Duration;
+
 use crate::error::{AppError, Result};
+use crate::net::builtins::MessagePayload;
 use crate::net::error::NetError;
 use crate::net::{ClientId, Deliverable, Packet, PacketLabel, Socket};
 use crate::{debugln, flee};
@@ -38,6 +41,59 @@ impl ServerSocket {
         }
     }
 
+    /// Sends the packet to all clients.
+    pub fn broadcast(&mut self, packet: &Packet) -> Result<()> {
+        self.socket.broadcast(packet).map_err(AppError::Net)
+    }
+
+    /// Sends a text message to all clients. Messages too large to fit within a packet are rejected.
+    #[allow(dead_code)]
+    pub fn broadcast_message(&mut self, text: &str) -> Result<()> {
+        let mut packet = Packet::new(PacketLabel::Message, self.id());
+        packet
+            .try_set_payload(MessagePayload(text.to_string()))
+            .map_err(AppError::Net)?;
+
+        self.broadcast(&packet)
+    }
+
+    /// Sends the packet to all clients except the one provided.
+    #[allow(dead_code)]
+    pub fn broadcast_except(&mut self, except: ClientId, packet: &Packet) -> Result<()> {
+        self.socket
+            .broadcast_except(except, packet)
+            .map_err(AppError::Net)
+    }
+
+    /// Notifies all clients that the server is shutting down. Packets can no longer be sent afterwards.
+    pub fn shutdown(&mut self) -> Result<()> {
+        self.socket.shutdown().map_err(AppError::Net)
+    }
+
+    /// Removes a client from the server on demand, notifying it if requested. A disconnect packet
+    /// from the client is received on the next step, so its game state is cleaned up the same
+    /// way as when it leaves on its own.
+    pub fn kick(&mut self, id: ClientId, notify: bool) -> Result<()> {
+        debugln!("SERVER: Kicking client [{}].", id);
+        self.disconnect_client(id, notify)
+    }
+
+    /// Kicks a client, notifying it, and blacklists its address for a duration so it cannot
+    /// reconnect. Fails with `NetError::NotConnected` if there is no such client, as there is no
+    /// address to blacklist.
+    pub fn kick_and_blacklist(&mut self, id: ClientId, duration: Duration) -> Result<()> {
+        match self.socket.blacklist_client(id, duration) {
+            Ok(()) => Ok(()),
+            Err(why @ (NetError::SocketError(..) | NetError::NotConnected(..))) => {
+                Err(AppError::Net(why))
+            }
+            Err(why) => {
+                debugln!("SERVER: Error while blacklisting client [{}]: {}", id, why);
+                Ok(())
+            }
+        }
+    }
+
     /// Disconnects a client from the server and removes it from the list.
     fn disconnect_client(&mut self, id: ClientId, notify: bool) -> Result<()> {
         // Remove the client from the list.
@@ -78,8 +134,8 @@ impl ServerSocket {
         if let PacketLabel::Disconnect = packet.label() {
             debugln!("SERVER: Client [{}] is disconnecting.", packet.source(),);
             self.disconnect_client(packet.source(), false)?;
-            if !self.socket.is_remote() {
-                // Local sockets shut the server down on disconnect.
+            if !self.socket.is_remote() && self.socket.client_count() == 0 {
+                // Local sockets shut the server down once the last client leaves.
                 flee!(AppError::Net(NetError::Disconnected));
             }
         }
@@ -88,3 +144,67 @@ impl ServerSocket {
         Ok(Some(()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::net::LocalHub;
+    use crate::net::builtins::ConnectionPayload;
+    use crate::utils::decode;
+
+    use super::*;
+
+    /// Connects a new local client to the server through the hub.
+    fn join(server: &mut ServerSocket, hub: &mut LocalHub) -> Socket {
+        let mut client = Socket::new_local_client(hub).unwrap();
+        let payload = ConnectionPayload(
+            Packet::CURRENT_VERSION,
+            client.id(),
+            5000,
+            0,
+            Packet::MIN_VERSION,
+        );
+        let request = Packet::with_payload(PacketLabel::Connect, client.id(), payload);
+        client.send(Deliverable::new(ClientId(0), request)).unwrap();
+        server.run_step().unwrap();
+
+        let accept = client.try_recv().unwrap().expect("accept was not received");
+        assert_eq!(accept.label(), PacketLabel::ConnectAccept);
+        client
+    }
+
+    #[test]
+    fn messages_are_broadcast_to_every_client() {
+        let (mut hub, socket) = Socket::new_local_hub().unwrap();
+        let mut server = ServerSocket::new(socket);
+        let mut clients = [join(&mut server, &mut hub), join(&mut server, &mut hub)];
+
+        server.broadcast_message("hello clients").unwrap();
+        for client in &mut clients {
+            let packet = client.try_recv().unwrap().expect("message was not sent");
+            assert_eq!(packet.label(), PacketLabel::Message);
+            let MessagePayload(text) = decode::<MessagePayload>(&packet).unwrap();
+            assert_eq!(text, "hello clients");
+        }
+
+        // Messages too large for a packet are not sent to anyone.
+        let text = "x".repeat(Packet::MAX_PAYLOAD_SIZE + 1);
+        assert!(server.broadcast_message(&text).is_err());
+        for client in &mut clients {
+            assert!(client.try_recv().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn blacklisting_an_unknown_client_fails() {
+        let (mut hub, socket) = Socket::new_local_hub().unwrap();
+        let mut server = ServerSocket::new(socket);
+        let client = join(&mut server, &mut hub);
+
+        let ban = Duration::from_secs(30);
+        server.kick_and_blacklist(client.id(), ban).unwrap();
+        assert!(matches!(
+            server.kick_and_blacklist(client.id(), ban),
+            Err(AppError::Net(NetError::NotConnected(..)))
+        ));
+    }
+}
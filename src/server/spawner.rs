@@ -47,4 +47,14 @@ impl Spawner {
     pub fn add_entity(&mut self, entity: Entity) {
         self.entities.push(entity);
     }
+
+    /// Forgets spawned entities that are no longer alive. If the spawner was at capacity, the
+    /// cooldown restarts so the replacements spawn one spawn rate after the deaths.
+    pub fn prune(&mut self, is_alive: impl Fn(Entity) -> bool) {
+        let was_full = self.at_capacity();
+        self.entities.retain(|entity| is_alive(*entity));
+        if was_full && !self.at_capacity() {
+            self.reset();
+        }
+    }
 }
@@ -1,13 +1,20 @@
-use rand::random_range;
-
 use crate::server::ai::{AiState, BasicAi};
 use crate::server::core::LastTarget;
 use crate::server::ecs::World;
+use crate::server::world_map::WorldMap;
 use crate::shared::payload::Movement;
 use crate::shared::transform::Transform;
+use crate::utils::Rng;
 use crate::vec2f::Vec2f;
 
-pub fn ai(world: &mut World) {
+/// Furthest an entity is moved towards its target at once while pursuing.
+const PURSUE_MAX_STEP: f32 = 1.5;
+
+/// Updates the movement of AI controlled entities. Pursuing entities path around blocked cells
+/// of the map, while wandering draws from the world's `Rng` resource so the simulation is
+/// reproducible for a given seed. States only change on their own after `BasicAi::MIN_DWELL`
+/// updates, so targets near the edge of the pursue range do not cause flickering.
+pub fn ai(world: &mut World, map: &WorldMap) {
     world.fetch_components(
         |_entity,
          transform: &Transform,
@@ -20,6 +27,7 @@ pub fn ai(world: &mut World) {
                 None
             };
 
+            ai.tick();
             match ai.state {
                 AiState::Pursue => {
                     let Some(entity_transform) = target_transform else {
@@ -27,26 +35,37 @@ pub fn ai(world: &mut World) {
                         return;
                     };
 
-                    if (transform.position - entity_transform.position).length() > 5.0 {
-                        // Out of reach, start wandering.
-                        ai.set_state(AiState::Wander(3.0, 1));
-                    } else {
-                        // Update the movement vector to follow the entity.
-                        movement.0 = entity_transform.position - transform.position;
+                    let distance = (transform.position - entity_transform.position).length();
+                    if distance > BasicAi::ESCAPE_RANGE && ai.try_set_state(AiState::Wander(3.0, 1))
+                    {
+                        return; // Out of reach, start wandering.
                     }
+
+                    // Follow the entity through the next waypoint, or head straight for it if
+                    // there is no path.
+                    let target = entity_transform.position;
+                    let next = ai
+                        .next_waypoint(map, transform.position, target)
+                        .unwrap_or(target);
+                    movement.0 = (next - transform.position).clamp_length(PURSUE_MAX_STEP);
                 }
                 AiState::Wander(radius, speed) => {
-                    if let Some(entity_transform) = target_transform {
-                        if (transform.position - entity_transform.position).length() <= 5.0 {
-                            // Within range, begin pursuing.
-                            ai.set_state(AiState::Pursue);
-                            return;
-                        }
+                    let in_range = target_transform.is_some_and(|entity_transform| {
+                        (transform.position - entity_transform.position).length()
+                            < BasicAi::PURSUE_RANGE
+                    });
+
+                    if in_range && ai.try_set_state(AiState::Pursue) {
+                        return; // Within range, begin pursuing.
                     }
 
                     if movement.0 == Vec2f::ZERO {
-                        let vec_x = random_range(-radius..=radius);
-                        let vec_y = random_range(-radius..=radius);
+                        let Some(mut rng) = world.fetch_resource::<&mut Rng>() else {
+                            return; // Nothing to wander with.
+                        };
+
+                        let vec_x = rng.range(-radius, radius);
+                        let vec_y = rng.range(-radius, radius);
                         *movement = Movement(Vec2f(vec_x, vec_y), speed);
                     }
                 }
@@ -55,3 +74,137 @@ pub fn ai(world: &mut World) {
         },
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ecs::Entity;
+
+    /// World seeded with `seed` holding three wandering entities that are standing still.
+    fn wandering_world(seed: u64) -> World {
+        let mut world = World::new();
+        world.register_component::<Transform>();
+        world.register_component::<Movement>();
+        world.register_component::<LastTarget>();
+        world.register_component::<BasicAi>();
+        world.register_resource(Rng::new(seed));
+
+        for x in [0.0, 2.0, 4.0] {
+            let mut ai = BasicAi::new();
+            ai.set_state(AiState::Wander(3.0, 1));
+            world
+                .spawn_entity()
+                .attach(Transform::with_position(Vec2f(x, 0.0)))
+                .attach(Movement(Vec2f::ZERO, 1))
+                .attach(LastTarget(None))
+                .attach(ai)
+                .build();
+        }
+
+        world
+    }
+
+    /// Runs the AI once and obtains the movement of every entity in spawn order.
+    fn wander_once(seed: u64) -> Vec<Vec2f> {
+        let mut world = wandering_world(seed);
+        let map = WorldMap::new(Vec2f::ZERO, 20.0, 20.0);
+        ai(&mut world, &map);
+
+        let mut moved = vec![];
+        world.fetch_components(|_, movement: &Movement| moved.push(movement.0));
+        moved
+    }
+
+    #[test]
+    fn same_seed_wanders_the_same_way() {
+        let first = wander_once(42);
+        assert_eq!(first, wander_once(42));
+        assert_ne!(first, wander_once(43));
+
+        assert_eq!(first.len(), 3);
+        for movement in first {
+            assert_ne!(movement, Vec2f::ZERO);
+            assert!(movement.0.abs() <= 3.0 && movement.1.abs() <= 3.0);
+        }
+    }
+
+    /// World holding a wandering entity at the origin and the target it watches, in that order.
+    fn watching_world() -> (World, Entity, Entity) {
+        let mut world = wandering_world(7);
+        let target = world
+            .spawn_entity()
+            .attach(Transform::with_position(Vec2f(10.0, 0.0)))
+            .build();
+
+        let mut watcher = None;
+        world.fetch_components(|entity, transform: &Transform, _: &BasicAi| {
+            if transform.position == Vec2f::ZERO {
+                watcher = Some(entity);
+            }
+        });
+
+        let watcher = watcher.expect("no entity at the origin");
+        if let Some(mut last) = world.fetch_component::<&mut LastTarget>(watcher) {
+            last.0 = Some(target);
+        }
+        (world, watcher, target)
+    }
+
+    /// Moves the target to `x` and runs the AI once, obtaining whether the watcher pursues it.
+    fn pursues_at(world: &mut World, watcher: Entity, target: Entity, x: f32) -> bool {
+        if let Some(mut transform) = world.fetch_component::<&mut Transform>(target) {
+            transform.position = Vec2f(x, 0.0);
+        }
+
+        let map = WorldMap::new(Vec2f::ZERO, 20.0, 20.0);
+        ai(world, &map);
+        world
+            .fetch_component::<&BasicAi>(watcher)
+            .is_some_and(|ai| matches!(ai.state, AiState::Pursue))
+    }
+
+    #[test]
+    fn state_does_not_thrash_across_the_band() {
+        let (mut world, watcher, target) = watching_world();
+
+        // Jumping in and out of range every tick only switches once each dwell.
+        let ticks = BasicAi::MIN_DWELL * 4;
+        let states: Vec<bool> = (0..ticks)
+            .map(|tick| {
+                let x = if tick % 2 == 0 { 4.0 } else { 8.0 };
+                pursues_at(&mut world, watcher, target, x)
+            })
+            .collect();
+
+        let switches: Vec<usize> = (1..states.len())
+            .filter(|&tick| states[tick] != states[tick - 1])
+            .collect();
+        assert!(!switches.is_empty(), "state never changed");
+        for pair in switches.windows(2) {
+            assert!(
+                pair[1] - pair[0] >= BasicAi::MIN_DWELL as usize,
+                "{switches:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn pursuit_holds_between_the_ranges() {
+        let (mut world, watcher, target) = watching_world();
+        let entered =
+            (0..=BasicAi::MIN_DWELL).any(|_| pursues_at(&mut world, watcher, target, 4.0));
+        assert!(entered, "target in range was not pursued");
+
+        // Hovering past the pursue range, but within the escape range, keeps the pursuit.
+        for tick in 0..BasicAi::MIN_DWELL * 3 {
+            let x = if tick % 2 == 0 { 5.5 } else { 6.5 };
+            assert!(
+                pursues_at(&mut world, watcher, target, x),
+                "left at tick {tick}"
+            );
+        }
+
+        let left = (0..=BasicAi::MIN_DWELL).any(|_| !pursues_at(&mut world, watcher, target, 7.5));
+        assert!(left, "target beyond the escape range was still pursued");
+    }
+}
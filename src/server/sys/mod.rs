@@ -3,5 +3,5 @@ mod movement;
 mod spawn;
 
 pub use ai::ai;
-pub use movement::movement;
+pub use movement::{ArrivedEvent, CollisionEvent, movement};
 pub use spawn::spawn;
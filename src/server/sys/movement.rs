@@ -1,15 +1,30 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::server::ecs::{Entity, World};
 use crate::server::world_map::WorldMap;
 use crate::shared::node::Node2d;
 use crate::shared::payload::Movement;
-use crate::shared::shape::Rectangle;
+use crate::shared::shape::{Circle, Rectangle};
 use crate::shared::transform::Transform;
 use crate::utils::SpatialHash;
 use crate::vec2f::Vec2f;
 
-/// Moves entities in the world based on their movement components.
+/// Sent when an entity travels the whole of its movement. Entities stopped early by a wall, the
+/// edge of the map, or another entity have not arrived.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrivedEvent(pub Entity);
+
+/// Sent when entity `a` is stopped by running into entity `b`.
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionEvent {
+    pub a: Entity, // Entity that was moving.
+    pub b: Entity, // Entity it ran into.
+}
+
+/// Moves entities in the world based on their movement components, turning them to face the
+/// direction they move. Entities that stop keep facing the way they last moved. Sends an
+/// `ArrivedEvent` for each entity that travelled the whole of its movement this tick, and a
+/// `CollisionEvent` for each entity stopped by another.
 pub fn movement(
     world: &mut World,
     map: &WorldMap,
@@ -17,52 +32,93 @@ pub fn movement(
     tick_rate: f32,
 ) -> HashSet<Entity> {
     let mut moved = HashSet::new();
+    let mut arrived = Vec::new();
+    let mut collisions = Vec::new();
+    let mut positions = Vec::new();
+
+    // Build every node once per tick instead of once per collision check. Rotated bodies
+    // collide with each other as oriented boxes.
+    let mut nodes: HashMap<Entity, Node2d> = world
+        .iter::<(&Rectangle, &Transform)>()
+        .map(|(entity, geometry, transform)| (entity, Node2d::from((*geometry, *transform))))
+        .collect();
+    nodes.extend(
+        world
+            .iter::<(&Circle, &Transform)>()
+            .map(|(entity, geometry, transform)| (entity, Node2d::from((*geometry, *transform)))),
+    );
 
     world.fetch_components(
-        |entity: Entity,
-         geometry: &Rectangle,
-         transform: &mut Transform,
-         movement: &mut Movement| {
+        |entity: Entity, transform: &Transform, movement: &mut Movement| {
             let Movement(ref mut velocity, speed) = *movement;
 
             if *velocity == Vec2f::ZERO {
                 return; // No movement required.
             }
 
+            let Some(mut node) = nodes.get(&entity).copied() else {
+                return; // Only entities with geometry can move.
+            };
+
             let old_pos = transform.position;
             let mut new_pos = transform.position;
+            let rotation = velocity.angle(); // Face the direction of travel.
             let speed_delta = f32::from(speed.clamp(1, 3));
             let travel = speed_delta * tick_rate;
 
-            if travel >= velocity.length() {
+            let mut completed = travel >= velocity.length();
+            if completed {
                 // Step distance is smaller than travel requirements.
                 new_pos += *velocity;
                 *velocity = Vec2f::ZERO;
             } else {
                 // Move the position using the velocity.
                 let direction = velocity.normalized();
-                let disp = direction.scale(travel);
+                let disp = direction * travel;
                 new_pos += disp;
                 *velocity -= disp;
             }
 
-            // Ensure the position remains within the map.
-            new_pos = map.clamp_bounds(new_pos);
-            let node = Node2d::from((*geometry, Transform::with_position(new_pos)));
+            // Ensure the position remains within the map, hitting the edge stops the movement.
+            let (clamped, hit_edge) = map.clamp_bounds_checked(new_pos);
+            new_pos = clamped;
+            if hit_edge {
+                *velocity = Vec2f::ZERO;
+                completed = false;
+            }
+
+            node.set_position(new_pos);
+            node.set_rotation(rotation);
+
+            // Terrain is tested against the unrotated footprint, so turning never catches on walls.
+            let mut footprint = node;
+            footprint.set_rotation(0.0);
+            if map.is_area_blocked(footprint.aabb()) {
+                // Moving into impassable terrain, treated the same as a collision.
+                *velocity = Vec2f::ZERO;
+                new_pos = old_pos;
+                completed = false;
+            }
 
             // Check nearby entities at the new position.
             let entities = gps.query(new_pos, 2.0);
-            for (other, other_pos) in entities.iter().map(|(e, p)| (Entity::from(*e), *p)) {
+            for (other, other_pos) in entities.iter().map(|(e, p)| (world.resolve(*e), *p)) {
                 if other == entity {
                     continue;
                 }
 
-                let other_transform = Transform::with_position(*other_pos);
-                let other_node = Node2d::from((*geometry, other_transform));
+                let other_node = nodes.get(&other).copied().unwrap_or_else(|| {
+                    Node2d::from((*node.geometry(), Transform::with_position(*other_pos)))
+                });
                 if node.intersects(&other_node) {
                     // Collision detected.
+                    collisions.push(CollisionEvent {
+                        a: entity,
+                        b: other,
+                    });
                     *velocity = Vec2f::ZERO; // Stop movement.
                     new_pos = old_pos; // Revert to old position.
+                    completed = false;
                     break;
                 }
             }
@@ -71,12 +127,175 @@ pub fn movement(
             if old_pos == new_pos {
                 *velocity = Vec2f::ZERO;
             } else {
-                transform.position = new_pos;
-                gps.insert(transform.position, entity.into());
+                positions.push((entity, new_pos, rotation));
+                gps.insert(new_pos, entity.into());
+                nodes.insert(entity, node);
                 moved.insert(entity);
             }
+
+            if completed {
+                arrived.push(ArrivedEvent(entity));
+            }
         },
     );
 
+    // Only moved entities access their transform mutably, leaving the rest unchanged.
+    for (entity, position, rotation) in positions {
+        if let Some(mut transform) = world.fetch_component::<&mut Transform>(entity) {
+            transform.position = position;
+            transform.rotation = rotation;
+        }
+    }
+
+    for event in arrived {
+        world.send_event(event);
+    }
+
+    for event in collisions {
+        world.send_event(event);
+    }
+
     moved
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10x10 map whose cells span `(0, 0)` to `(10, 10)`.
+    fn open_map() -> WorldMap {
+        WorldMap::new(Vec2f(5.0, 5.0), 10.0, 10.0)
+    }
+
+    /// World with a single 1x1 entity at `position` moving by `velocity`.
+    fn world_with_mover(position: Vec2f, velocity: Vec2f) -> (World, Entity) {
+        let mut world = World::new();
+        world.register_component::<Transform>();
+        world.register_component::<Rectangle>();
+        world.register_component::<Circle>();
+        world.register_component::<Movement>();
+        let entity = world
+            .spawn_entity()
+            .attach(Transform::with_position(position))
+            .attach(Rectangle::new(1.0, 1.0))
+            .attach(Movement(velocity, 1))
+            .build();
+        (world, entity)
+    }
+
+    fn position(world: &World, entity: Entity) -> Vec2f {
+        world
+            .fetch_component::<&Transform>(entity)
+            .map(|transform| transform.position)
+            .unwrap()
+    }
+
+    fn rotation(world: &World, entity: Entity) -> f32 {
+        world
+            .fetch_component::<&Transform>(entity)
+            .map(|transform| transform.rotation)
+            .unwrap()
+    }
+
+    fn velocity(world: &World, entity: Entity) -> Vec2f {
+        world
+            .fetch_component::<&Movement>(entity)
+            .map(|movement| movement.0)
+            .unwrap()
+    }
+
+    #[test]
+    fn wall_stops_movement_at_its_edge() {
+        let mut map = open_map();
+        for row in 0..10u8 {
+            map.set_blocked(Vec2f(5.5, f32::from(row) + 0.5), true);
+        }
+
+        let (mut world, entity) = world_with_mover(Vec2f(1.0, 2.0), Vec2f(6.0, 0.0));
+        let mut gps = SpatialHash::new(1.0);
+        for _ in 0..6 {
+            movement(&mut world, &map, &mut gps, 1.0);
+        }
+
+        // Flush against the wall, with the rest of the movement dropped.
+        assert_eq!(position(&world, entity), Vec2f(4.0, 2.0));
+        assert_eq!(velocity(&world, entity), Vec2f::ZERO);
+        assert!(world.drain_events::<ArrivedEvent>().is_empty());
+    }
+
+    #[test]
+    fn turned_body_does_not_catch_on_walls() {
+        let mut map = open_map();
+        for row in 0..10u8 {
+            map.set_blocked(Vec2f(5.5, f32::from(row) + 0.5), true);
+        }
+
+        // Flush against the wall and moving mostly along it. A body rotated to face the
+        // movement would reach into the wall.
+        let velocity = Vec2f(-0.1, 1.0);
+        let (mut world, entity) = world_with_mover(Vec2f(4.0, 2.0), velocity);
+        let mut gps = SpatialHash::new(1.0);
+        movement(&mut world, &map, &mut gps, 1.0);
+
+        assert!(
+            position(&world, entity).1 > 2.9,
+            "{:?}",
+            position(&world, entity)
+        );
+        assert!((rotation(&world, entity) - velocity.angle()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rotated_bodies_collide_as_oriented_boxes() {
+        // A thin bar running diagonally from (3.5, 1.5), its bounding box covers the mover's path.
+        let bar = |world: &mut World, gps: &mut SpatialHash| {
+            let transform = Transform {
+                rotation: std::f32::consts::FRAC_PI_4,
+                ..Transform::with_position(Vec2f(3.5, 1.5))
+            };
+            let bar = world
+                .spawn_entity()
+                .attach(transform)
+                .attach(Rectangle::new(4.0, 0.2))
+                .build();
+            gps.insert(transform.position, bar.into());
+            bar
+        };
+
+        // Turned to face the bar, the mover stays clear of it beside its bounding box.
+        let (mut world, entity) = world_with_mover(Vec2f(4.8, 1.2), Vec2f(0.1, 0.1));
+        let mut gps = SpatialHash::new(1.0);
+        bar(&mut world, &mut gps);
+        movement(&mut world, &open_map(), &mut gps, 1.0);
+        assert!(position(&world, entity).distance_squared(Vec2f(4.9, 1.3)) < 1e-9);
+        assert!(world.drain_events::<CollisionEvent>().is_empty());
+
+        // Unrotated it would stay clear of the bar, turned it reaches into it.
+        let (mut world, entity) = world_with_mover(Vec2f(4.9, 1.6), Vec2f(0.1, 0.1));
+        let mut gps = SpatialHash::new(1.0);
+        let bar = bar(&mut world, &mut gps);
+        movement(&mut world, &open_map(), &mut gps, 1.0);
+        assert_eq!(position(&world, entity), Vec2f(4.9, 1.6));
+        let collisions = world.drain_events::<CollisionEvent>();
+        assert_eq!(collisions.len(), 1);
+        assert_eq!((collisions[0].a, collisions[0].b), (entity, bar));
+    }
+
+    #[test]
+    fn stopped_entities_keep_their_heading() {
+        let (mut world, entity) = world_with_mover(Vec2f(1.0, 2.0), Vec2f(0.0, 1.0));
+        let mut gps = SpatialHash::new(1.0);
+        movement(&mut world, &open_map(), &mut gps, 1.0);
+        assert_eq!(velocity(&world, entity), Vec2f::ZERO);
+        assert_eq!(
+            world.drain_events::<ArrivedEvent>(),
+            vec![ArrivedEvent(entity)]
+        );
+
+        let heading = Vec2f(0.0, 1.0).angle();
+        assert!((rotation(&world, entity) - heading).abs() < 1e-6);
+
+        movement(&mut world, &open_map(), &mut gps, 1.0);
+        assert!((rotation(&world, entity) - heading).abs() < 1e-6);
+    }
+}
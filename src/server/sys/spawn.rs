@@ -14,6 +14,7 @@ pub fn spawn(world: &mut World, map: &WorldMap) -> HashSet<Entity> {
     let mut spawned = HashSet::new();
 
     world.fetch_components(|entity, transform: &Transform, spawner: &mut Spawner| {
+        spawner.prune(|child| world.is_alive(child));
         if spawner.at_capacity() || !spawner.is_ready() {
             return;
         }
@@ -44,3 +45,64 @@ pub fn spawn(world: &mut World, map: &WorldMap) -> HashSet<Entity> {
 
     spawned
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::server::core::ServerCore;
+
+    const COOLDOWN: Duration = Duration::from_millis(20);
+
+    /// World with a spawner of at most two entities, spawning every `COOLDOWN`.
+    fn world_with_spawner() -> (World, Entity) {
+        let mut world = ServerCore::create_world();
+        let spawner = world
+            .spawn_entity()
+            .attach(Transform::with_position(Vec2f(5.0, 5.0)))
+            .attach(Spawner::new(2, 1.0, COOLDOWN.as_secs_f32()))
+            .build();
+        (world, spawner)
+    }
+
+    /// Waits out the cooldown, then runs the spawn system.
+    fn spawn_after_cooldown(world: &mut World, map: &WorldMap) -> HashSet<Entity> {
+        sleep(COOLDOWN * 2);
+        spawn(world, map)
+    }
+
+    #[test]
+    fn spawning_stops_at_the_max_and_resumes_after_a_death() {
+        let map = WorldMap::new(Vec2f(5.0, 5.0), 10.0, 10.0);
+        let (mut world, spawner) = world_with_spawner();
+
+        let first = spawn_after_cooldown(&mut world, &map);
+        assert_eq!(first.len(), 1);
+        assert!(
+            spawn(&mut world, &map).is_empty(),
+            "spawned during the cooldown"
+        );
+
+        assert_eq!(spawn_after_cooldown(&mut world, &map).len(), 1);
+        assert!(
+            spawn_after_cooldown(&mut world, &map).is_empty(),
+            "spawned over the max"
+        );
+
+        // A death frees a slot, but the replacement waits for the cooldown.
+        let child = *first.iter().next().unwrap();
+        world.kill_entity(child);
+        assert!(
+            spawn(&mut world, &map).is_empty(),
+            "replaced without a cooldown"
+        );
+
+        let replacement = spawn_after_cooldown(&mut world, &map);
+        assert_eq!(replacement.len(), 1);
+        let replacement = *replacement.iter().next().unwrap();
+        let owner = world.fetch_component::<&Owner>(replacement).unwrap();
+        assert_eq!(owner.0, spawner);
+    }
+}
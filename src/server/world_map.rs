@@ -1,8 +1,17 @@
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
 use crate::{shared::box_2d::Box2D, vec2f::Vec2f};
 
 /// Simple implementation of the game world map.
 pub(crate) struct WorldMap {
     bounds: Box2D,
+    columns: usize,           // Number of cells along the x-axis.
+    rows: usize,              // Number of cells along the y-axis.
+    blocked: Vec<bool>,       // Impassable cells, indexed by `row * columns + column`.
+    spawn_points: Vec<Vec2f>, // Points that new players are spawned at in turn.
+    next_spawn: Cell<usize>,  // Index of the spawn point to use next.
 }
 
 impl WorldMap {
@@ -11,7 +20,21 @@ impl WorldMap {
         let mut bounds = Box2D::new(Vec2f::ZERO, x_width, y_length);
         bounds.center_on(center);
 
-        Self { bounds }
+        // Cells are one unit in size, partial cells along the edges are included.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (columns, rows) = (
+            (x_width.ceil() as usize).max(1),
+            (y_length.ceil() as usize).max(1),
+        );
+
+        Self {
+            bounds,
+            columns,
+            rows,
+            blocked: vec![false; columns * rows],
+            spawn_points: Vec::new(),
+            next_spawn: Cell::new(0),
+        }
     }
 
     /// Gets the spawn point for new entities in the world.
@@ -19,6 +42,26 @@ impl WorldMap {
         self.bounds.center()
     }
 
+    /// Adds a point that new players can be spawned at. Points out of bounds are ignored.
+    pub fn add_spawn_point(&mut self, point: Vec2f) {
+        if self.in_bounds(point) {
+            self.spawn_points.push(point);
+        }
+    }
+
+    /// Gets the spawn point for the next player, cycling through the added spawn points in order
+    /// so players do not spawn on top of each other. Falls back to `spawn_point` if none were
+    /// added.
+    pub fn next_spawn_point(&self) -> Vec2f {
+        if self.spawn_points.is_empty() {
+            return *self.spawn_point();
+        }
+
+        let idx = self.next_spawn.get() % self.spawn_points.len();
+        self.next_spawn.set(idx + 1);
+        self.spawn_points[idx]
+    }
+
     /// Checks if the given position is within the bounds of the world map.
     pub fn in_bounds(&self, pos: Vec2f) -> bool {
         self.bounds.contains(pos)
@@ -28,4 +71,198 @@ impl WorldMap {
     pub fn clamp_bounds(&self, pos: Vec2f) -> Vec2f {
         self.bounds.clamp(pos)
     }
+
+    /// Clamps the position to be within the map bounds, and whether it was outside of them.
+    pub fn clamp_bounds_checked(&self, pos: Vec2f) -> (Vec2f, bool) {
+        self.bounds.clamp_checked(pos)
+    }
+
+    /// Marks the cell containing the position as impassable or clears it. Positions out of bounds
+    /// are ignored.
+    pub fn set_blocked(&mut self, cell: Vec2f, blocked: bool) {
+        if let Some(cell) = self.cell(cell) {
+            self.blocked[cell] = blocked;
+        }
+    }
+
+    /// Checks if the cell containing the position is impassable.
+    pub fn is_blocked(&self, pos: Vec2f) -> bool {
+        self.cell(pos).is_some_and(|cell| self.blocked[cell])
+    }
+
+    /// Checks if any cell overlapped by the area between the top-left and bottom-right corners is
+    /// impassable. An area ending exactly on a cell edge does not overlap the next cell.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn is_area_blocked(&self, (min, max): (Vec2f, Vec2f)) -> bool {
+        let (min, max) = (min - self.bounds.position, max - self.bounds.position);
+        let first_column = (min.0.floor().max(0.0) as usize).min(self.columns - 1);
+        let first_row = (min.1.floor().max(0.0) as usize).min(self.rows - 1);
+        let last_column = ((max.0.ceil() - 1.0).max(0.0) as usize).min(self.columns - 1);
+        let last_row = ((max.1.ceil() - 1.0).max(0.0) as usize).min(self.rows - 1);
+
+        (first_row..=last_row).any(|row| {
+            (first_column..=last_column).any(|column| self.blocked[row * self.columns + column])
+        })
+    }
+
+    /// Obtains the index of the cell containing the position. None if out of bounds.
+    pub fn cell(&self, pos: Vec2f) -> Option<usize> {
+        if !self.in_bounds(pos) {
+            return None;
+        }
+
+        let offset = pos - self.bounds.position;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (column, row) = (offset.0.floor() as usize, offset.1.floor() as usize);
+
+        // Positions on the far edges belong to the last cell.
+        Some(row.min(self.rows - 1) * self.columns + column.min(self.columns - 1))
+    }
+
+    /// Obtains the center of the cell.
+    #[allow(clippy::cast_precision_loss)]
+    fn cell_center(&self, cell: usize) -> Vec2f {
+        let (column, row) = (cell % self.columns, cell / self.columns);
+        self.bounds.position + Vec2f(column as f32 + 0.5, row as f32 + 0.5)
+    }
+
+    /// Cells that share an edge with the cell.
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = usize> {
+        let (column, row) = (cell % self.columns, cell / self.columns);
+        [
+            (column > 0).then(|| cell - 1),
+            (column + 1 < self.columns).then(|| cell + 1),
+            (row > 0).then(|| cell - self.columns),
+            (row + 1 < self.rows).then(|| cell + self.columns),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Estimated number of steps between two cells, never more than the actual distance.
+    fn heuristic(&self, from: usize, to: usize) -> usize {
+        let (from_column, from_row) = (from % self.columns, from / self.columns);
+        let (to_column, to_row) = (to % self.columns, to / self.columns);
+        from_column.abs_diff(to_column) + from_row.abs_diff(to_row)
+    }
+
+    /// Finds a path between two positions that avoids blocked cells using A*. The waypoints are
+    /// the centers of each cell to step through, ending with `to` itself. None if either
+    /// position is out of bounds, the destination is blocked, or no path exists.
+    pub fn path(&self, from: Vec2f, to: Vec2f) -> Option<Vec<Vec2f>> {
+        let (start, goal) = (self.cell(from)?, self.cell(to)?);
+        if self.blocked[goal] {
+            return None;
+        } else if start == goal {
+            return Some(vec![to]);
+        }
+
+        // Every buffer is bounded by the number of cells in the grid.
+        let mut cost = vec![usize::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+
+        cost[start] = 0;
+        open.push(Reverse((self.heuristic(start, goal), start)));
+
+        while let Some(Reverse((_, current))) = open.pop() {
+            if current == goal {
+                break;
+            }
+
+            for next in self.neighbours(current) {
+                let next_cost = cost[current] + 1;
+                if self.blocked[next] || next_cost >= cost[next] {
+                    continue;
+                }
+
+                cost[next] = next_cost;
+                came_from[next] = current;
+                open.push(Reverse((next_cost + self.heuristic(next, goal), next)));
+            }
+        }
+
+        if came_from[goal] == usize::MAX {
+            return None; // Goal was never reached.
+        }
+
+        // Walk back from the goal, the start cell is already occupied and is left out.
+        let mut waypoints = vec![to];
+        let mut current = came_from[goal];
+        while current != start {
+            waypoints.push(self.cell_center(current));
+            current = came_from[current];
+        }
+
+        waypoints.reverse();
+        Some(waypoints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 5x5 map whose cells span `(0, 0)` to `(5, 5)`, with a wall down column 2 that leaves only
+    /// the bottom row open.
+    fn walled_map() -> WorldMap {
+        let mut map = WorldMap::new(Vec2f(2.5, 2.5), 5.0, 5.0);
+        for row in [0.5, 1.5, 2.5, 3.5] {
+            map.set_blocked(Vec2f(2.5, row), true);
+        }
+        map
+    }
+
+    #[test]
+    fn path_routes_around_a_wall() {
+        let map = walled_map();
+        let (from, to) = (Vec2f(0.5, 0.5), Vec2f(4.2, 0.3));
+        let path = map.path(from, to).expect("no path around the wall");
+
+        // Down to the gap, across, and back up, one cell at a time.
+        assert_eq!(path.len(), 12);
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.contains(&Vec2f(2.5, 4.5)), "path did not use the gap");
+
+        let mut previous = from;
+        for waypoint in &path[..path.len() - 1] {
+            assert!(
+                !map.is_blocked(*waypoint),
+                "path crosses the wall at {waypoint:?}"
+            );
+            let step = previous.distance(*waypoint);
+            assert!((step - 1.0).abs() < f32::EPSILON, "path skips a cell");
+            assert_eq!(
+                (waypoint.0.fract(), waypoint.1.fract()),
+                (0.5, 0.5),
+                "not a cell center"
+            );
+            previous = *waypoint;
+        }
+    }
+
+    #[test]
+    fn sealed_wall_has_no_path() {
+        let mut map = walled_map();
+        map.set_blocked(Vec2f(2.5, 4.5), true);
+        assert_eq!(map.path(Vec2f(0.5, 0.5), Vec2f(4.5, 0.5)), None);
+        assert_eq!(map.path(Vec2f(0.5, 0.5), Vec2f(2.5, 0.5)), None);
+    }
+
+    #[test]
+    fn spawn_points_are_used_in_turn() {
+        let mut map = WorldMap::new(Vec2f(5.0, 5.0), 10.0, 10.0);
+        assert_eq!(map.next_spawn_point(), Vec2f(5.0, 5.0));
+
+        let points = [Vec2f(1.0, 1.0), Vec2f(8.0, 1.0), Vec2f(8.0, 8.0)];
+        for point in points {
+            map.add_spawn_point(point);
+        }
+        map.add_spawn_point(Vec2f(20.0, 20.0)); // Out of bounds, ignored.
+
+        let used: Vec<Vec2f> = (0..7).map(|_| map.next_spawn_point()).collect();
+        assert_eq!(used[..3], points);
+        assert_eq!(used[3..6], points);
+        assert_eq!(used[6], points[0]);
+    }
 }
@@ -53,9 +53,53 @@ impl Box2D {
     /// Restricts a position to within the bounds of the box.
     /// Ensures the returned point is always within the box, even if the input point is outside.
     pub fn clamp(&self, point: Vec2f) -> Vec2f {
+        self.clamp_checked(point).0
+    }
+
+    /// Restricts a position to within the bounds of the box, and whether either axis had to be
+    /// clamped. Points on the edge of the box are within it and are not clamped.
+    pub fn clamp_checked(&self, point: Vec2f) -> (Vec2f, bool) {
         let x = point.0.clamp(self.position.0, self.max_x());
         let y = point.1.clamp(self.position.1, self.max_y());
 
-        Vec2f(x, y)
+        (Vec2f(x, y), !self.contains(point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Box spanning `(0, 0)` to `(10, 5)`.
+    fn area() -> Box2D {
+        Box2D::new(Vec2f::ZERO, 10.0, 5.0)
+    }
+
+    #[test]
+    fn points_inside_or_on_the_edge_are_not_clamped() {
+        for point in [
+            Vec2f(3.0, 2.0),
+            Vec2f::ZERO,
+            Vec2f(10.0, 5.0),
+            Vec2f(10.0, 1.0),
+        ] {
+            assert_eq!(area().clamp_checked(point), (point, false), "{point:?}");
+        }
+    }
+
+    #[test]
+    fn points_outside_are_clamped_on_each_axis() {
+        let cases = [
+            (Vec2f(-4.0, 2.0), Vec2f(0.0, 2.0)),
+            (Vec2f(25.0, 2.0), Vec2f(10.0, 2.0)),
+            (Vec2f(3.0, -1.0), Vec2f(3.0, 0.0)),
+            (Vec2f(3.0, 9.0), Vec2f(3.0, 5.0)),
+            (Vec2f(-1.0, 9.0), Vec2f(0.0, 5.0)),
+        ];
+
+        for (point, clamped) in cases {
+            assert_eq!(area().clamp_checked(point), (clamped, true), "{point:?}");
+            assert_eq!(area().clamp(point), clamped);
+        }
     }
 }
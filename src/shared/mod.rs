@@ -2,4 +2,5 @@ pub mod box_2d;
 pub mod node;
 pub mod payload;
 pub mod shape;
+pub mod snapshot;
 pub mod transform;
@@ -1,49 +1,292 @@
 use crate::vec2f::Vec2f;
 
-use super::shape::Rectangle;
+use super::shape::Shape;
 use super::transform::Transform;
 
-/// Represents a node in a 2D space with geometry and transformation.
+/// Represents a node in a 2D space with geometry and transformation. The axis-aligned bounding
+/// box is computed once on creation so repeated intersection checks do not recompute it.
+/// Rectangles with a rotation are rotated about the transform's origin and tested as oriented
+/// boxes, while unrotated rectangles keep the cheaper axis-aligned test.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Node2d {
-    pub geometry: Rectangle,  // Geometry of the node.
-    pub transform: Transform, // Transformation applied to the node.
+    geometry: Shape,             // Geometry of the node.
+    transform: Transform,        // Transformation applied to the node.
+    min: Vec2f,                  // Cached top-left corner of the bounding box.
+    max: Vec2f,                  // Cached bottom-right corner of the bounding box.
+    corners: Option<[Vec2f; 4]>, // Cached corners of a rotated rectangle, in winding order.
 }
 
 impl Node2d {
+    /// Geometry of the node.
+    pub fn geometry(&self) -> &Shape {
+        &self.geometry
+    }
+
+    /// Transformation applied to the node.
+    #[allow(dead_code)]
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Top-left and bottom-right corners of the bounding box.
+    #[allow(dead_code)]
+    pub fn aabb(&self) -> (Vec2f, Vec2f) {
+        (self.min, self.max)
+    }
+
+    /// Moves the node to a new position, updating the cached bounding box.
+    pub fn set_position(&mut self, position: Vec2f) {
+        self.transform.position = position;
+        self.update_aabb();
+    }
+
+    /// Rotates the node to a new rotation in radians, updating the cached bounding box.
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.transform.rotation = rotation;
+        self.update_aabb();
+    }
+
+    /// Center of the node and half of its scaled size, before any rotation.
+    fn extents(&self) -> (Vec2f, Vec2f) {
+        let size = match self.geometry {
+            Shape::Rectangle(rect) => Vec2f(rect.width, rect.height),
+            Shape::Circle(circle) => Vec2f(circle.radius * 2.0, circle.radius * 2.0),
+        };
+
+        let half = Vec2f(
+            size.0 * self.transform.scale.0,
+            size.1 * self.transform.scale.1,
+        ) / 2.0;
+        (self.transform.position + half, half)
+    }
+
+    /// Point the node rotates about, its origin relative to its position.
+    fn pivot(&self) -> Vec2f {
+        self.transform.position + self.transform.origin
+    }
+
+    /// Recomputes the bounding box from the geometry and transformation.
+    fn update_aabb(&mut self) {
+        let (center, half) = self.extents();
+        self.min = center - half;
+        self.max = center + half;
+        self.corners = None;
+
+        let rotation = self.transform.rotation;
+        if rotation == 0.0 || matches!(self.geometry, Shape::Circle(_)) {
+            return; // Axis-aligned, or a circle which looks the same at any rotation.
+        }
+
+        // Rotate the corners about the pivot, the bounding box then encloses them.
+        let pivot = self.pivot();
+        let corners = [
+            Vec2f(-half.0, -half.1),
+            Vec2f(half.0, -half.1),
+            half,
+            Vec2f(-half.0, half.1),
+        ]
+        .map(|corner| pivot + (center + corner - pivot).rotate(rotation));
+
+        self.min = corners[0];
+        self.max = corners[0];
+        for corner in corners {
+            self.min = Vec2f(self.min.0.min(corner.0), self.min.1.min(corner.1));
+            self.max = Vec2f(self.max.0.max(corner.0), self.max.1.max(corner.1));
+        }
+        self.corners = Some(corners);
+    }
+
+    /// Corners of the rectangle in winding order, rotated if the node has a rotation.
+    fn corners(&self) -> [Vec2f; 4] {
+        self.corners.unwrap_or([
+            self.min,
+            Vec2f(self.max.0, self.min.1),
+            self.max,
+            Vec2f(self.min.0, self.max.1),
+        ])
+    }
+
+    /// Center and radius of the node if it is a circle. The circle is centered within its
+    /// bounding box and uses the larger of the two scales.
+    fn circle(&self) -> Option<(Vec2f, f32)> {
+        let Shape::Circle(circle) = self.geometry else {
+            return None;
+        };
+
+        let scale = self.transform.scale.0.max(self.transform.scale.1);
+        Some((self.min.lerp(self.max, 0.5), circle.radius * scale))
+    }
+
     /// Detects if the node intersects with another node.
     pub fn intersects(&self, other: &Self) -> bool {
-        // Compute AABB for self.
-        let a_min = self.transform.position; // top-left
-        let a_max = a_min
-            + Vec2f(
-                self.geometry.width * self.transform.scale.0,
-                self.geometry.height * self.transform.scale.1,
-            ); // bottom-right
-
-        // Compute AABB for other.
-        let b_min = other.transform.position;
-        let b_max = b_min
-            + Vec2f(
-                other.geometry.width * other.transform.scale.0,
-                other.geometry.height * other.transform.scale.1,
-            );
+        match (self.circle(), other.circle()) {
+            (Some((center, radius)), Some((other_center, other_radius))) => {
+                // Touching counts as overlap.
+                center.distance_squared(other_center) <= (radius + other_radius).powi(2)
+            }
+            (Some((center, radius)), None) => other.intersects_circle(center, radius),
+            (None, Some((center, radius))) => self.intersects_circle(center, radius),
+            (None, None) if self.corners.is_none() && other.corners.is_none() => {
+                self.intersects_aabb(other)
+            }
+            (None, None) => self.intersects_aabb(other) && self.intersects_obb(other),
+        }
+    }
 
+    /// Detects if the bounding boxes of both nodes intersect.
+    fn intersects_aabb(&self, other: &Self) -> bool {
         // If one is strictly to the left of the other, no overlap
-        if a_min.0 > b_max.0 || b_min.0 > a_max.0 {
+        if self.min.0 > other.max.0 || other.min.0 > self.max.0 {
             return false;
         }
 
         // On the Y axis, allow equality (touching counts as overlap).
-        a_min.1 <= b_max.1 && b_min.1 <= a_max.1
+        self.min.1 <= other.max.1 && other.min.1 <= self.max.1
+    }
+
+    /// Detects if the rectangles of both nodes intersect using the separating axis test, where
+    /// the edge normals of both rectangles are the only candidate axes.
+    fn intersects_obb(&self, other: &Self) -> bool {
+        let (corners, other_corners) = (self.corners(), other.corners());
+        let project = |corners: &[Vec2f; 4], axis: Vec2f| {
+            corners
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), corner| {
+                    let dist = corner.dot(axis);
+                    (min.min(dist), max.max(dist))
+                })
+        };
+
+        [corners, other_corners]
+            .iter()
+            .flat_map(|corners| [corners[1] - corners[0], corners[3] - corners[0]])
+            .all(|axis| {
+                let (min, max) = project(&corners, axis);
+                let (other_min, other_max) = project(&other_corners, axis);
+                // Touching counts as overlap.
+                min <= other_max && other_min <= max
+            })
+    }
+
+    /// Detects if the rectangle of the node intersects with a circle.
+    fn intersects_circle(&self, center: Vec2f, radius: f32) -> bool {
+        // Work in the rectangle's frame, where it is axis-aligned about the origin.
+        let (box_center, half) = self.extents();
+        let mut center = center;
+        if self.corners.is_some() {
+            let pivot = self.pivot();
+            center = pivot + (center - pivot).rotate(-self.transform.rotation);
+        }
+        let local = center - box_center;
+
+        // Closest point of the box to the center of the circle.
+        let closest = Vec2f(
+            local.0.clamp(-half.0, half.0),
+            local.1.clamp(-half.1, half.1),
+        );
+
+        closest.distance_squared(local) <= radius * radius
     }
 }
 
-impl From<(Rectangle, Transform)> for Node2d {
-    fn from((geometry, transform): (Rectangle, Transform)) -> Self {
-        Self {
-            geometry,
+impl<S: Into<Shape>> From<(S, Transform)> for Node2d {
+    fn from((geometry, transform): (S, Transform)) -> Self {
+        let mut node = Self {
+            geometry: geometry.into(),
             transform,
-        }
+            min: Vec2f::ZERO,
+            max: Vec2f::ZERO,
+            corners: None,
+        };
+
+        node.update_aabb();
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    use super::*;
+    use crate::shared::shape::{Circle, Rectangle};
+
+    /// Node for a circle of `radius` centered on `center`.
+    fn circle(center: Vec2f, radius: f32) -> Node2d {
+        let position = center - Vec2f(radius, radius);
+        Node2d::from((Circle::new(radius), Transform::with_position(position)))
+    }
+
+    /// Node for a rectangle with its top-left at `position`.
+    fn rectangle(position: Vec2f, width: f32, height: f32) -> Node2d {
+        Node2d::from((
+            Rectangle::new(width, height),
+            Transform::with_position(position),
+        ))
+    }
+
+    #[test]
+    fn circle_overlapping_a_rectangle_corner() {
+        let rect = rectangle(Vec2f::ZERO, 2.0, 2.0);
+        let near = circle(Vec2f(2.6, 2.6), 1.0);
+        assert!(rect.intersects(&near));
+        assert!(near.intersects(&rect));
+    }
+
+    #[test]
+    fn circle_just_missing_a_rectangle_corner() {
+        // The bounding boxes overlap, but the corner is outside of the circle.
+        let rect = rectangle(Vec2f::ZERO, 2.0, 2.0);
+        let far = circle(Vec2f(2.8, 2.8), 1.0);
+        assert!(!rect.intersects(&far));
+        assert!(!far.intersects(&rect));
+    }
+
+    #[test]
+    fn circles_touching_overlap() {
+        let a = circle(Vec2f::ZERO, 1.0);
+        assert!(a.intersects(&circle(Vec2f(2.0, 0.0), 1.0)));
+        assert!(!a.intersects(&circle(Vec2f(2.1, 0.0), 1.0)));
+    }
+
+    /// Node for a rectangle with its top-left at `position`, rotated about `origin`.
+    fn rotated(position: Vec2f, size: Vec2f, origin: Vec2f, rotation: f32) -> Node2d {
+        let transform = Transform {
+            origin,
+            rotation,
+            ..Transform::with_position(position)
+        };
+        Node2d::from((Rectangle::new(size.0, size.1), transform))
+    }
+
+    #[test]
+    fn rotated_rectangle_overlaps_a_neighbour() {
+        let small = rectangle(Vec2f(2.2, 0.8), 0.4, 0.4);
+        assert!(!rectangle(Vec2f::ZERO, 2.0, 2.0).intersects(&small));
+
+        // Turned about its center, a corner reaches past its unrotated edge.
+        let turned = rotated(Vec2f::ZERO, Vec2f(2.0, 2.0), Vec2f(1.0, 1.0), FRAC_PI_4);
+        assert!(turned.intersects(&small));
+        assert!(small.intersects(&turned));
+    }
+
+    #[test]
+    fn rotated_rectangle_clears_its_bounding_box_corner() {
+        let turned = rotated(Vec2f::ZERO, Vec2f(2.0, 2.0), Vec2f(1.0, 1.0), FRAC_PI_4);
+        let corner = rectangle(Vec2f(2.0, 2.0), 0.3, 0.3);
+        assert!(!turned.intersects(&corner));
+        assert!(!corner.intersects(&turned));
+        assert!(!turned.intersects(&circle(Vec2f(2.2, 2.2), 0.3)));
+    }
+
+    #[test]
+    fn rectangles_rotate_about_their_origin() {
+        let turned = rotated(Vec2f::ZERO, Vec2f(2.0, 2.0), Vec2f::ZERO, FRAC_PI_2);
+        let (min, max) = turned.aabb();
+        assert!(min.distance_squared(Vec2f(-2.0, 0.0)) < 1e-9, "{min:?}");
+        assert!(max.distance_squared(Vec2f(0.0, 2.0)) < 1e-9, "{max:?}");
+
+        assert!(turned.intersects(&circle(Vec2f(-1.0, 1.0), 0.1)));
+        assert!(!turned.intersects(&circle(Vec2f(1.0, 1.0), 0.1)));
     }
 }
@@ -1,14 +1,17 @@
-use crate::net::traits::{NetDecoder, NetEncoder};
+use crate::net::error::{NetError, Result};
+use crate::net::traits::{NetDecoder, NetEncoder, VersionedPayload};
 use crate::vec2f::Vec2f;
 use netcode_derive::{NetDecode, NetEncode};
 
 #[repr(u8)]
 pub enum PayloadId {
     Connect = 0x06,
-    State,
-    Position,
-    Movement,
-    Unknown,
+    State = 0x07,
+    Movement = 0x09, // 0x08 was the retired per-entity Position payload.
+    Despawn = 0x0A,
+    Snapshot = 0x0B,
+    SnapshotAck = 0x0C,
+    Unknown = 0xFF,
 }
 
 impl From<u8> for PayloadId {
@@ -16,8 +19,10 @@ impl From<u8> for PayloadId {
         match value {
             0x06 => PayloadId::Connect,
             0x07 => PayloadId::State,
-            0x08 => PayloadId::Position,
             0x09 => PayloadId::Movement,
+            0x0A => PayloadId::Despawn,
+            0x0B => PayloadId::Snapshot,
+            0x0C => PayloadId::SnapshotAck,
             _ => PayloadId::Unknown,
         }
     }
@@ -28,8 +33,10 @@ impl From<PayloadId> for u8 {
         match value {
             PayloadId::Connect => 0x06,
             PayloadId::State => 0x07,
-            PayloadId::Position => 0x08,
             PayloadId::Movement => 0x09,
+            PayloadId::Despawn => 0x0A,
+            PayloadId::Snapshot => 0x0B,
+            PayloadId::SnapshotAck => 0x0C,
             PayloadId::Unknown => 0xFF,
         }
     }
@@ -46,10 +53,79 @@ pub struct ServerState {
     pub tick_id: u64,
 }
 
-/// Represents an Entity ID, position, and velocity.
-#[derive(NetDecode, NetEncode, Debug, Clone, Copy)]
-pub struct Position(pub u32, pub Vec2f, pub Vec2f);
-
 /// Represents a movement command with a movement delta and speed.
 #[derive(NetDecode, NetEncode, Debug, Clone, Copy)]
 pub struct Movement(pub Vec2f, pub u8);
+
+/// Movement sent by a client along with its input sequence, so the server can echo back the
+/// last input it applied.
+#[derive(NetDecode, NetEncode, Debug, Clone, Copy)]
+pub struct MovementInput(pub Movement, pub u32);
+
+impl VersionedPayload for MovementInput {
+    const VERSION: u8 = 2;
+
+    /// Version 1 has no input sequence, which is treated as 0, no input.
+    fn decode_version(version: u8, data: &[u8]) -> Result<(Self, usize)> {
+        match version {
+            1 => {
+                let (movement, used) = Movement::decode(data)?;
+                Ok((Self(movement, 0), used))
+            }
+            _ => Err(NetError::NetCode(format!(
+                "Unsupported Movement version {version}"
+            ))),
+        }
+    }
+}
+
+/// Sent from a server to remove an Entity Id that no longer exists.
+#[derive(NetDecode, NetEncode, Debug, Clone, Copy)]
+pub struct Despawn(pub u32);
+
+/// Sent from a client to acknowledge the Snapshot Id it has applied.
+#[derive(NetDecode, NetEncode, Debug, Clone, Copy)]
+pub struct SnapshotAck(pub u32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::PacketLabel;
+    use crate::net::traits::Versioned;
+
+    #[test]
+    fn payload_ids_are_valid_extensions() {
+        let ids = [
+            PayloadId::Connect,
+            PayloadId::State,
+            PayloadId::Movement,
+            PayloadId::Despawn,
+            PayloadId::Snapshot,
+            PayloadId::SnapshotAck,
+        ];
+        for id in ids {
+            let value = u8::from(id);
+            assert!(PacketLabel::extension(value).is_some(), "{value:#04x}");
+            assert_eq!(u8::from(PayloadId::from(value)), value);
+        }
+    }
+
+    #[test]
+    fn movement_input_keeps_the_version_2_layout() {
+        let input = MovementInput(Movement(Vec2f(1.0, -1.0), 2), 9);
+        let mut expected = vec![2];
+        expected.extend((Vec2f(1.0, -1.0), 2_u8, 9_u32).encode());
+        assert_eq!(Versioned(input).encode(), expected);
+    }
+
+    #[test]
+    fn version_1_movement_has_no_input() {
+        let mut bytes = vec![1];
+        bytes.extend(Movement(Vec2f(0.0, 1.0), 3).encode());
+
+        let (Versioned(MovementInput(movement, input_seq)), used) =
+            Versioned::<MovementInput>::decode(&bytes).unwrap();
+        assert_eq!(used, bytes.len());
+        assert_eq!((movement.0, movement.1, input_seq), (Vec2f(0.0, 1.0), 3, 0));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::vec2f::Vec2f;
+
 /// Geometry for a 2D rectangle (width × height).
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rectangle {
@@ -10,6 +12,110 @@ impl Rectangle {
     pub fn new(width: f32, height: f32) -> Self {
         Self { width, height }
     }
+
+    /// Area covered by the rectangle.
+    #[allow(dead_code)]
+    pub fn area(self) -> f32 {
+        self.width * self.height
+    }
+
+    /// Length around the edges of the rectangle.
+    #[allow(dead_code)]
+    pub fn perimeter(self) -> f32 {
+        2.0 * (self.width + self.height)
+    }
+
+    /// Checks if a point is within the rectangle placed with its top-left at `origin`. Points on
+    /// the edge are within it.
+    #[allow(dead_code)]
+    pub fn contains_point(self, origin: Vec2f, point: Vec2f) -> bool {
+        (origin.0..=origin.0 + self.width).contains(&point.0)
+            && (origin.1..=origin.1 + self.height).contains(&point.1)
+    }
+
+    /// Obtains the top-left and size of the area shared with `other`, with each rectangle placed
+    /// with its top-left at its origin. None if they do not overlap or only share an edge.
+    #[allow(dead_code)]
+    pub fn overlap(self, origin: Vec2f, other: Self, other_origin: Vec2f) -> Option<(Vec2f, Self)> {
+        let min = Vec2f(origin.0.max(other_origin.0), origin.1.max(other_origin.1));
+        let max = Vec2f(
+            (origin.0 + self.width).min(other_origin.0 + other.width),
+            (origin.1 + self.height).min(other_origin.1 + other.height),
+        );
+
+        (max.0 > min.0 && max.1 > min.1).then(|| (min, Self::new(max.0 - min.0, max.1 - min.1)))
+    }
 }
 
 impl Eq for Rectangle {}
+
+/// Geometry for a 2D circle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub radius: f32,
+}
+
+impl Circle {
+    /// Creates a new circle with the specified radius.
+    #[allow(dead_code)]
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Eq for Circle {}
+
+/// Geometry of any supported shape, allowing different shapes to be tested against each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Rectangle(Rectangle),
+    Circle(Circle),
+}
+
+impl From<Rectangle> for Shape {
+    fn from(rectangle: Rectangle) -> Self {
+        Shape::Rectangle(rectangle)
+    }
+}
+
+impl From<Circle> for Shape {
+    fn from(circle: Circle) -> Self {
+        Shape::Circle(circle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_inside_and_on_the_edge_are_contained() {
+        let rect = Rectangle::new(4.0, 2.0);
+        let origin = Vec2f(1.0, 1.0);
+        assert!(rect.contains_point(origin, Vec2f(3.0, 2.0)));
+        assert!(rect.contains_point(origin, Vec2f(5.0, 3.0)));
+        assert!(!rect.contains_point(origin, Vec2f(5.1, 2.0)));
+        assert!(!rect.contains_point(origin, Vec2f(3.0, 0.9)));
+
+        assert!((rect.area() - 8.0).abs() < 1e-6);
+        assert!((rect.perimeter() - 12.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn overlap_is_the_shared_area() {
+        let wide = Rectangle::new(4.0, 2.0);
+        let tall = Rectangle::new(2.0, 4.0);
+        assert_eq!(
+            wide.overlap(Vec2f::ZERO, tall, Vec2f(3.0, -1.0)),
+            Some((Vec2f(3.0, 0.0), Rectangle::new(1.0, 2.0)))
+        );
+        assert_eq!(
+            tall.overlap(Vec2f(3.0, -1.0), wide, Vec2f::ZERO),
+            Some((Vec2f(3.0, 0.0), Rectangle::new(1.0, 2.0)))
+        );
+
+        // Rectangles that are apart or only touch have nothing in common.
+        assert_eq!(wide.overlap(Vec2f::ZERO, tall, Vec2f(6.0, 0.0)), None);
+        assert_eq!(wide.overlap(Vec2f::ZERO, tall, Vec2f(4.0, 0.0)), None);
+    }
+}
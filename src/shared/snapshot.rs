@@ -0,0 +1,160 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::net::traits::{NetDecoder, NetEncoder, NetVec};
+use crate::vec2f::Vec2f;
+use netcode_derive::{NetDecode, NetEncode};
+
+/// Steps per world unit used to quantize position deltas.
+const QUANTIZE: f32 = 256.0;
+
+/// Position and velocity of every entity in a snapshot, keyed by Entity Id.
+pub type SnapshotState = HashMap<u32, (Vec2f, Vec2f)>;
+
+/// Position of an entity within a snapshot.
+#[derive(NetDecode, NetEncode, Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotPosition {
+    /// Absolute position, used when the baseline does not have the entity.
+    Full(Vec2f),
+    /// Quantized offset from the entity's position in the baseline.
+    Delta(i16, i16),
+}
+
+/// Represents an Entity Id, position, and velocity within a snapshot.
+#[derive(NetDecode, NetEncode, Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotEntry(pub u32, pub SnapshotPosition, pub Vec2f);
+
+/// Entities that changed since the baseline snapshot, or every entity if there is no baseline.
+#[derive(NetDecode, NetEncode, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub id: u32,
+    pub baseline: Option<u32>,
+    pub input_seq: u32, // Last movement input from the receiving client that was processed.
+    pub entries: NetVec<SnapshotEntry>,
+}
+
+impl Snapshot {
+    /// Creates a snapshot of `current` against the `baseline` state it was acknowledged with.
+    /// Returns the snapshot and the state the receiver will hold after applying it, which has
+    /// the quantized positions and must be used as the baseline for later snapshots.
+    pub fn diff(
+        id: u32,
+        baseline: Option<(u32, &SnapshotState)>,
+        current: &SnapshotState,
+    ) -> (Self, SnapshotState) {
+        let mut state = baseline.map(|(_, state)| state.clone()).unwrap_or_default();
+        let mut entries = Vec::new();
+
+        for (&entity, &(position, velocity)) in current {
+            let (position, resolved) = match state.get(&entity) {
+                Some(&(base, base_velocity)) => match quantize(position - base) {
+                    Some((0, 0)) if velocity == base_velocity => continue, // Unchanged.
+                    Some((dx, dy)) => (SnapshotPosition::Delta(dx, dy), base + dequantize(dx, dy)),
+                    None => (SnapshotPosition::Full(position), position), // Too far for a delta.
+                },
+                None => (SnapshotPosition::Full(position), position),
+            };
+
+            state.insert(entity, (resolved, velocity));
+            entries.push(SnapshotEntry(entity, position, velocity));
+        }
+
+        let snapshot = Self {
+            id,
+            baseline: baseline.map(|(id, _)| id),
+            input_seq: 0,
+            entries: entries.into(),
+        };
+
+        (snapshot, state)
+    }
+
+    /// Applies the snapshot onto the `baseline` state it was created against, returning the new
+    /// state. None if an entry is a delta for an entity the baseline does not have.
+    pub fn apply(&self, baseline: Option<&SnapshotState>) -> Option<SnapshotState> {
+        let mut state = baseline.cloned().unwrap_or_default();
+        for entry in &self.entries.0 {
+            let resolved = entry.resolve(&state)?;
+            state.insert(entry.0, resolved);
+        }
+
+        Some(state)
+    }
+}
+
+impl SnapshotEntry {
+    /// Resolves the absolute position and velocity of the entry against a state.
+    fn resolve(&self, state: &SnapshotState) -> Option<(Vec2f, Vec2f)> {
+        match self.1 {
+            SnapshotPosition::Full(position) => Some((position, self.2)),
+            SnapshotPosition::Delta(dx, dy) => {
+                let (base, _) = state.get(&self.0)?;
+                Some((*base + dequantize(dx, dy), self.2))
+            }
+        }
+    }
+}
+
+/// Quantizes an offset into steps. None if either component does not fit.
+#[allow(clippy::cast_possible_truncation)]
+fn quantize(offset: Vec2f) -> Option<(i16, i16)> {
+    let step = |value: f32| {
+        let steps = (value * QUANTIZE).round();
+        (steps >= f32::from(i16::MIN) && steps <= f32::from(i16::MAX)).then_some(steps as i16)
+    };
+
+    Some((step(offset.0)?, step(offset.1)?))
+}
+
+/// Converts quantized steps back into an offset.
+fn dequantize(dx: i16, dy: i16) -> Vec2f {
+    Vec2f(f32::from(dx), f32::from(dy)) / QUANTIZE
+}
+
+/// Recent snapshot states by Id, oldest first. Holds at most `SnapshotHistory::CAPACITY`
+/// states, dropping the oldest once full.
+#[derive(Debug, Default)]
+pub struct SnapshotHistory(VecDeque<(u32, SnapshotState)>);
+
+impl SnapshotHistory {
+    /// Most states retained.
+    pub const CAPACITY: usize = 32;
+
+    /// Adds the state of a snapshot, dropping the oldest if full.
+    pub fn push(&mut self, id: u32, state: SnapshotState) {
+        if self.0.len() >= Self::CAPACITY {
+            self.0.pop_front();
+        }
+        self.0.push_back((id, state));
+    }
+
+    /// Obtains the state of a snapshot.
+    pub fn get(&self, id: u32) -> Option<&SnapshotState> {
+        self.0
+            .iter()
+            .find(|(snapshot, _)| *snapshot == id)
+            .map(|(_, state)| state)
+    }
+
+    /// Removes the snapshot and every older one, returning it.
+    pub fn take(&mut self, id: u32) -> Option<(u32, SnapshotState)> {
+        let idx = self.0.iter().position(|(snapshot, _)| *snapshot == id)?;
+        self.0.drain(..=idx).next_back()
+    }
+
+    /// Checks if the history holds `CAPACITY` states.
+    pub fn is_full(&self) -> bool {
+        self.0.len() >= Self::CAPACITY
+    }
+
+    /// Removes an entity from every state, so it is sent in full if it appears again.
+    pub fn forget(&mut self, entity: u32) {
+        for (_, state) in &mut self.0 {
+            state.remove(&entity);
+        }
+    }
+
+    /// Removes every state.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
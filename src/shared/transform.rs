@@ -4,9 +4,9 @@ use crate::vec2f::Vec2f;
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Transform {
     pub position: Vec2f, // Top-left position.
-    pub origin: Vec2f,   // Pivot point for rotations.
+    pub origin: Vec2f,   // Pivot point for rotations, relative to the position.
     pub scale: Vec2f,    // Scale of the transformation.
-    pub rotation: f32,   // Rotation in degrees.
+    pub rotation: f32,   // Rotation in radians.
 }
 
 impl Transform {
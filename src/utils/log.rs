@@ -0,0 +1,86 @@
+#[cfg(test)]
+use std::cell::RefCell;
+use std::sync::RwLock;
+
+/// Severity of a logged message, ordered from least to most severe.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogLevel::Debug => write!(f, "DEBUG"),
+            LogLevel::Info => write!(f, "INFO"),
+            LogLevel::Warn => write!(f, "WARN"),
+            LogLevel::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// Callback that receives every logged message.
+type Logger = Box<dyn Fn(LogLevel, &str) + Send + Sync>;
+
+/// Installed logger. None writes to stderr.
+static LOGGER: RwLock<Option<Logger>> = RwLock::new(None);
+
+#[cfg(test)]
+thread_local! {
+    /// Messages logged on this thread while `capture` runs.
+    static CAPTURED: RefCell<Option<Vec<(LogLevel, String)>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, collecting the messages it logs on the current thread instead of sending them to the
+/// logger. Tests run in parallel, so they capture per thread rather than replacing the logger.
+#[cfg(test)]
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, Vec<(LogLevel, String)>) {
+    CAPTURED.with_borrow_mut(|captured| *captured = Some(vec![]));
+    let result = f();
+    let messages = CAPTURED.with_borrow_mut(Option::take).unwrap_or_default();
+    (result, messages)
+}
+
+/// Routes every logged message to `logger` instead of stderr, replacing any previous logger.
+/// Used by applications embedding the crate to filter or redirect its output.
+#[allow(dead_code)]
+pub fn set_logger(logger: impl Fn(LogLevel, &str) + Send + Sync + 'static) {
+    if let Ok(mut current) = LOGGER.write() {
+        *current = Some(Box::new(logger));
+    }
+}
+
+/// Removes the installed logger, writing messages to stderr again.
+#[allow(dead_code)]
+pub fn reset_logger() {
+    if let Ok(mut current) = LOGGER.write() {
+        *current = None;
+    }
+}
+
+/// Sends a message to the installed logger, or stderr if there is none. Use the `logln!` and
+/// `debugln!` macros instead of calling this directly.
+pub fn log(level: LogLevel, args: std::fmt::Arguments) {
+    #[cfg(test)]
+    {
+        let captured = CAPTURED.with_borrow_mut(|captured| match captured {
+            Some(messages) => {
+                messages.push((level, args.to_string()));
+                true
+            }
+            None => false,
+        });
+        if captured {
+            return;
+        }
+    }
+
+    match LOGGER.read().as_deref() {
+        Ok(Some(logger)) => logger(level, &args.to_string()),
+        _ => eprintln!("[{level}] {args}"),
+    }
+}
@@ -1,9 +1,18 @@
-/// Prints out a debug message, wraps `println!` macro.
+/// Logs a message at the given `LogLevel` through the installed logger.
+#[macro_export]
+macro_rules! logln {
+    ($level:expr, $fmt:expr $(, $($arg:tt)*)?) => {
+        $crate::utils::log::log($level, format_args!($fmt, $($($arg)*)?))
+    };
+}
+
+/// Logs a debug message through the installed logger. Only logged in debug builds or with the
+/// `debug_output` feature.
 #[macro_export]
 macro_rules! debugln {
     ($fmt:expr $(, $($arg:tt)*)?) => {
         #[cfg(any(debug_assertions, feature = "debug_output"))]
-        println!(concat!("[DEBUG] ", $fmt), $($($arg)*)?);
+        $crate::logln!($crate::utils::log::LogLevel::Debug, $fmt $(, $($arg)*)?);
     };
 }
 
@@ -1,8 +1,11 @@
+pub mod log;
 mod macros;
+mod rng;
 mod spatial_hash;
 mod sset;
 mod timestep;
 
+pub use rng::Rng;
 pub use spatial_hash::SpatialHash;
 pub use sset::SparseSet;
 pub use timestep::Timestep;
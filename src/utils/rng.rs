@@ -0,0 +1,41 @@
+/// Small seedable pseudo-random number generator (xorshift64*). Not suitable for cryptography,
+/// but identical seeds always produce identical sequences, keeping the simulation reproducible.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64, // Current state, never zero.
+}
+
+impl Rng {
+    /// Creates a generator from a seed. A seed of zero is replaced, as it would only produce zeros.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Obtains the next random 64-bit value.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Obtains a random value in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        const SCALE: f32 = 16_777_216.0; // 2^24, every 24-bit value is exact in an `f32`.
+
+        #[allow(clippy::cast_precision_loss)]
+        let value = (self.next_u64() >> 40) as f32;
+        value / SCALE
+    }
+
+    /// Obtains a random value in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
@@ -30,6 +30,26 @@ impl SpatialHash {
         }
     }
 
+    /// Obtains the size of each cell.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Changes the size of each cell, rehashing every entity into the new grid at its current
+    /// position.
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.inverse_cell_size = 1.0 / cell_size;
+
+        let old_cells = std::mem::take(&mut self.cells);
+        for (entity, pos) in old_cells.into_values().flatten() {
+            let cell = self.convert_vec2f(pos);
+            let bucket = self.cells.entry(cell).or_default();
+            bucket.push((entity, pos));
+            self.lookup.insert(entity, (cell, bucket.len() - 1));
+        }
+    }
+
     /// Converts from Cell to Vec2f which is used out of spatial hash context.
     #[allow(clippy::cast_precision_loss)]
     fn convert_cell(&self, cell: Cell) -> Vec2f {
@@ -108,6 +128,50 @@ impl SpatialHash {
         }
     }
 
+    /// Asserts that the lookup table and cells agree. Every lookup entry must point at a slot
+    /// holding that entity, every stored entity must be in the lookup, and no cell may be empty.
+    #[cfg(debug_assertions)]
+    pub fn validate(&self) {
+        for (entity, &(cell, idx)) in &self.lookup {
+            let Some(bucket) = self.cells.get(&cell) else {
+                panic!("Entity {entity} points at missing cell {cell:?}.");
+            };
+
+            match bucket.get(idx) {
+                Some((found, _)) if found == entity => {}
+                Some((found, _)) => {
+                    panic!("Entity {entity} points at {cell:?}[{idx}] which holds {found}.")
+                }
+                None => panic!("Entity {entity} points past the end of {cell:?}[{idx}]."),
+            }
+        }
+
+        let mut stored = 0;
+        for (cell, bucket) in &self.cells {
+            assert!(
+                !bucket.is_empty(),
+                "Cell {cell:?} is empty but was not flushed."
+            );
+            for (idx, (entity, _)) in bucket.iter().enumerate() {
+                assert_eq!(
+                    self.lookup.get(entity),
+                    Some(&(*cell, idx)),
+                    "Entity {entity} in {cell:?}[{idx}] does not match its lookup entry."
+                );
+            }
+
+            stored += bucket.len();
+        }
+
+        // Lookup keys are unique, so matching counts means no entity is stored twice.
+        assert_eq!(
+            stored,
+            self.lookup.len(),
+            "Cells hold {stored} entries but the lookup has {}.",
+            self.lookup.len()
+        );
+    }
+
     /// Obtains all entities within the given radius of the position.
     #[allow(clippy::cast_possible_truncation)]
     pub fn query(&self, pos: Vec2f, radius: f32) -> Vec<(u32, &Vec2f)> {
@@ -136,4 +200,215 @@ impl SpatialHash {
 
         hits
     }
+
+    /// Obtains all entities within the axis-aligned box from `min` to `max`, inclusive of its
+    /// edges. Only the cells overlapping the box are examined.
+    pub fn query_rect(&self, min: Vec2f, max: Vec2f) -> Vec<(u32, &Vec2f)> {
+        let min_cell = self.convert_vec2f(min);
+        let max_cell = self.convert_vec2f(max);
+
+        // Collections of entities that are within the box.
+        let mut hits = Vec::new();
+
+        for x in min_cell.0..=max_cell.0 {
+            for y in min_cell.1..=max_cell.1 {
+                if let Some(bucket) = self.cells.get(&Cell(x, y)) {
+                    for (entity, entity_pos) in bucket {
+                        if (min.0..=max.0).contains(&entity_pos.0)
+                            && (min.1..=max.1).contains(&entity_pos.1)
+                        {
+                            hits.push((*entity, entity_pos)); // Entity is within the box.
+                        }
+                    }
+                }
+            }
+        }
+
+        hits
+    }
+
+    /// Obtains the entity closest to the position within `max_radius`. Cells are searched ring by
+    /// ring outward from the origin, stopping once no unexamined ring can hold a closer entity.
+    /// Ties are broken by the lowest entity ID.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn nearest(&self, pos: Vec2f, max_radius: f32) -> Option<(u32, Vec2f)> {
+        let origin = self.convert_vec2f(pos);
+        let max_ring = (max_radius * self.inverse_cell_size).ceil() as i32;
+        let r2 = max_radius * max_radius;
+
+        let mut best: Option<(u32, Vec2f, f32)> = None;
+        for ring in 0..=max_ring {
+            // Check only the cells on the edge of the current ring.
+            for dx in -ring..=ring {
+                for dy in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue; // Inner cell, already examined.
+                    }
+
+                    let Some(bucket) = self.cells.get(&Cell(origin.0 + dx, origin.1 + dy)) else {
+                        continue;
+                    };
+
+                    for &(entity, entity_pos) in bucket {
+                        let d2 = entity_pos.distance_squared(pos);
+                        if d2 > r2 {
+                            continue;
+                        }
+
+                        let closer = match best {
+                            None => true,
+                            Some((best_entity, _, best_d2)) => d2
+                                .total_cmp(&best_d2)
+                                .then(entity.cmp(&best_entity))
+                                .is_lt(),
+                        };
+
+                        if closer {
+                            best = Some((entity, entity_pos, d2));
+                        }
+                    }
+                }
+            }
+
+            // Every cell beyond this ring is at least `ring` cells away from the position.
+            let bound = ring as f32 * self.cell_size;
+            if best.is_some_and(|(_, _, d2)| d2 < bound * bound) {
+                break;
+            }
+        }
+
+        best.map(|(entity, entity_pos, _)| (entity, entity_pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_after_inserts_moves_and_removes() {
+        let mut hash = SpatialHash::new(1.0);
+        for entity in 0..20u16 {
+            let value = f32::from(entity);
+            hash.insert(Vec2f(value * 0.3, value * 0.7), u32::from(entity));
+        }
+        hash.validate();
+
+        // Move within the same cell, across cells, and back, forcing swap-remove fixups.
+        for entity in 0..20u16 {
+            let value = f32::from(entity);
+            hash.insert(Vec2f(value * 0.3 + 0.01, value * 0.7), u32::from(entity));
+            hash.insert(Vec2f(-value, value), u32::from(entity));
+            hash.validate();
+        }
+
+        for entity in (0..20).step_by(3) {
+            hash.remove(entity);
+            hash.validate();
+        }
+
+        // Removing an entity that is not present changes nothing.
+        hash.remove(100);
+        hash.validate();
+        assert_eq!(hash.query(Vec2f(0.0, 0.0), 100.0).len(), 13);
+    }
+
+    #[test]
+    fn nearest_finds_the_true_closest() {
+        let mut hash = SpatialHash::new(2.0);
+        hash.insert(Vec2f(5.0, 5.0), 1);
+        hash.insert(Vec2f(1.9, 0.0), 2); // Same cell as the origin, but farther away.
+        hash.insert(Vec2f(-0.5, -0.5), 3); // Neighbouring cell, and the closest.
+        hash.insert(Vec2f(20.0, 0.0), 4);
+
+        assert_eq!(
+            hash.nearest(Vec2f(0.1, 0.1), 10.0),
+            Some((3, Vec2f(-0.5, -0.5)))
+        );
+        assert_eq!(
+            hash.nearest(Vec2f(19.0, 0.0), 10.0),
+            Some((4, Vec2f(20.0, 0.0)))
+        );
+        assert_eq!(hash.nearest(Vec2f(100.0, 100.0), 10.0), None);
+    }
+
+    #[test]
+    fn nearest_breaks_ties_by_lowest_id() {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert(Vec2f(3.0, 0.0), 9);
+        hash.insert(Vec2f(-3.0, 0.0), 4);
+        hash.insert(Vec2f(0.0, 3.0), 7);
+
+        for _ in 0..3 {
+            assert_eq!(hash.nearest(Vec2f::ZERO, 5.0).map(|(id, _)| id), Some(4));
+        }
+    }
+
+    #[test]
+    fn query_rect_includes_cell_boundaries() {
+        let mut hash = SpatialHash::new(2.0);
+        hash.insert(Vec2f(2.0, 2.0), 1); // On the corner shared by four cells.
+        hash.insert(Vec2f(4.0, 3.0), 2); // On the right edge of the box.
+        hash.insert(Vec2f(4.01, 3.0), 3); // Just outside of the box.
+        hash.insert(Vec2f(0.0, 0.0), 4); // On the top-left corner of the box.
+
+        let mut hits: Vec<u32> = hash
+            .query_rect(Vec2f::ZERO, Vec2f(4.0, 4.0))
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        hits.sort_unstable();
+        assert_eq!(hits, vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn query_rect_with_zero_area() {
+        let mut hash = SpatialHash::new(2.0);
+        hash.insert(Vec2f(2.0, 2.0), 1);
+        hash.insert(Vec2f(2.0, 2.5), 2);
+
+        let point = Vec2f(2.0, 2.0);
+        let hits: Vec<u32> = hash
+            .query_rect(point, point)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(hits, vec![1]);
+    }
+
+    #[test]
+    fn resizing_cells_keeps_query_membership() {
+        let mut hash = SpatialHash::new(1.0);
+        for entity in 0..30u16 {
+            let value = f32::from(entity);
+            hash.insert(
+                Vec2f(value * 0.45 - 3.0, value * -0.8 + 7.0),
+                u32::from(entity),
+            );
+        }
+
+        let members = |hash: &SpatialHash| {
+            let mut ids: Vec<u32> = hash
+                .query(Vec2f(2.0, 1.0), 4.5)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        let before = members(&hash);
+        assert!(!before.is_empty());
+        for cell_size in [4.0, 0.25] {
+            hash.set_cell_size(cell_size);
+            hash.validate();
+            assert_eq!(members(&hash), before);
+        }
+
+        // Entities can still be moved and removed through the rebuilt lookup.
+        hash.insert(Vec2f(100.0, 100.0), before[0]);
+        hash.remove(before[1]);
+        hash.validate();
+        assert_eq!(members(&hash), before[2..]);
+    }
 }
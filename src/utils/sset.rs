@@ -1,16 +1,11 @@
-#[derive(Debug)]
-struct Entry<T> {
-    key: usize,
-    value: T,
-}
-
 /// A sparse set is a data structure that allows for efficient insertion, deletion, and lookup of
 #[derive(Debug)]
 pub struct SparseSet<T> {
-    dense: Vec<Entry<T>>, // Dense set of values.
-    sparse: Vec<usize>,   // Sparse set of indices.
-    max_capacity: usize,  // Maximum capacity of the sparse set.
-    invalid_key: usize,   // Invalid index for the sparse set.
+    keys: Vec<usize>,    // Dense set of keys, parallel to `values`.
+    values: Vec<T>,      // Dense set of values.
+    sparse: Vec<usize>,  // Sparse set of indices.
+    max_capacity: usize, // Maximum capacity of the sparse set.
+    invalid_key: usize,  // Invalid index for the sparse set.
 }
 
 impl<T> SparseSet<T> {
@@ -22,7 +17,8 @@ impl<T> SparseSet<T> {
         );
 
         Self {
-            dense: Vec::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+            values: Vec::with_capacity(capacity),
             sparse: vec![invalid_key; capacity],
             max_capacity: capacity,
             invalid_key,
@@ -42,7 +38,7 @@ impl<T> SparseSet<T> {
         }
 
         let dense_idx = self.sparse[key];
-        if dense_idx < self.dense.len() {
+        if dense_idx < self.values.len() {
             Some(dense_idx)
         } else {
             None
@@ -56,13 +52,13 @@ impl<T> SparseSet<T> {
 
     /// Gets the amount of elements in the sparse set.
     pub fn length(&self) -> usize {
-        self.dense.len()
+        self.values.len()
     }
 
     /// Obtains a reference for the value associated with the key.
     pub fn get(&self, key: usize) -> Option<&T> {
         if let Some(dense_idx) = self.get_dense_idx(key) {
-            Some(&self.dense[dense_idx].value)
+            Some(&self.values[dense_idx])
         } else {
             None
         }
@@ -71,7 +67,7 @@ impl<T> SparseSet<T> {
     /// Obtains a mutable reference for the value associated with the key.
     pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
         if let Some(dense_idx) = self.get_dense_idx(key) {
-            Some(&mut self.dense[dense_idx].value)
+            Some(&mut self.values[dense_idx])
         } else {
             None
         }
@@ -91,28 +87,33 @@ impl<T> SparseSet<T> {
             *stored = value;
         } else {
             // Index not in dense set.
-            self.sparse[key] = self.dense.len();
-            self.dense.push(Entry { key, value });
+            self.sparse[key] = self.values.len();
+            self.keys.push(key);
+            self.values.push(value);
         }
 
         self.sparse[key]
     }
 
-    /// Removes a value based on the key provided.
-    pub fn remove(&mut self, key: usize) -> Option<T> {
-        if !self.has_key(key) {
-            return None;
-        }
+    /// Removes the entry at the dense index, moving the last entry into its place.
+    fn swap_remove(&mut self, dense_idx: usize) -> (usize, T) {
+        let key = self.keys.swap_remove(dense_idx);
+        let value = self.values.swap_remove(dense_idx);
 
-        let dense_idx = self.sparse[key];
-        let entry = self.dense.swap_remove(dense_idx);
-        if (dense_idx) < self.dense.len() {
-            let swapped = &self.dense[dense_idx];
-            self.sparse[swapped.key] = dense_idx;
+        // Update the sparse index for the swapped entry.
+        if dense_idx < self.keys.len() {
+            self.sparse[self.keys[dense_idx]] = dense_idx;
         }
 
+        // Mark the removed entry as invalid in the sparse set.
         self.sparse[key] = self.invalid_key();
-        Some(entry.value)
+        (key, value)
+    }
+
+    /// Removes a value based on the key provided.
+    pub fn remove(&mut self, key: usize) -> Option<T> {
+        let dense_idx = self.get_dense_idx(key)?;
+        Some(self.swap_remove(dense_idx).1)
     }
 
     /// Allows for iteration over the dense set.
@@ -124,6 +125,46 @@ impl<T> SparseSet<T> {
         }
     }
 
+    /// Obtains the dense keys and values as parallel slices, in iteration order. The value at
+    /// each index belongs to the key at the same index.
+    #[allow(dead_code)]
+    pub fn as_slices(&self) -> (&[usize], &[T]) {
+        (&self.keys, &self.values)
+    }
+
+    /// Obtains the dense values as a mutable slice, in iteration order.
+    #[allow(dead_code)]
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    /// Removes every value, leaving the capacity unchanged. Only the slots in use are reset.
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        for key in self.keys.drain(..) {
+            self.sparse[key] = self.invalid_key;
+        }
+
+        self.values.clear();
+    }
+
+    /// Keeps only the values that match the predicate `f`, discarding the rest.
+    #[allow(dead_code)]
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &T) -> bool,
+    {
+        let mut dense_idx = 0;
+
+        while dense_idx < self.values.len() {
+            if f(self.keys[dense_idx], &self.values[dense_idx]) {
+                dense_idx += 1;
+            } else {
+                self.swap_remove(dense_idx);
+            }
+        }
+    }
+
     /// Removes all values that match the predicate `f`.
     #[allow(dead_code)]
     pub fn drain_if<F>(&mut self, mut f: F) -> impl Iterator<Item = (usize, T)>
@@ -133,20 +174,9 @@ impl<T> SparseSet<T> {
         let mut removed = vec![];
         let mut dense_idx = 0;
 
-        while dense_idx < self.dense.len() {
-            if f(&self.dense[dense_idx].value) {
-                // Extract and save the key / value to be returned.
-                let Entry { key, value } = self.dense.swap_remove(dense_idx);
-                removed.push((key, value));
-
-                // Mark the removed entry as invalid in the sparse set.
-                self.sparse[key] = self.invalid_key();
-
-                // Update the sparse index for the swapped entry.
-                if dense_idx < self.dense.len() {
-                    let swapped_key = &self.dense[dense_idx].key;
-                    self.sparse[*swapped_key] = dense_idx;
-                }
+        while dense_idx < self.values.len() {
+            if f(&self.values[dense_idx]) {
+                removed.push(self.swap_remove(dense_idx));
             } else {
                 dense_idx += 1;
             }
@@ -166,12 +196,80 @@ impl<'a, T> Iterator for SparseSetIterator<'a, T> {
     type Item = (&'a usize, &'a T);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.sset.dense.len() {
-            let entry = &self.sset.dense[self.index];
+        if self.index < self.sset.values.len() {
+            let idx = self.index;
             self.index += 1;
-            Some((&entry.key, &entry.value))
+            Some((&self.sset.keys[idx], &self.sset.values[idx]))
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Set of capacity 8 holding each key in `keys` with ten times the key as its value.
+    fn filled(keys: &[usize]) -> SparseSet<usize> {
+        let mut set = SparseSet::new(8, 8);
+        for &key in keys {
+            set.insert(key, key * 10);
+        }
+        set
+    }
+
+    #[test]
+    fn clear_empties_every_key() {
+        let mut set = filled(&[0, 3, 7]);
+        set.clear();
+
+        assert_eq!(set.length(), 0);
+        assert!((0..8).all(|key| !set.has_key(key)));
+        assert!(set.sparse.iter().all(|&idx| idx == set.invalid_key()));
+        assert_eq!(set.sparse.len(), 8);
+
+        // The set is usable again at full capacity.
+        set.insert(7, 1);
+        assert_eq!(set.get(7), Some(&1));
+        assert!(!set.has_key(3));
+    }
+
+    #[test]
+    fn retain_keeps_only_matches() {
+        let mut set = filled(&[1, 2, 3, 4, 5]);
+        set.retain(|key, value| key % 2 == 1 && *value != 30);
+
+        let mut kept: Vec<_> = set.iter().map(|(key, value)| (*key, *value)).collect();
+        kept.sort_unstable();
+        assert_eq!(kept, vec![(1, 10), (5, 50)]);
+        for key in [2, 3, 4] {
+            assert!(!set.has_key(key));
+            assert_eq!(set.sparse[key], set.invalid_key());
+        }
+
+        // Dense and sparse still agree after the swaps.
+        for (dense_idx, key) in set.keys.iter().enumerate() {
+            assert_eq!(set.sparse[*key], dense_idx);
+        }
+    }
+
+    #[test]
+    fn slices_follow_the_dense_order() {
+        let mut set = filled(&[6, 2, 4]);
+        set.remove(6);
+
+        // The last value is swapped into the removed slot.
+        let (keys, values) = set.as_slices();
+        assert_eq!(keys, &[4, 2]);
+        assert_eq!(values, &[40, 20]);
+
+        for value in set.values_mut() {
+            *value += 1;
+        }
+        assert_eq!(set.get(4), Some(&41));
+        assert_eq!(set.get(2), Some(&21));
+        let iterated: Vec<_> = set.iter().map(|(key, value)| (*key, *value)).collect();
+        assert_eq!(iterated, vec![(4, 41), (2, 21)]);
+    }
+}
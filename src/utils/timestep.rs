@@ -1,10 +1,20 @@
 use std::time::{Duration, Instant};
 
 /// Controls loop ticks in increments to maintain TPS.
+///
+/// Time is measured with an accumulator: elapsed time is added to it, and every whole
+/// `tick_duration` in the accumulator is consumed as a tick. The remainder is the progress
+/// towards the next tick, exposed as `alpha` for interpolating between fixed steps.
+///
+/// When a tick overruns its duration while waiting, the overrun is accumulated as lag. Later
+/// ticks that finish early skip sleeping until the lag is paid back.
 pub struct Timestep {
     pub last_ts: Instant,    // Last timestamp processed.
     tick: u64,               // Current tick count.
     tick_duration: Duration, // Duration of each tick.
+    accumulator: Duration,   // Time elapsed since the last tick that has not been consumed.
+    lag: Duration,           // Time that overrunning ticks are behind schedule.
+    elapsed: Duration,       // Real time passed since creation or the last reset.
 }
 
 impl Timestep {
@@ -14,34 +24,196 @@ impl Timestep {
             last_ts: Instant::now(),
             tick: 0,
             tick_duration: Duration::from_secs_f32(1.0 / tick_rate),
+            accumulator: Duration::ZERO,
+            lag: Duration::ZERO,
+            elapsed: Duration::ZERO,
         }
     }
 
+    /// Most ticks of lag that are accumulated. Anything beyond is dropped instead of caught up,
+    /// so a long stall does not cause a burst of ticks without rest.
+    const MAX_LAG_TICKS: u32 = 5;
+
     /// Returns the fixed delta time in seconds.
     #[inline]
     pub fn fixed_dt(&self) -> f32 {
         self.tick_duration.as_secs_f32()
     }
 
+    /// Changes the ticks-per-second, taking effect from the next tick.
+    #[allow(dead_code)]
+    pub fn set_rate(&mut self, tick_rate: f32) {
+        self.tick_duration = Duration::from_secs_f32(1.0 / tick_rate);
+    }
+
+    /// Returns the real time that has passed since creation or the last reset.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Starts over from tick zero, discarding the elapsed time, lag, and progress towards the
+    /// next tick. Used when a new round begins.
+    #[allow(dead_code)]
+    pub fn reset(&mut self) {
+        self.last_ts = Instant::now();
+        self.tick = 0;
+        self.accumulator = Duration::ZERO;
+        self.lag = Duration::ZERO;
+        self.elapsed = Duration::ZERO;
+    }
+
     /// Returns the current tick count.
     #[inline]
     pub fn tick(&self) -> u64 {
         self.tick
     }
 
-    /// Blocks until the next tick is due, and updates the tick count.
-    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    /// Fractional progress in `[0, 1)` from the last tick towards the next one.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick_duration.as_secs_f32()
+    }
+
+    /// Returns how far behind schedule the ticks are.
+    #[inline]
+    pub fn lag(&self) -> Duration {
+        self.lag
+    }
+
+    /// Checks if a tick overran its duration and the lag has not been caught up yet.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn is_behind(&self) -> bool {
+        !self.lag.is_zero()
+    }
+
+    /// Checks if the lag is more than `ticks` whole ticks.
+    #[inline]
+    pub fn is_behind_by(&self, ticks: u32) -> bool {
+        self.lag > self.tick_duration * ticks
+    }
+
+    /// Adds the elapsed time to the accumulator and consumes every whole tick within it.
+    /// Returns the number of ticks that passed.
+    pub fn advance(&mut self, elapsed: Duration) -> u64 {
+        self.accumulator += elapsed;
+        self.elapsed += elapsed;
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_duration {
+            self.accumulator -= self.tick_duration;
+            ticks += 1;
+        }
+
+        self.tick += ticks;
+        ticks
+    }
+
+    /// Advances by the time elapsed since the last update without blocking. Returns the number of
+    /// ticks that passed.
+    pub fn update(&mut self) -> u64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_ts);
+        self.last_ts = now;
+        self.advance(elapsed)
+    }
+
+    /// Blocks until the next tick is due, and updates the tick count. The accumulator is emptied
+    /// since the loop is aligned to the start of the new tick.
+    ///
+    /// A tick that took longer than its duration adds the overrun to the lag. A tick that
+    /// finished early uses its spare time to reduce the lag before sleeping the rest.
     pub fn wait(&mut self) {
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_ts);
 
         // Check if we're behind schedule.
-        if elapsed < self.tick_duration {
-            std::thread::sleep(self.tick_duration - elapsed);
+        if let Some(spare) = self.tick_duration.checked_sub(elapsed) {
+            let repaid = spare.min(self.lag);
+            self.lag = self.lag.saturating_sub(repaid);
+            std::thread::sleep(spare.saturating_sub(repaid));
+        } else {
+            let overrun = elapsed.saturating_sub(self.tick_duration);
+            self.lag = (self.lag + overrun).min(self.tick_duration * Self::MAX_LAG_TICKS);
         }
 
         // Keep our tick and timestamp up to date.
-        self.last_ts = Instant::now();
+        let now = Instant::now();
+        self.elapsed += now.duration_since(self.last_ts);
+        self.last_ts = now;
+        self.accumulator = Duration::ZERO;
         self.tick += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpha_ramps_between_ticks() {
+        let mut step = Timestep::new(10.0);
+        assert_eq!(step.advance(Duration::from_millis(25)), 0);
+        assert!((step.alpha() - 0.25).abs() < 1e-3);
+
+        assert_eq!(step.advance(Duration::from_millis(50)), 0);
+        assert!((step.alpha() - 0.75).abs() < 1e-3);
+    }
+
+    #[test]
+    fn alpha_wraps_after_each_tick() {
+        let mut step = Timestep::new(10.0);
+        assert_eq!(step.advance(Duration::from_millis(90)), 0);
+        assert_eq!(step.advance(Duration::from_millis(30)), 1);
+        assert!((step.alpha() - 0.2).abs() < 1e-3);
+
+        // Several ticks at once only leave the remainder.
+        assert_eq!(step.advance(Duration::from_millis(350)), 3);
+        assert!((step.alpha() - 0.7).abs() < 1e-3);
+        assert_eq!(step.tick(), 4);
+        assert!(step.alpha() < 1.0);
+    }
+
+    #[test]
+    fn behind_by_counts_whole_ticks_of_lag() {
+        let mut step = Timestep::new(10.0);
+        step.lag = Duration::from_millis(50);
+        assert!(step.is_behind());
+        assert!(!step.is_behind_by(1));
+
+        step.lag = Duration::from_millis(150);
+        assert!(step.is_behind_by(1));
+        assert!(!step.is_behind_by(2));
+    }
+
+    #[test]
+    fn reset_starts_a_new_round() {
+        let mut step = Timestep::new(10.0);
+        assert_eq!(step.advance(Duration::from_millis(250)), 2);
+        assert_eq!(step.advance(Duration::from_millis(120)), 1);
+        assert_eq!(step.elapsed(), Duration::from_millis(370));
+        step.lag = Duration::from_millis(40);
+
+        step.reset();
+        assert_eq!(step.tick(), 0);
+        assert_eq!(step.elapsed(), Duration::ZERO);
+        assert!(step.alpha() < 1e-6);
+        assert!(!step.is_behind());
+    }
+
+    #[test]
+    fn rate_changes_apply_to_the_next_ticks() {
+        let mut step = Timestep::new(10.0);
+        assert_eq!(step.advance(Duration::from_millis(130)), 1);
+
+        // The leftover progress counts towards the faster ticks.
+        step.set_rate(20.0);
+        assert!((step.fixed_dt() - 0.05).abs() < 1e-6);
+        assert_eq!(step.advance(Duration::from_millis(80)), 2);
+        assert_eq!(step.tick(), 3);
+        assert_eq!(step.elapsed(), Duration::from_millis(210));
+    }
+}
@@ -45,7 +45,7 @@ impl Vec2f {
         )
     }
 
-    /// Scales the vector by a scalar.
+    /// Scales the vector by a scalar. Same as multiplying by the scalar.
     pub fn scale(self, s: f32) -> Vec2f {
         Vec2f(self.0 * s, self.1 * s)
     }
@@ -60,10 +60,53 @@ impl Vec2f {
         }
     }
 
+    /// Scales the vector down to `max` length if it is longer, otherwise it is left unchanged.
+    pub fn clamp_length(self, max: f32) -> Vec2f {
+        if self.length_squared() > max * max {
+            self.with_length(max)
+        } else {
+            self
+        }
+    }
+
+    /// Sets the length of the vector while keeping its direction. Zero vectors have no direction
+    /// and are left unchanged.
+    pub fn with_length(self, len: f32) -> Vec2f {
+        self.normalized() * len
+    }
+
     /// Rounds the components of the vector to the nearest integer.
     pub fn round(self) -> Vec2f {
         Vec2f(self.0.round(), self.1.round())
     }
+
+    /// Obtains the angle of the vector in radians, measured from the positive x-axis.
+    pub fn angle(self) -> f32 {
+        self.1.atan2(self.0)
+    }
+
+    /// Creates a unit vector pointing in the direction of the angle in radians.
+    #[allow(dead_code)]
+    pub fn from_angle(radians: f32) -> Vec2f {
+        let (sin, cos) = radians.sin_cos();
+        Vec2f(cos, sin)
+    }
+
+    /// Rotates the vector counter-clockwise by the angle in radians.
+    pub fn rotate(self, radians: f32) -> Vec2f {
+        if self == Vec2f::ZERO {
+            return Vec2f::ZERO;
+        }
+
+        let (sin, cos) = radians.sin_cos();
+        Vec2f(self.0 * cos - self.1 * sin, self.0 * sin + self.1 * cos)
+    }
+
+    /// Obtains the vector rotated 90 degrees counter-clockwise.
+    #[allow(dead_code)]
+    pub fn perpendicular(self) -> Vec2f {
+        Vec2f(-self.1, self.0)
+    }
 }
 
 impl std::ops::Add for Vec2f {
@@ -96,6 +139,45 @@ impl std::ops::SubAssign for Vec2f {
     }
 }
 
+impl std::ops::Mul<f32> for Vec2f {
+    type Output = Vec2f;
+
+    fn mul(self, s: f32) -> Vec2f {
+        Vec2f(self.0 * s, self.1 * s)
+    }
+}
+
+impl std::ops::MulAssign<f32> for Vec2f {
+    fn mul_assign(&mut self, s: f32) {
+        self.0 *= s;
+        self.1 *= s;
+    }
+}
+
+/// Division follows IEEE 754, so dividing by zero produces infinite (or NaN) components.
+impl std::ops::Div<f32> for Vec2f {
+    type Output = Vec2f;
+
+    fn div(self, s: f32) -> Vec2f {
+        Vec2f(self.0 / s, self.1 / s)
+    }
+}
+
+impl std::ops::DivAssign<f32> for Vec2f {
+    fn div_assign(&mut self, s: f32) {
+        self.0 /= s;
+        self.1 /= s;
+    }
+}
+
+impl std::ops::Neg for Vec2f {
+    type Output = Vec2f;
+
+    fn neg(self) -> Vec2f {
+        Vec2f(-self.0, -self.1)
+    }
+}
+
 impl Eq for Vec2f {}
 
 impl Hash for Vec2f {
@@ -116,3 +198,76 @@ impl From<(f32, f32)> for Vec2f {
         Vec2f(v.0, v.1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+
+    /// Checks that two vectors are equal within floating point error.
+    fn assert_near(actual: Vec2f, expected: Vec2f) {
+        assert!(
+            actual.distance(expected) < 1e-5,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    #[test]
+    fn rotate_turns_counter_clockwise() {
+        assert_near(Vec2f(1.0, 0.0).rotate(FRAC_PI_2), Vec2f(0.0, 1.0));
+        assert_near(Vec2f(1.0, 0.0).rotate(PI), Vec2f(-1.0, 0.0));
+        assert_eq!(Vec2f::ZERO.rotate(FRAC_PI_2), Vec2f::ZERO);
+    }
+
+    #[test]
+    fn angle_round_trips_through_from_angle() {
+        assert!((Vec2f(0.0, 2.0).angle() - FRAC_PI_2).abs() < 1e-6);
+        assert_near(
+            Vec2f::from_angle(Vec2f(3.0, -3.0).angle()),
+            Vec2f(3.0, -3.0).normalized(),
+        );
+        assert_near(Vec2f(2.0, 1.0).perpendicular(), Vec2f(-1.0, 2.0));
+    }
+
+    #[test]
+    fn scalar_operators_match_scale() {
+        let v = Vec2f(1.5, -2.0);
+        assert_eq!(v * 2.0, Vec2f(3.0, -4.0));
+        assert_eq!(v * 2.0, v.scale(2.0));
+        assert_eq!(v / 2.0, Vec2f(0.75, -1.0));
+        assert_eq!(-v, Vec2f(-1.5, 2.0));
+        assert_eq!(-Vec2f::ZERO, Vec2f::ZERO);
+
+        let mut assigned = v;
+        assigned *= 4.0;
+        assert_eq!(assigned, Vec2f(6.0, -8.0));
+        assigned /= 8.0;
+        assert_eq!(assigned, Vec2f(0.75, -1.0));
+    }
+
+    #[test]
+    fn division_by_zero_follows_ieee() {
+        let Vec2f(x, y) = Vec2f(1.0, -1.0) / 0.0;
+        assert_eq!((x, y), (f32::INFINITY, f32::NEG_INFINITY));
+
+        let Vec2f(x, y) = Vec2f(0.0, 2.0) / 0.0;
+        assert!(x.is_nan());
+        assert!(y.is_infinite() && y.is_sign_positive());
+    }
+
+    #[test]
+    fn clamp_length_only_shortens_long_vectors() {
+        assert_near(Vec2f(30.0, -40.0).clamp_length(5.0), Vec2f(3.0, -4.0));
+        assert_eq!(Vec2f(0.3, 0.4).clamp_length(5.0), Vec2f(0.3, 0.4));
+        assert_eq!(Vec2f(3.0, 4.0).clamp_length(5.0), Vec2f(3.0, 4.0));
+        assert_eq!(Vec2f::ZERO.clamp_length(5.0), Vec2f::ZERO);
+    }
+
+    #[test]
+    fn with_length_keeps_the_direction() {
+        assert_near(Vec2f(0.0, -0.5).with_length(2.0), Vec2f(0.0, -2.0));
+        assert_near(Vec2f(3.0, 4.0).with_length(10.0), Vec2f(6.0, 8.0));
+        assert_eq!(Vec2f::ZERO.with_length(2.0), Vec2f::ZERO);
+    }
+}